@@ -61,6 +61,24 @@ pub struct Datastore {
     pub (crate) project_heads : Mutex<Store<ProjectHeads, ProjectId>>,
     pub (crate) project_metadata : Mutex<LinkedStore<Metadata, ProjectId>>,
 
+    /** Issues, pull requests and releases of GitHub projects. 
+     
+        These are only obtained on request (see `GithubItem` and the `github` updater command) and updated incrementally, i.e. only items updated since the latest stored item of the project are requested. 
+     */
+    pub (crate) project_issues : Mutex<LinkedStore<GithubItem, ProjectId>>,
+    pub (crate) project_pull_requests : Mutex<LinkedStore<GithubItem, ProjectId>>,
+    pub (crate) project_releases : Mutex<LinkedStore<GithubItem, ProjectId>>,
+
+    /** Relations of projects to other projects, i.e. forks and projects sharing root commits (see `ProjectRelation`). 
+     */
+    pub (crate) project_relations : Mutex<LinkedStore<ProjectRelation, ProjectId>>,
+
+    /** Root commits of the projects' repositories, each mapped to the first project it was found in. 
+     
+        Loaded from the projects' metadata when first needed and used to detect projects sharing their history. 
+     */
+    project_roots : Mutex<Option<HashMap<SHA, ProjectId>>>,
+
     /** Current and past urls for known projects so that when new projects are added we can check for ambiguity.
      
         TODO take this out of the datastore and into the updater? 
@@ -83,14 +101,32 @@ impl Datastore {
     pub (crate) const PROJECT_UPDATES : &'static str = "project-updates";
     pub (crate) const PROJECT_HEADS : &'static str = "project-heads";
     pub (crate) const PROJECT_METADATA : &'static str = "project-metadata";
+    pub (crate) const PROJECT_ISSUES : &'static str = "project-issues";
+    pub (crate) const PROJECT_PULL_REQUESTS : &'static str = "project-pull-requests";
+    pub (crate) const PROJECT_RELEASES : &'static str = "project-releases";
+    pub (crate) const PROJECT_RELATIONS : &'static str = "project-relations";
     pub (crate) const SAVEPOINTS : &'static str = "savepoints";
 
+    /** Prefix of the savepoints created by substore garbage collection. 
+     
+        Garbage collection rewrites the substore files, so any savepoint older than a garbage collection savepoint can no longer be used to revert the datastore.
+     */
+    pub (crate) const GC_SAVEPOINT_PREFIX : &'static str = "gc-";
+
+    /** Prefix of the savepoints created by table compaction. 
+     
+        Like garbage collection, compaction rewrites the table files so older savepoints can no longer be used to revert the datastore. 
+     */
+    pub (crate) const COMPACT_SAVEPOINT_PREFIX : &'static str = "compact-";
+
 
     /** The version of the datastore. 
      
-        Versions have backwards compatibility, but newer versions may add extra items, or metadata. When new version is executed, all projects & commits and other items are force updated to make sure that all data that should be obtained are obtained. 
+        Versions have backwards compatibility, but newer versions may add extra items, or metadata. When new version is executed, all projects & commits and other items are force updated to make sure that all data that should be obtained are obtained, unless the new version does not require it (see `RepoUpdater::new_version_update`). 
+
+        - 1: project update log records for tombstones, exceeded limits and classified errors
      */
-    pub const VERSION : u16 = 0;
+    pub const VERSION : u16 = 1;
 
     pub const SMALL_PROJECT_THRESHOLD : usize = 10;
 
@@ -115,32 +151,51 @@ impl Datastore {
             project_updates : Mutex::new(LinkedStore::new(root, Datastore::PROJECT_UPDATES, readonly)),
             project_heads : Mutex::new(Store::new(root, Datastore::PROJECT_HEADS, readonly)),
             project_metadata : Mutex::new(LinkedStore::new(root, Datastore::PROJECT_METADATA, readonly)),
+            project_issues : Mutex::new(LinkedStore::new(root, Datastore::PROJECT_ISSUES, readonly)),
+            project_pull_requests : Mutex::new(LinkedStore::new(root, Datastore::PROJECT_PULL_REQUESTS, readonly)),
+            project_releases : Mutex::new(LinkedStore::new(root, Datastore::PROJECT_RELEASES, readonly)),
+            project_relations : Mutex::new(LinkedStore::new(root, Datastore::PROJECT_RELATIONS, readonly)),
+            project_roots : Mutex::new(None),
             project_urls : Mutex::new(HashSet::new()),
 
             substores : Vec::new(),
 
             savepoints : Mutex::new(LinkedStore::new(root, Datastore::SAVEPOINTS, readonly)),
         };
-        // initialize the substores
+        // initialize the substores, recovering from interrupted garbage collections
+        let mut unfinished_gc = Vec::new();
         for store_kind in SplitKindIter::<StoreKind>::new() {
+            let store_path = root_path.join(format!("{:?}", store_kind));
+            if Substore::recover_gc(store_path.to_str().unwrap(), readonly) {
+                unfinished_gc.push(store_kind);
+            }
             ds.substores.push(Substore::new(
-                & root_path.join(format!("{:?}", store_kind)),
+                & store_path,
                 store_kind,
                 readonly
             ));
         }
+        for store_kind in unfinished_gc {
+            if readonly {
+                LOG!("    {:?}: unfinished garbage collection, project heads may be invalid", store_kind);
+            } else {
+                LOG!("    {:?}: updating project heads after interrupted garbage collection", store_kind);
+                ds.finish_gc(store_kind).unwrap();
+            }
+        }
         return ds;
     }
 
     pub (crate) fn verify(& self, task : & updater::TaskStatus) -> Result<usize, std::io::Error> {
         let mut progress = 0;
-        let max_progress = 6;
+        let max_progress = 10;
         task.progress(progress, max_progress);
         let mut items = 0;
         self.projects.lock().unwrap().verify(& mut |_|{
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking projects...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -150,6 +205,7 @@ impl Datastore {
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking project substores...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -159,6 +215,7 @@ impl Datastore {
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking project updates...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -168,6 +225,7 @@ impl Datastore {
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking project heads...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -177,6 +235,29 @@ impl Datastore {
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking project metadata...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
+            }
+            return Ok(());
+        })?;
+        progress += 1;
+        task.progress(progress, max_progress);
+        for kind in GithubItemKind::ALL.iter() {
+            self.github_items(*kind).lock().unwrap().verify(& mut |_|{
+                items += 1;
+                if items % 1000 == 0 {
+                    task.info(format!("{} items, checking project {:?}...", helpers::pretty_value(items), kind));
+                    task.check_cancelled()?;
+                }
+                return Ok(());
+            })?;
+            progress += 1;
+            task.progress(progress, max_progress);
+        }
+        self.project_relations.lock().unwrap().verify(& mut |_|{
+            items += 1;
+            if items % 1000 == 0 {
+                task.info(format!("{} items, checking project relations...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -186,6 +267,7 @@ impl Datastore {
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking savepoints...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -206,15 +288,13 @@ impl Datastore {
      
         The savepoint will contain itself as well, i.e. when restoring the datastore to the savepoint the savepoint definition will remain intact. 
         
-        This is done by first creating dummy savepoint, storing it, thus increasing the savepoints size, creating new savepoint, reverting to the dummy one, and writing the proper savepoint. 
+        This is done by computing the size of the savepoints table after the savepoint is stored, which does not depend on the sizes in the savepoint, before storing it. Unlike storing a dummy savepoint first and reverting it, this does not truncate the savepoints table, which readers may have memory mapped. 
      */
     pub (crate) fn create_and_save_savepoint(& self, name : String) -> Savepoint {
-        let dummy = self.create_savepoint(name.clone());
-        self.savepoints.lock().unwrap().set(0, & dummy);
-        let result = self.create_savepoint(name);
-        self.savepoints.lock().unwrap().revert_to_savepoint(& dummy);
-        *self.savepoints.lock().unwrap() = LinkedStore::new(& self.root, "savepoints", false);
-        self.savepoints.lock().unwrap().set(0, & result);
+        let mut result = self.create_savepoint(name);
+        let mut savepoints = self.savepoints.lock().unwrap();
+        savepoints.savepoint_including_self(& mut result, 0);
+        savepoints.set(0, & result);
         return result;
     }
 
@@ -227,6 +307,10 @@ impl Datastore {
         self.project_updates.lock().unwrap().savepoint(& mut savepoint);
         self.project_heads.lock().unwrap().savepoint(& mut savepoint);
         self.project_metadata.lock().unwrap().savepoint(& mut savepoint);
+        self.project_issues.lock().unwrap().savepoint(& mut savepoint);
+        self.project_pull_requests.lock().unwrap().savepoint(& mut savepoint);
+        self.project_releases.lock().unwrap().savepoint(& mut savepoint);
+        self.project_relations.lock().unwrap().savepoint(& mut savepoint);
         self.savepoints.lock().unwrap().savepoint(& mut savepoint);
         for substore in self.substores.iter() {
             substore.savepoint(& mut savepoint);
@@ -234,6 +318,16 @@ impl Datastore {
         return savepoint;
     }
 
+    /** Writes the headers of the mapping indices with values added since they were last written. 
+     
+        The committed lengths of the tables need no flushing as they are published after every append (see `CommittedLengths`). 
+     */
+    pub (crate) fn flush(& self) {
+        for substore in self.substores.iter() {
+            substore.flush();
+        }
+    }
+
     /** Reverts the datastore to given savepoint. 
      
         Note that this is destructive operation and *will* delete all data *after the savepoint 
@@ -244,6 +338,11 @@ impl Datastore {
         self.project_updates.lock().unwrap().revert_to_savepoint(sp);
         self.project_heads.lock().unwrap().revert_to_savepoint(sp);
         self.project_metadata.lock().unwrap().revert_to_savepoint(sp);
+        self.project_issues.lock().unwrap().revert_to_savepoint(sp);
+        self.project_pull_requests.lock().unwrap().revert_to_savepoint(sp);
+        self.project_releases.lock().unwrap().revert_to_savepoint(sp);
+        self.project_relations.lock().unwrap().revert_to_savepoint(sp);
+        *self.project_roots.lock().unwrap() = None;
         self.savepoints.lock().unwrap().revert_to_savepoint(sp);
         for substore in self.substores.iter() {
             substore.revert_to_savepoint(sp);
@@ -256,6 +355,52 @@ impl Datastore {
             .map(|(_, sp)| sp);
     }
 
+    /** Returns the latest garbage collection or compaction savepoint created after the given savepoint, if any. 
+     
+        If such savepoint exists, the datastore cannot be reverted to the given savepoint as some of its files have been rewritten since. 
+     */
+    pub (crate) fn get_rewrite_savepoint_after(& self, sp : & Savepoint) -> Option<Savepoint> {
+        return self.savepoints.lock().unwrap().iter_all()
+            .filter(|(_, x)| x.time() > sp.time())
+            .filter(|(_, x)| x.name().starts_with(Datastore::GC_SAVEPOINT_PREFIX) || x.name().starts_with(Datastore::COMPACT_SAVEPOINT_PREFIX))
+            .map(|(_, x)| x)
+            .max_by_key(|x| x.time());
+    }
+
+
+    /** Compacts the table of given name, removing its superseded records. 
+     
+        Both the datastore tables and the substore tables (prefixed with the substore kind, such as `Generic-commits-metadata`) can be compacted. Stores keep the latest record of each id, metadata tables the latest record of each key for every id and GitHub item tables the latest record of each item. Relations are only deduplicated. If savepoint is given, history since the savepoint is kept, otherwise only the latest records are. See `Store::compact` and `LinkedStore::compact` for more details. 
+
+        The following tables are refused: projects, whose offsets are referenced by project renames, savepoints, which are needed to revert the datastore, the project update log, whose history is part of the data, and the substore mappings and contents, which are never updated. 
+        
+        Returns the size of the table before and after the compaction. 
+     */
+    pub (crate) fn compact_table(& self, name : & str, since : Option<& Savepoint>) -> Result<(u64, u64), std::io::Error> {
+        match name {
+            Datastore::PROJECT_SUBSTORES => return self.project_substores.lock().unwrap().compact(since),
+            Datastore::PROJECT_HEADS => return self.project_heads.lock().unwrap().compact(since),
+            Datastore::PROJECT_METADATA => return self.project_metadata.lock().unwrap().compact(since, |x| x.key.clone()),
+            Datastore::PROJECT_ISSUES => return self.project_issues.lock().unwrap().compact(since, |x| x.number),
+            Datastore::PROJECT_PULL_REQUESTS => return self.project_pull_requests.lock().unwrap().compact(since, |x| x.number),
+            Datastore::PROJECT_RELEASES => return self.project_releases.lock().unwrap().compact(since, |x| x.number),
+            Datastore::PROJECT_RELATIONS => return self.project_relations.lock().unwrap().compact(since, |x| x.clone()),
+            Datastore::PROJECTS | Datastore::SAVEPOINTS => {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Table {} cannot be compacted", name)));
+            },
+            Datastore::PROJECT_UPDATES => {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Table {} keeps history and cannot be compacted", name)));
+            },
+            _ => {
+                for substore in self.substores.iter() {
+                    if let Some(result) = substore.compact_table(name, since) {
+                        return result;
+                    }
+                }
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Unknown table {}", name)));
+            }
+        }
+    }
 
     // substores --------------------------------------------------------------------------------------------------------
 
@@ -303,6 +448,12 @@ impl Datastore {
         return self.project_updates.lock().unwrap().get(id);
     }
 
+    /** Returns up to `max` latest records of the project's update log, latest first. 
+     */
+    pub fn get_project_log(& self, id : ProjectId, max : usize) -> Vec<ProjectLog> {
+        return self.project_updates.lock().unwrap().iter_id(id).take(max).collect();
+    }
+
     /** Updates the project's update status with a new record. 
      */
     pub fn update_project_update_status(& self, id : ProjectId, status : ProjectLog) {
@@ -327,6 +478,68 @@ impl Datastore {
         });
     }
 
+    /** Moves the project to a new substore, setting its heads to the given value. 
+     
+        Unlike `update_project_substore`, the heads are expected to be valid in the new substore, i.e. the project's data must already be migrated there. Any pending migration of the project is cleared. If the project already belongs to the substore (such as when a pending migration back to the project's substore is finished), only its heads are updated and no store change is logged. 
+     */
+    pub (crate) fn migrate_project_substore(& self, id : ProjectId, store : StoreKind, heads : & ProjectHeads) {
+        if self.get_project_substore(id) != store {
+            self.project_substores.lock().unwrap().set(id, & store);
+            self.project_updates.lock().unwrap().set(id,  & ProjectLog::ChangeStore{
+                time : helpers::now(),
+                version : Datastore::VERSION,
+                new_kind : store,
+            });
+        }
+        self.project_heads.lock().unwrap().set(id, heads);
+        self.clear_project_pending_migration(id);
+    }
+
+    /** Returns the pending migration of the project, if any. 
+     
+        The pending migration consists of the substore the project's data are stored in and the heads valid in that substore. 
+     */
+    pub (crate) fn get_project_pending_migration(& self, id : ProjectId) -> Option<(StoreKind, ProjectHeads)> {
+        let value = self.get_project_metadata(id, Metadata::PENDING_MIGRATION)?;
+        if value.is_empty() {
+            return None;
+        }
+        let json = json::parse(& value).ok()?;
+        let store = StoreKind::from_number(json["store"].as_u64()?);
+        let mut heads = ProjectHeads::new();
+        for (name, head) in json["heads"].entries() {
+            heads.insert(name.to_owned(), (
+                CommitId::from(head[0].as_u64()?),
+                SHA::from_str(head[1].as_str()?).ok()?
+            ));
+        }
+        return Some((store, heads));
+    }
+
+    /** Records a pending migration of the project's data. 
+     
+        When the substore of a project changes, its data should be migrated to the new substore, which requires the new substore to be loaded. If this is not the case the old substore and heads valid in it are stored in the project's metadata so that the migration can be performed later, when the project is updated with the new substore loaded. 
+     */
+    pub (crate) fn set_project_pending_migration(& self, id : ProjectId, store : StoreKind, heads : & ProjectHeads) {
+        let mut json_heads = json::JsonValue::new_object();
+        for (name, (commit_id, hash)) in heads.iter() {
+            let mut head = json::JsonValue::new_array();
+            head.push(u64::from(*commit_id)).unwrap();
+            head.push(hash.to_string()).unwrap();
+            json_heads[name.as_str()] = head;
+        }
+        let mut value = json::JsonValue::new_object();
+        value["store"] = store.to_number().into();
+        value["heads"] = json_heads;
+        self.update_project_metadata_if_differ(id, Metadata::PENDING_MIGRATION.to_owned(), value.to_string());
+    }
+
+    pub (crate) fn clear_project_pending_migration(& self, id : ProjectId) {
+        if self.get_project_pending_migration(id).is_some() {
+            self.update_project_metadata_if_differ(id, Metadata::PENDING_MIGRATION.to_owned(), String::new());
+        }
+    }
+
     /** Returns the latest project heads for given project. 
      */
     pub fn get_project_heads(& self, id : ProjectId) -> Option<ProjectHeads> {
@@ -339,6 +552,42 @@ impl Datastore {
         self.project_heads.lock().unwrap().set(id, heads);
     }
 
+    /** Finishes the garbage collection of given substore after its files have been replaced. 
+     
+        Updates the project heads and pending migrations from the journal left in the substore folder by the garbage collection (see `Substore::write_gc_journal`), drops the history of heads of all projects that ever belonged to the substore and removes the journal. Applying the journal again is harmless, which is how garbage collections interrupted after the substore files have been replaced are finished when the datastore is opened. 
+     */
+    pub (crate) fn finish_gc(& self, store : StoreKind) -> Result<(), std::io::Error> {
+        let root = self.substore(store).root_folder().to_owned();
+        if ! Substore::has_gc_journal(& root) {
+            return Ok(());
+        }
+        for (id, heads) in Store::<ProjectHeads, ProjectId>::new(& root, Substore::GC_HEADS, true).iter() {
+            self.update_project_heads(id, & heads);
+        }
+        for (id, heads) in Store::<ProjectHeads, ProjectId>::new(& root, Substore::GC_MIGRATIONS, true).iter() {
+            self.set_project_pending_migration(id, store, & heads);
+        }
+        self.drop_project_heads_history(& self.projects_ever_in_substore(store))?;
+        return Substore::remove_gc_journal(& root);
+    }
+
+    /** Returns the projects that belong, or at some point belonged, to given substore. 
+     */
+    pub (crate) fn projects_ever_in_substore(& self, store : StoreKind) -> HashSet<ProjectId> {
+        return self.project_substores.lock().unwrap().iter_all()
+            .filter(|(_, kind)| *kind == store)
+            .map(|(id, _)| id)
+            .collect();
+    }
+
+    /** Drops the history of the heads of given projects, keeping only their latest heads. 
+     
+        Used by the garbage collection of a substore, after which the older heads of projects that were stored in the substore refer to commit ids that are no longer valid. Returns the size of the heads table before and after. 
+     */
+    pub (crate) fn drop_project_heads_history(& self, ids : & HashSet<ProjectId>) -> Result<(u64, u64), std::io::Error> {
+        return self.project_heads.lock().unwrap().compact_ids(None, |id| ids.contains(& id));
+    }
+
     /** Returns metadata value for given key and project, if one exists. 
      */
     pub fn get_project_metadata(& self, id : ProjectId, key : & str) -> Option<String> {
@@ -370,6 +619,95 @@ impl Datastore {
         return true;
     }
 
+    /** Returns the table of project items of given kind. 
+     */
+    pub (crate) fn github_items(& self, kind : GithubItemKind) -> & Mutex<LinkedStore<GithubItem, ProjectId>> {
+        match kind {
+            GithubItemKind::Issues => return & self.project_issues,
+            GithubItemKind::PullRequests => return & self.project_pull_requests,
+            GithubItemKind::Releases => return & self.project_releases,
+        }
+    }
+
+    /** Returns true if the given version of the project's item (i.e. its number and time) is already stored. 
+     */
+    pub (crate) fn has_project_github_item(& self, id : ProjectId, kind : GithubItemKind, number : u64, time : i64) -> bool {
+        return self.github_items(kind).lock().unwrap().iter_id(id).any(|x| x.number == number && x.time == time);
+    }
+
+    /** Adds new version of the project's item. 
+     */
+    pub (crate) fn add_project_github_item(& self, id : ProjectId, kind : GithubItemKind, item : & GithubItem) {
+        self.github_items(kind).lock().unwrap().set(id, item);
+    }
+
+    /** Returns the relations of the project to other projects, latest first. 
+     */
+    pub fn get_project_relations(& self, id : ProjectId) -> Vec<ProjectRelation> {
+        return self.project_relations.lock().unwrap().iter_id(id).collect();
+    }
+
+    /** Adds the relation of the project to another project, unless already recorded. 
+     
+        Returns true if the relation was added, false otherwise. 
+     */
+    pub (crate) fn add_project_relation(& self, id : ProjectId, relation : ProjectRelation) -> bool {
+        let mut relations = self.project_relations.lock().unwrap();
+        if relations.iter_id(id).any(|x| x == relation) {
+            return false;
+        }
+        relations.set(id, & relation);
+        return true;
+    }
+
+    /** Returns the root commits of the project's repository found so far. 
+     */
+    pub (crate) fn get_project_roots(& self, id : ProjectId) -> Vec<SHA> {
+        return match self.get_project_metadata(id, Metadata::ROOTS) {
+            Some(roots) => Datastore::parse_roots(& roots),
+            None => Vec::new(),
+        };
+    }
+
+    /** Adds root commits of the project's repository and returns the projects found to share them. 
+     
+        The roots are added to the ones stored in the project's metadata, as heads analyzed by earlier updates are not necessarily fetched again. Each root is attributed to the first project it was found in, the returned projects are those to which the project's roots are attributed, other than the project itself. 
+     */
+    pub (crate) fn add_project_roots(& self, id : ProjectId, roots : & Vec<SHA>) -> Vec<ProjectId> {
+        let mut index = self.project_roots.lock().unwrap();
+        if index.is_none() {
+            let mut loaded = HashMap::new();
+            for (project, mtd) in self.project_metadata.lock().unwrap().iter_all() {
+                if mtd.key == Metadata::ROOTS {
+                    for root in Datastore::parse_roots(& mtd.value) {
+                        loaded.entry(root).or_insert(project);
+                    }
+                }
+            }
+            *index = Some(loaded);
+        }
+        let index = index.as_mut().unwrap();
+        let mut all_roots = self.get_project_roots(id);
+        let mut result = Vec::new();
+        for root in roots.iter() {
+            if ! all_roots.contains(root) {
+                all_roots.push(*root);
+            }
+            let first = *index.entry(*root).or_insert(id);
+            if first != id && ! result.contains(& first) {
+                result.push(first);
+            }
+        }
+        all_roots.sort();
+        let value : Vec<String> = all_roots.iter().map(|x| x.to_string()).collect();
+        self.update_project_metadata_if_differ(id, Metadata::ROOTS.to_owned(), value.join(","));
+        return result;
+    }
+
+    fn parse_roots(roots : & str) -> Vec<SHA> {
+        return roots.split(",").filter_map(|x| SHA::from_str(x).ok()).collect();
+    }
+
     pub (crate) fn project_urls_loaded(& self) -> bool {
         if self.project_urls.lock().unwrap().len() > 0 {
             return true;
@@ -459,6 +797,11 @@ pub (crate) struct Substore {
      */
     loaded : AtomicBool,
     load_mutex : Mutex<()>,
+    /** Guards the substore's data against garbage collection, which replaces them. 
+     
+        Tasks adding data to the substore, or migrating data from it, hold the guard shared while the garbage collection holds it exclusively. Neither waits for the other: the garbage collection is refused while the substore is being updated and updates are postponed while the garbage collection runs (see `Substore::guard_update` and `Substore::guard_gc`). 
+     */
+    gc_guard : RwLock<()>,
 
     /** Commits stored in the dataset. 
      */
@@ -489,6 +832,11 @@ pub (crate) struct Substore {
      */
     pub (crate) users : Mutex<IndirectMapping<String, UserId>>,
     pub (crate) users_metadata : Mutex<LinkedStore<Metadata, UserId>>,
+    /** Canonical identities of the users. 
+     
+        Users identified as the same person (see `task_merge_aliases`) are mapped to the user with the smallest id in their cluster. Users without a record are their own canonical identity. Users migrated to another substore, or garbage collected, keep their canonical identity translated to the new ids, which need not be the smallest in the cluster until the aliases are merged again. 
+     */
+    pub (crate) users_aliases : Mutex<Store<u64, UserId>>,
 
 }
 
@@ -504,6 +852,15 @@ impl Substore {
     pub (crate) const PATHS_STRINGS : &'static str = "path-strings";
     pub (crate) const USERS : &'static str = "users";
     pub (crate) const USERS_METADATA : &'static str = "users-metadata";
+    pub (crate) const USERS_ALIASES : &'static str = "users-aliases";
+
+    /** Journal of the garbage collection, written into the folder of the collected substore (see `Substore::write_gc_journal`). 
+     
+        The journal consists of two stores with the new heads of the projects in the substore and of pending migrations from the substore, and of a marker file created once the stores have been written. 
+     */
+    pub (crate) const GC_HEADS : &'static str = "gc-heads";
+    pub (crate) const GC_MIGRATIONS : &'static str = "gc-migrations";
+    pub (crate) const GC_JOURNAL : &'static str = "gc-journal";
     
 
     pub fn new(root_path : & Path, kind : StoreKind, readonly : bool) -> Substore {
@@ -519,6 +876,7 @@ impl Substore {
             prefix : kind,
             loaded : AtomicBool::new(false),
             load_mutex : Mutex::new(()), 
+            gc_guard : RwLock::new(()),
 
             commits : Mutex::new(Mapping::new(root, & format!("{:?}-{}", kind, Substore::COMMITS), readonly)),
             commits_info : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::COMMITS_INFO), readonly)),
@@ -533,6 +891,7 @@ impl Substore {
 
             users : Mutex::new(IndirectMapping::new(root, & format!("{:?}-{}", kind, Substore::USERS), readonly)),
             users_metadata : Mutex::new(LinkedStore::new(root, & format!("{:?}-{}", kind, Substore::USERS_METADATA), readonly)),
+            users_aliases : Mutex::new(Store::new(root, & format!("{:?}-{}", kind, Substore::USERS_ALIASES), readonly)),
 
         };
         // add sentinels (0 index values) for commits, hashes, paths and users
@@ -545,6 +904,154 @@ impl Substore {
         return result;
     }
 
+    /** Reopens all tables of the substore from its root folder. 
+     
+        The substore is not loaded afterwards. 
+     */
+    fn reopen(& self, readonly : bool) {
+        self.loaded.store(false, Ordering::SeqCst);
+        let root = self.root.as_str();
+        let kind = self.prefix;
+        *self.commits.lock().unwrap() = Mapping::new(root, & format!("{:?}-{}", kind, Substore::COMMITS), readonly);
+        *self.commits_info.lock().unwrap() = Store::new(root, & format!("{:?}-{}", kind, Substore::COMMITS_INFO), readonly);
+        *self.commits_metadata.lock().unwrap() = LinkedStore::new(root, & format!("{:?}-{}", kind, Substore::COMMITS_METADATA), readonly);
+        *self.hashes.lock().unwrap() = Mapping::new(root, & format!("{:?}-{}", kind, Substore::HASHES), readonly);
+        *self.contents.lock().unwrap() = SplitStore::new(root, & format!("{:?}-{}", kind, Substore::CONTENTS), readonly);
+        *self.contents_metadata.lock().unwrap() = LinkedStore::new(root, & format!("{:?}-{}", kind, Substore::CONTENTS_METADATA), readonly);
+        *self.paths.lock().unwrap() = Mapping::new(root, & format!("{:?}-{}", kind, Substore::PATHS), readonly);
+        *self.path_strings.lock().unwrap() = Store::new(root, & format!("{:?}-{}", kind, Substore::PATHS_STRINGS), readonly);
+        *self.users.lock().unwrap() = IndirectMapping::new(root, & format!("{:?}-{}", kind, Substore::USERS), readonly);
+        *self.users_metadata.lock().unwrap() = LinkedStore::new(root, & format!("{:?}-{}", kind, Substore::USERS_METADATA), readonly);
+        *self.users_aliases.lock().unwrap() = Store::new(root, & format!("{:?}-{}", kind, Substore::USERS_ALIASES), readonly);
+    }
+
+    /** Replaces the substore's data with the data of given substore.
+     
+        The given substore must be of the same kind and must live in a different folder on the same filesystem. Its folder is moved in place of the substore's own folder, whose old contents are deleted, and all the tables are reopened. The substore is not loaded afterwards. 
+     */
+    pub (crate) fn replace_with(& self, other : Substore) -> Result<(), std::io::Error> {
+        assert!(other.prefix == self.prefix, "Substore can only be replaced with substore of the same kind");
+        let _x = self.load_mutex.lock().unwrap();
+        let other_root = other.root.clone();
+        std::mem::drop(other);
+        let old_root = format!("{}.old", self.root);
+        std::fs::rename(& self.root, & old_root)?;
+        std::fs::rename(& other_root, & self.root)?;
+        self.reopen(false);
+        std::fs::remove_dir_all(& old_root)?;
+        return Ok(());
+    }
+
+    /** Writes the journal of a garbage collection into given folder of the collected substore. 
+     
+        The journal holds the heads of the projects and pending migrations translated to the ids of the collected substore. It is written before the collected substore replaces the original one so that the heads can be updated even if the garbage collection is interrupted afterwards (see `Datastore::finish_gc`). The journal is only valid once its marker file exists. 
+     */
+    pub (crate) fn write_gc_journal(root : & str, projects : & Vec<(ProjectId, ProjectHeads)>, migrations : & Vec<(ProjectId, ProjectHeads)>) -> Result<(), std::io::Error> {
+        for (name, heads) in [(Substore::GC_HEADS, projects), (Substore::GC_MIGRATIONS, migrations)].iter() {
+            let mut journal = Store::<ProjectHeads, ProjectId>::new(root, name, false);
+            for (id, heads) in heads.iter() {
+                journal.set(*id, heads);
+            }
+            journal.sync_all()?;
+        }
+        std::fs::File::create(format!("{}/{}", root, Substore::GC_JOURNAL))?.sync_all()?;
+        return Ok(());
+    }
+
+    /** Returns true if given substore folder contains a complete garbage collection journal. 
+     */
+    fn has_gc_journal(root : & str) -> bool {
+        return Path::new(& format!("{}/{}", root, Substore::GC_JOURNAL)).exists();
+    }
+
+    /** Removes the garbage collection journal from given folder, the marker file first. 
+     */
+    fn remove_gc_journal(root : & str) -> Result<(), std::io::Error> {
+        std::fs::remove_file(format!("{}/{}", root, Substore::GC_JOURNAL))?;
+        for name in [Substore::GC_HEADS, Substore::GC_MIGRATIONS].iter() {
+            for ext in ["store", "idx", "committed"].iter() {
+                let path = format!("{}/{}.{}", root, name, ext);
+                if Path::new(& path).exists() {
+                    std::fs::remove_file(path)?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    /** Recovers the substore folder after an interrupted garbage collection. 
+     
+        Garbage collection copies the substore into `{root}.gc`, writes its journal there and then renames `{root}` to `{root}.old` and `{root}.gc` to `{root}` (see `task_gc_substore`). A collected copy is removed unless the original folder has already been moved away, in which case the copy is moved in its place. Leftover old folder is removed once the substore folder exists. Readers never change the folders, but refuse to open a substore whose folder has been moved away. 
+
+        Returns true if the substore folder contains a journal that has not yet been applied to the project heads by `Datastore::finish_gc`. 
+     */
+    fn recover_gc(root : & str, readonly : bool) -> bool {
+        let gc_root = format!("{}.gc", root);
+        let old_root = format!("{}.old", root);
+        if ! Path::new(root).exists() && Path::new(& old_root).exists() {
+            if readonly || ! Substore::has_gc_journal(& gc_root) {
+                panic!("Substore {} has been moved to {} by an interrupted garbage collection, open the datastore for writing to recover", root, old_root);
+            }
+            LOG!("    {}: finishing interrupted garbage collection", root);
+            std::fs::rename(& gc_root, root).unwrap();
+        }
+        if readonly {
+            return Substore::has_gc_journal(root);
+        }
+        if Path::new(& gc_root).exists() {
+            LOG!("    {}: removing unfinished garbage collection", root);
+            std::fs::remove_dir_all(& gc_root).unwrap();
+        }
+        if Path::new(& old_root).exists() {
+            std::fs::remove_dir_all(& old_root).unwrap();
+        }
+        return Substore::has_gc_journal(root);
+    }
+
+    /** Compacts the substore table of given name, if it belongs to the substore. 
+     
+        Returns None if the substore has no such table. Only stores can be compacted, mappings and contents are never updated.
+     */
+    fn compact_table(& self, name : & str, since : Option<& Savepoint>) -> Option<Result<(u64, u64), std::io::Error>> {
+        let prefix = format!("{:?}-", self.prefix);
+        if ! name.starts_with(& prefix) {
+            return None;
+        }
+        match & name[prefix.len()..] {
+            Substore::COMMITS_INFO => return Some(self.commits_info.lock().unwrap().compact(since)),
+            Substore::COMMITS_METADATA => return Some(self.commits_metadata.lock().unwrap().compact(since, |x| x.key.clone())),
+            Substore::CONTENTS_METADATA => return Some(self.contents_metadata.lock().unwrap().compact(since, |x| x.key.clone())),
+            Substore::PATHS_STRINGS => return Some(self.path_strings.lock().unwrap().compact(since)),
+            // users keep all the names they have used (see `Substore::add_user_metadata_if_new`)
+            Substore::USERS_METADATA => return Some(self.users_metadata.lock().unwrap().compact(since, |x| {
+                let value = if x.key == Metadata::USER_NAME { x.value.clone() } else { String::new() };
+                return (x.key.clone(), value);
+            })),
+            Substore::USERS_ALIASES => return Some(self.users_aliases.lock().unwrap().compact(since)),
+            _ => return None,
+        }
+    }
+
+    /** Returns the root folder of the substore. 
+     */
+    pub (crate) fn root_folder(& self) -> & str {
+        return & self.root;
+    }
+
+    /** Returns the size of all substore files on disk in bytes. 
+     */
+    pub (crate) fn disk_size(& self) -> u64 {
+        let mut result = 0;
+        if let Ok(entries) = std::fs::read_dir(& self.root) {
+            for entry in entries.flatten() {
+                if let Ok(md) = entry.metadata() {
+                    result += md.len();
+                }
+            }
+        }
+        return result;
+    }
+
     fn savepoint(& self, savepoint : & mut Savepoint) {
         self.commits.lock().unwrap().savepoint(savepoint);
         self.commits_info.lock().unwrap().savepoint(savepoint);
@@ -556,6 +1063,14 @@ impl Substore {
         self.path_strings.lock().unwrap().savepoint(savepoint);
         self.users.lock().unwrap().savepoint(savepoint);
         self.users_metadata.lock().unwrap().savepoint(savepoint);
+        self.users_aliases.lock().unwrap().savepoint(savepoint);
+    }
+
+    fn flush(& self) {
+        self.commits.lock().unwrap().flush();
+        self.hashes.lock().unwrap().flush();
+        self.paths.lock().unwrap().flush();
+        self.users.lock().unwrap().flush();
     }
 
     fn revert_to_savepoint(& self, savepoint : & Savepoint) {
@@ -569,6 +1084,7 @@ impl Substore {
         self.path_strings.lock().unwrap().revert_to_savepoint(savepoint);
         self.users.lock().unwrap().revert_to_savepoint(savepoint);
         self.users_metadata.lock().unwrap().revert_to_savepoint(savepoint);
+        self.users_aliases.lock().unwrap().revert_to_savepoint(savepoint);
     }
 
     pub (crate) fn load(& self, task : & updater::TaskStatus) {
@@ -611,16 +1127,29 @@ impl Substore {
         return self.loaded.load(Ordering::SeqCst);
     }
 
+    /** Returns the guard for updating the substore, or None if the substore is being garbage collected. 
+     */
+    pub (crate) fn guard_update(& self) -> Option<RwLockReadGuard<'_, ()>> {
+        return self.gc_guard.try_read().ok();
+    }
+
+    /** Returns the guard for garbage collecting the substore, or None if the substore is being updated. 
+     */
+    pub (crate) fn guard_gc(& self) -> Option<RwLockWriteGuard<'_, ()>> {
+        return self.gc_guard.try_write().ok();
+    }
+
     pub (crate) fn verify(& self, task : & updater::TaskStatus) -> Result<usize, std::io::Error> {
         self.load(task);
         let mut progress = 0;
-        let max_progress = 10;
+        let max_progress = 11;
         task.progress(progress, max_progress);
         let mut items = 0;
         self.commits.lock().unwrap().verify(& mut |_|{
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking commits...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -630,6 +1159,7 @@ impl Substore {
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking commits info...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -639,6 +1169,7 @@ impl Substore {
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking commits metadata...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -648,6 +1179,7 @@ impl Substore {
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking hashes ...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -657,6 +1189,7 @@ impl Substore {
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking contents ...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -666,6 +1199,7 @@ impl Substore {
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking contents metadata ...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -675,6 +1209,7 @@ impl Substore {
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking paths ...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -684,6 +1219,7 @@ impl Substore {
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking path strings ...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -693,6 +1229,7 @@ impl Substore {
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking users ...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -702,6 +1239,17 @@ impl Substore {
             items += 1;
             if items % 1000 == 0 {
                 task.info(format!("{} items, checking users metadata ...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
+            }
+            return Ok(());
+        })?;
+        progress += 1;
+        task.progress(progress, max_progress);
+        self.users_aliases.lock().unwrap().verify(& mut |_|{
+            items += 1;
+            if items % 1000 == 0 {
+                task.info(format!("{} items, checking users aliases ...", helpers::pretty_value(items)));
+                task.check_cancelled()?;
             }
             return Ok(());
         })?;
@@ -712,7 +1260,7 @@ impl Substore {
 
     /** Returns the memory report for the substore. 
      
-        This is either an empty string if the substore is not loaded, or the name of the substore and the total number of mappings in memory the substore holds. 
+        This is either an empty string if the substore is not loaded, or the name of the substore and the total number of values in the substore's mapping indices. 
      */
     pub (crate) fn memory_report(& self) -> String {
         if self.is_loaded() {
@@ -726,6 +1274,26 @@ impl Substore {
         }
     }
 
+    /** Returns the estimated number of bytes needed to keep all values of the substore's mappings cached in memory, as an update of the substore may need. 
+     
+        The estimate is calculated from the values stored on disk so that it is available for substores that are not loaded as well (see `Mapping::memory_estimate`). 
+     */
+    pub (crate) fn memory_estimate(& self) -> usize {
+        return self.commits.lock().unwrap().memory_estimate()
+            + self.hashes.lock().unwrap().memory_estimate()
+            + self.paths.lock().unwrap().memory_estimate()
+            + self.users.lock().unwrap().memory_estimate();
+    }
+
+    /** Returns the number of bytes taken by the values currently cached in the substore's mappings, 0 if the substore is not loaded. 
+     */
+    pub (crate) fn memory_size(& self) -> usize {
+        return self.commits.lock().unwrap().memory_size()
+            + self.hashes.lock().unwrap().memory_size()
+            + self.paths.lock().unwrap().memory_size()
+            + self.users.lock().unwrap().memory_size();
+    }
+
     /** Returns and id of given commit. 
      
         The secord returned value determines whether the commit is new,  or already known.
@@ -734,6 +1302,28 @@ impl Substore {
         return self.commits.lock().unwrap().get_or_create_mapping(hash);
     }
 
+    /** Returns the id of given commit, if known. 
+     
+        The lookup uses the mapping's hash index so the substore does not have to be loaded. 
+     */
+    pub (crate) fn get_commit_id(& self, hash : & SHA) -> Option<CommitId> {
+        return self.commits.lock().unwrap().get_mapping(hash);
+    }
+
+    /** Returns the information about given commit, if stored. 
+     
+        The commit information is read from disk so the substore does not have to be loaded. 
+     */
+    pub (crate) fn get_commit_info(& self, id : CommitId) -> Option<CommitInfo> {
+        return self.commits_info.lock().unwrap().get(id);
+    }
+
+    /** Returns the hash of given commit. 
+     */
+    pub (crate) fn get_commit_hash(& self, id : CommitId) -> Option<SHA> {
+        return self.commits.lock().unwrap().get(id);
+    }
+
     pub (crate) fn add_commit_info_if_missing(& self, id : CommitId, commit_info : & CommitInfo) {
         let mut cinfo = self.commits_info.lock().unwrap();
         if ! cinfo.has(id) {
@@ -790,4 +1380,73 @@ impl Substore {
         return self.users.lock().unwrap().get_or_create_mapping(email);
     }
 
+    /** Returns the email of given user. 
+     */
+    pub (crate) fn get_user_email(& self, id : UserId) -> Option<String> {
+        return self.users.lock().unwrap().get(id);
+    }
+
+    /** Returns metadata value for given key and user, if one exists. 
+     */
+    pub (crate) fn get_user_metadata(& self, id : UserId, key : & str) -> Option<String> {
+        let mut metadata = self.users_metadata.lock().unwrap();
+        for kv in metadata.iter_id(id) {
+            if kv.key == key {
+                return Some(kv.value);
+            }
+        }
+        return None;
+    }
+
+    /** Updates user metadata value for given key if the last stored value differs. 
+     
+        Returns true if the value was updated, false otherwise.
+     */
+    pub (crate) fn update_user_metadata_if_differ(& self, id : UserId, key : String, value : String) -> bool {
+        let mut metadata = self.users_metadata.lock().unwrap();
+        for kv in metadata.iter_id(id) {
+            if kv.key == key {
+                if kv.value == value {
+                    return false;
+                } else {
+                    break;
+                }
+            }
+        }
+        metadata.set(id, & Metadata{key, value });
+        return true;
+    }
+
+    /** Adds user metadata value for given key unless the same value has been stored for the key before. 
+     
+        Unlike `update_user_metadata_if_differ`, all values ever stored for the key are compared, which is used for keys whose every value is valid, such as the names the user uses. Returns true if the value was added, false otherwise. 
+     */
+    pub (crate) fn add_user_metadata_if_new(& self, id : UserId, key : String, value : String) -> bool {
+        let mut metadata = self.users_metadata.lock().unwrap();
+        if metadata.iter_id(id).any(|kv| kv.key == key && kv.value == value) {
+            return false;
+        }
+        metadata.set(id, & Metadata{key, value });
+        return true;
+    }
+
+    /** Returns the canonical identity of given user. 
+     */
+    pub (crate) fn get_user_canonical(& self, id : UserId) -> UserId {
+        return self.users_aliases.lock().unwrap().get(id).map(UserId::from).unwrap_or(id);
+    }
+
+    /** Sets the canonical identity of given user if it differs from the stored one. 
+     
+        Returns true if the canonical identity was updated, false otherwise. 
+     */
+    pub (crate) fn update_user_canonical_if_differ(& self, id : UserId, canonical : UserId) -> bool {
+        let mut aliases = self.users_aliases.lock().unwrap();
+        if aliases.get(id).map(UserId::from).unwrap_or(id) == canonical {
+            return false;
+        }
+        aliases.set(id, & u64::from(canonical));
+        return true;
+    }
+
 }
@@ -23,14 +23,17 @@ pub (crate) fn task_add_projects(ds : & Datastore, source : String,  task : Task
     return Ok(());
 }
 
-fn add_project(ds : & Datastore, url : & str, added : & mut usize, existing : & mut usize, invalid : & mut usize) {
+/** Adds the project of given url and returns its id if it was added. 
+ */
+fn add_project(ds : & Datastore, url : & str, added : & mut usize, existing : & mut usize, invalid : & mut usize) -> Option<ProjectId> {
     match ProjectUrl::from_url(url) {
         Some(project) => {
             match ds.add_project(& project) {
-                Some(_id) => {
+                Some(id) => {
                     // don't actually schedule the update, it has to be explicitly enabled by the user
-                    //updater.schedule(Task::UpdateRepo{ id, last_update_time : Updater::NEVER });
+                    //updater.schedule(Task::UpdateRepo{ id, due_time : Updater::NEVER });
                     *added += 1;
+                    return Some(id);
                 },
                 _ => {
                     *existing += 1;
@@ -39,6 +42,7 @@ fn add_project(ds : & Datastore, url : & str, added : & mut usize, existing : &
         }, 
         None => *invalid += 1,
     }
+    return None;
 } 
 
 fn add_projects_from_csv(ds : & Datastore, source : String, task : & TaskStatus, added : & mut usize, existing : & mut usize, invalid : & mut usize) -> Result<(), std::io::Error>{
@@ -48,6 +52,8 @@ fn add_projects_from_csv(ds : & Datastore, source : String, task : & TaskStatus,
         .escape(Some(b'\\'))
         .from_path(source)?;
     let headers = reader.headers()?;
+    // forks are marked as such if the source has a fork column (such as the scrapper output)
+    let col_fork = headers.iter().position(|x| x == "fork");
     let mut col_id = if let Some(id) = find_repo_url_column(& headers) {
         add_project(ds, & headers[id], added, existing, invalid);
         id
@@ -63,7 +69,11 @@ fn add_projects_from_csv(ds : & Datastore, source : String, task : & TaskStatus,
                 return Err(std::io::Error::new(std::io::ErrorKind::Other, "Cannot determine column containing project urls"));
             }
         }
-        add_project(ds, & record[col_id], added, existing, invalid);
+        if let Some(id) = add_project(ds, & record[col_id], added, existing, invalid) {
+            if let Some(fork) = col_fork.and_then(|i| record.get(i)) {
+                ds.update_project_metadata_if_differ(id, Metadata::FORK.to_owned(), (fork == "1" || fork == "true").to_string());
+            }
+        }
         if (*added + *existing + *invalid) % 1000 == 0 {
             task.info(format!("{} added, {} existing, {} invalid, using column {}", added, existing, invalid, col_id));
         }
@@ -110,6 +120,30 @@ pub (crate) fn task_create_savepoint(ds : & Datastore, task : TaskStatus) -> Res
     return Ok(());
 }
 
+/** Compacts given table, optionally keeping its history since a savepoint. 
+ 
+    Once compacted, a new savepoint is created as the compaction invalidates all older savepoints. 
+ */
+pub (crate) fn task_compact_table(ds : & Datastore, task : TaskStatus) -> Result<(), std::io::Error> {
+    if let Task::CompactTable{table, savepoint} = & task.task {
+        let since = match savepoint {
+            Some(name) => match ds.get_savepoint(name) {
+                Some(sp) => Some(sp),
+                None => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Unknown savepoint {}", name))),
+            },
+            None => None,
+        };
+        task.extra(table);
+        task.info("compacting...");
+        let (before, after) = ds.compact_table(table, since.as_ref())?;
+        let sp = ds.create_and_save_savepoint(format!("{}{}-{}", Datastore::COMPACT_SAVEPOINT_PREFIX, table, helpers::now()));
+        task.info(format!("{} -> {}, reclaimed {}, savepoint {}", helpers::pretty_size(before), helpers::pretty_size(after), helpers::pretty_size(before.saturating_sub(after)), sp.name()));
+    } else {
+        panic!("Invalid task kind");
+    }
+    return Ok(());
+}
+
 pub (crate) fn task_load_substore(ds : & Datastore, store : StoreKind,  task : TaskStatus) -> Result<(), std::io::Error> {
     ds.substore(store).load(& task);
     task.info(format!("{:?}", store));
@@ -51,10 +51,10 @@ impl Id for u64 {
 pub trait Serializable {
     type Item;
 
-    fn serialize(f : & mut File, value : & Self::Item);
-    fn deserialize(f : & mut File) -> Self::Item;
+    fn serialize<W : Write>(f : & mut W, value : & Self::Item);
+    fn deserialize<R : Read>(f : & mut R) -> Self::Item;
 
-    fn verify(f : & mut File) -> Result<Self::Item, std::io::Error>;
+    fn verify<R : Read>(f : & mut R) -> Result<Self::Item, std::io::Error>;
 }
 
 pub trait FixedSizeSerializable : Serializable {
@@ -69,14 +69,14 @@ pub trait Indexable : FixedSizeSerializable + Eq {
  */
 impl Serializable for u64 {
     type Item = u64;
-    fn serialize(f : & mut File, value : & u64) {
+    fn serialize<W : Write>(f : & mut W, value : & u64) {
         f.write_u64::<LittleEndian>(*value).unwrap();
     }
-    fn deserialize(f : & mut File) -> u64 {
+    fn deserialize<R : Read>(f : & mut R) -> u64 {
         return f.read_u64::<LittleEndian>().unwrap();
     }
 
-    fn verify(f : & mut File) -> Result<u64, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<u64, std::io::Error> {
         return f.read_u64::<LittleEndian>();
     }
 
@@ -92,14 +92,14 @@ impl Indexable for u64 {
 
 impl Serializable for i64 {
     type Item = i64;
-    fn serialize(f : & mut File, value : & i64) {
+    fn serialize<W : Write>(f : & mut W, value : & i64) {
         f.write_i64::<LittleEndian>(*value).unwrap();
     }
-    fn deserialize(f : & mut File) -> i64 {
+    fn deserialize<R : Read>(f : & mut R) -> i64 {
         return f.read_i64::<LittleEndian>().unwrap();
     }
 
-    fn verify(f : & mut File) -> Result<i64, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<i64, std::io::Error> {
         return f.read_i64::<LittleEndian>();
     }
 }
@@ -110,14 +110,14 @@ impl FixedSizeSerializable for i64 {
 
 impl Serializable for u32 {
     type Item = u32;
-    fn serialize(f : & mut File, value : & u32) {
+    fn serialize<W : Write>(f : & mut W, value : & u32) {
         f.write_u32::<LittleEndian>(*value).unwrap();
     }
-    fn deserialize(f : & mut File) -> u32 {
+    fn deserialize<R : Read>(f : & mut R) -> u32 {
         return f.read_u32::<LittleEndian>().unwrap();
     }
 
-    fn verify(f : & mut File) -> Result<u32, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<u32, std::io::Error> {
         return f.read_u32::<LittleEndian>();
     }
 
@@ -129,14 +129,14 @@ impl FixedSizeSerializable for u32 {
 
 impl Serializable for u16 {
     type Item = u16;
-    fn serialize(f : & mut File, value : & u16) {
+    fn serialize<W : Write>(f : & mut W, value : & u16) {
         f.write_u16::<LittleEndian>(*value).unwrap();
     }
-    fn deserialize(f : & mut File) -> u16 {
+    fn deserialize<R : Read>(f : & mut R) -> u16 {
         return f.read_u16::<LittleEndian>().unwrap();
     }
 
-    fn verify(f : & mut File) -> Result<u16, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<u16, std::io::Error> {
         return f.read_u16::<LittleEndian>();
     }
 
@@ -148,13 +148,13 @@ impl FixedSizeSerializable for u16 {
 
 impl Serializable for u8 {
     type Item = u8;
-    fn serialize(f : & mut File, value : & u8) {
+    fn serialize<W : Write>(f : & mut W, value : & u8) {
         f.write_u8(*value).unwrap();
     }
-    fn deserialize(f : & mut File) -> u8 {
+    fn deserialize<R : Read>(f : & mut R) -> u8 {
         return f.read_u8().unwrap();
     }
-    fn verify(f : & mut File) -> Result<u8, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<u8, std::io::Error> {
         return f.read_u8();
     }
 
@@ -169,12 +169,12 @@ impl FixedSizeSerializable for u8 {
 impl Serializable for String {
     type Item = String;
 
-    fn serialize(f : & mut File, value : & String) {
+    fn serialize<W : Write>(f : & mut W, value : & String) {
         f.write_u32::<LittleEndian>(value.len() as u32).unwrap();
         f.write(value.as_bytes()).unwrap();
     }
 
-    fn deserialize(f : & mut File) -> String {
+    fn deserialize<R : Read>(f : & mut R) -> String {
         let len = f.read_u32::<LittleEndian>().unwrap();
         let mut buf = vec![0; len as usize];
         if f.read(& mut buf).unwrap() as u32 != len {
@@ -182,7 +182,7 @@ impl Serializable for String {
         }
         return String::from_utf8(buf).unwrap();
     }
-    fn verify(f : & mut File) -> Result<String, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<String, std::io::Error> {
         let len = u32::verify(f)?;
         if len as u64 > MAX_BUFFER_LENGTH {
             return Err(std::io::Error::new(std::io::ErrorKind::Other, "Invalid buffer size"));
@@ -237,6 +237,310 @@ impl<T : Table> Iterator for TableOwningIterator<T> {
     }
 }
 
+/** Determines how the table files are accessed. 
+ 
+    The datastore opens its tables for reading and writing, while views into the datastore open them for reading only. Read only tables can also be memory mapped, which is much faster for random access heavy workloads as seeks and reads of the table files do not require any syscalls. 
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessMode {
+    ReadWrite,
+    ReadOnly,
+    Mapped,
+}
+
+impl AccessMode {
+    pub fn from_readonly(readonly : bool) -> AccessMode {
+        return if readonly { AccessMode::ReadOnly } else { AccessMode::ReadWrite };
+    }
+
+    pub fn is_readonly(& self) -> bool {
+        return * self != AccessMode::ReadWrite;
+    }
+}
+
+/** File backing a table. 
+ 
+    Either an ordinary file, or a read only memory mapped file. Memory mapped files map the data present when they were opened, anything appended later is read from the file itself. Writing to them or changing their length is an error. The mapped files must not be truncated while mapped (see `DataFile::open`). 
+
+    The data file can also be limited to given length, in which case it behaves as if the file ended at the limit. This is used by readers to only see the committed parts of the tables (see `CommittedLengths`). The position in the file is tracked by the data file itself so that the limit can be checked without extra syscalls. 
+ */
+pub struct DataFile {
+    backend : DataFileBackend,
+    pos : u64,
+    limit : u64,
+    /* Length of the file when last checked, only used for limited and memory mapped files. */
+    len : u64,
+}
+
+enum DataFileBackend {
+    File(File),
+    /* The map is None for empty files, which cannot be mapped. */
+    Mapped(File, Option<memmap2::Mmap>),
+}
+
+impl DataFile {
+    pub fn open(path : & str, mode : AccessMode) -> Result<DataFile, std::io::Error> {
+        let backend = match mode {
+            AccessMode::ReadWrite => DataFileBackend::File(OpenOptions::new().read(true).write(true).create(true).open(path)?),
+            AccessMode::ReadOnly => DataFileBackend::File(OpenOptions::new().read(true).open(path)?),
+            AccessMode::Mapped => {
+                let f = OpenOptions::new().read(true).open(path)?;
+                if f.metadata()?.len() == 0 {
+                    DataFileBackend::Mapped(f, None)
+                } else {
+                    // accessing mapped range that has been truncated since is undefined behavior (SIGBUS on Linux), see the notes in memmap2 on the safety of file mappings. Compaction, garbage collection and hash index rebuilds replace the files instead of truncating them, so the mapped files stay intact, and savepoints are created without truncating the savepoints table. The only truncation is reverting to a savepoint, which must not be done while the datastore is read through memory mapped tables
+                    let mmap = unsafe { memmap2::Mmap::map(& f)? };
+                    DataFileBackend::Mapped(f, Some(mmap))
+                }
+            },
+        };
+        return Ok(DataFile{ backend, pos : 0, limit : u64::MAX, len : 0 });
+    }
+
+    /** Returns the limit of the file, u64::MAX if the file is not limited. 
+     */
+    pub fn limit(& self) -> u64 {
+        return self.limit;
+    }
+
+    /** Limits the file to given length. 
+     */
+    pub fn set_limit(& mut self, limit : u64) {
+        self.limit = limit;
+    }
+
+    /** Executes given function with the limit temporarily lifted. 
+     
+        This is useful for following index entries updated after the limit has been set as the records they point to are guaranteed to be complete. 
+     */
+    pub fn unlimited<R>(& mut self, f : impl FnOnce(& mut DataFile) -> R) -> R {
+        let limit = self.limit;
+        self.limit = u64::MAX;
+        let result = f(self);
+        self.limit = limit;
+        return result;
+    }
+
+    pub fn set_len(& self, size : u64) -> Result<(), std::io::Error> {
+        match & self.backend {
+            DataFileBackend::File(f) => return f.set_len(size),
+            DataFileBackend::Mapped(..) => return Err(DataFile::mapped_error()),
+        }
+    }
+
+    pub fn sync_all(& self) -> Result<(), std::io::Error> {
+        match & self.backend {
+            DataFileBackend::File(f) => return f.sync_all(),
+            DataFileBackend::Mapped(..) => return Ok(()),
+        }
+    }
+
+    /** Returns the end of the file, taking the limit into account. 
+     
+        The length of the file is only checked if the last known length is below the limit, as the files only grow while they are limited. 
+     */
+    fn end(& mut self) -> std::io::Result<u64> {
+        if self.len < self.limit {
+            self.len = match & self.backend {
+                DataFileBackend::File(f) => f.metadata()?.len(),
+                DataFileBackend::Mapped(f, _) => f.metadata()?.len(),
+            };
+        }
+        return Ok(std::cmp::min(self.len, self.limit));
+    }
+
+    fn mapped_error() -> std::io::Error {
+        return std::io::Error::new(std::io::ErrorKind::Other, "Memory mapped table files are read only");
+    }
+}
+
+impl Read for DataFile {
+    fn read(& mut self, buf : & mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.limit {
+            return Ok(0);
+        }
+        let max_len = std::cmp::min(buf.len() as u64, self.limit - self.pos) as usize;
+        let len = match & mut self.backend {
+            DataFileBackend::File(f) => f.read(& mut buf[..max_len])?,
+            DataFileBackend::Mapped(f, mmap) => {
+                let data : & [u8] = match mmap {
+                    Some(mmap) => & mmap[..],
+                    None => & [],
+                };
+                use std::os::unix::fs::FileExt;
+                if self.pos < data.len() as u64 {
+                    let start = self.pos as usize;
+                    let len = std::cmp::min(max_len, data.len() - start);
+                    buf[..len].copy_from_slice(& data[start..(start + len)]);
+                    // records crossing the end of the mapped data are completed from the file as the deserializers expect a single read to fill the buffer
+                    if len < max_len {
+                        len + f.read_at(& mut buf[len..max_len], self.pos + len as u64)?
+                    } else {
+                        len
+                    }
+                } else {
+                    // data appended after the file has been mapped
+                    f.read_at(& mut buf[..max_len], self.pos)?
+                }
+            }
+        };
+        self.pos += len as u64;
+        return Ok(len);
+    }
+}
+
+impl Write for DataFile {
+    fn write(& mut self, buf : & [u8]) -> std::io::Result<usize> {
+        match & mut self.backend {
+            DataFileBackend::File(f) => {
+                let len = f.write(buf)?;
+                self.pos += len as u64;
+                return Ok(len);
+            },
+            DataFileBackend::Mapped(..) => return Err(DataFile::mapped_error()),
+        }
+    }
+
+    fn flush(& mut self) -> std::io::Result<()> {
+        match & mut self.backend {
+            DataFileBackend::File(f) => return f.flush(),
+            DataFileBackend::Mapped(..) => return Ok(()),
+        }
+    }
+}
+
+impl Seek for DataFile {
+    fn seek(& mut self, from : SeekFrom) -> std::io::Result<u64> {
+        // seeking to the current position is used to determine the position, no need to touch the file
+        if let SeekFrom::Current(0) = from {
+            return Ok(self.pos);
+        }
+        if let DataFileBackend::File(f) = & mut self.backend {
+            if self.limit == u64::MAX {
+                self.pos = f.seek(from)?;
+                return Ok(self.pos);
+            }
+        }
+        // only seeking from the end needs the length of the file
+        let new_pos = match from {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.end()? as i64 + offset,
+            SeekFrom::Current(offset) => self.pos as i64 + offset,
+        };
+        if new_pos < 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Seek before the start of the file"));
+        }
+        if let DataFileBackend::File(f) = & mut self.backend {
+            f.seek(SeekFrom::Start(new_pos as u64))?;
+        }
+        self.pos = new_pos as u64;
+        return Ok(self.pos);
+    }
+}
+
+/** Committed lengths of the files of a table. 
+ 
+    Tables are appended to in multiple steps (the data first, then the index), so a reader that opens the table files while the datastore is being updated could observe a partially written record, or an index entry whose data are not yet available. To prevent this, writers publish the lengths of all table files in a `NAME.committed` file at consistent points, i.e. when both data and index have been written. The lengths are published after every append, which only rewrites the small header of the file, so that readers never lag behind the writer. Readers then limit themselves to the committed lengths, which gives them a consistent view of the table. The consistency is per table only, tables are committed independently of each other so that a record of one table may refer to records not yet committed in another table. 
+
+    The lengths are written by a single positioned write together with their checksum so that readers can detect an update in progress and retry. 
+ */
+pub struct CommittedLengths {
+    /* The committed file, only available to writers. */
+    f : Option<File>,
+    lengths : Vec<u64>,
+}
+
+impl CommittedLengths {
+
+    /** Number of attempts a reader makes to get consistent committed lengths. 
+     */
+    const READ_ATTEMPTS : usize = 10;
+
+    /** Creates the committed lengths for given table and file lengths. 
+     
+        Writers publish the lengths immediately, readers only keep them. 
+     */
+    pub fn open(root : & str, name : & str, mode : AccessMode, lengths : Vec<u64>) -> CommittedLengths {
+        if mode.is_readonly() {
+            return CommittedLengths{ f : None, lengths };
+        }
+        let f = OpenOptions::new().write(true).create(true).open(CommittedLengths::filename(root, name)).unwrap();
+        let mut result = CommittedLengths{ f : Some(f), lengths };
+        result.publish();
+        return result;
+    }
+
+    /** Reads the committed lengths of given table. 
+     
+        Must be called *before* the table's files are opened so that the committed lengths never exceed what the reader sees. Returns None if the table has no committed lengths (such as tables created by older versions, or tables that have never been opened for writing), or if consistent lengths could not be read. 
+     */
+    pub fn read(root : & str, name : & str, count : usize) -> Option<Vec<u64>> {
+        for _ in 0..CommittedLengths::READ_ATTEMPTS {
+            let data = match std::fs::read(CommittedLengths::filename(root, name)) {
+                Ok(data) => data,
+                Err(_) => return None,
+            };
+            if let Some(lengths) = CommittedLengths::decode(& data, count) {
+                return Some(lengths);
+            }
+            std::thread::yield_now();
+        }
+        LOG!("    {}: unable to read committed lengths, using actual file lengths", name);
+        return None;
+    }
+
+    pub fn get(& self, index : usize) -> u64 {
+        return self.lengths[index];
+    }
+
+    pub fn set(& mut self, index : usize, length : u64) {
+        self.lengths[index] = length;
+    }
+
+    /** Records a consistent append by publishing the lengths. 
+     */
+    pub fn commit(& mut self) {
+        self.publish();
+    }
+
+    /** Publishes the committed lengths. Does nothing for readers. 
+     */
+    pub fn publish(& mut self) {
+        if let Some(f) = & self.f {
+            let mut buffer = Vec::with_capacity((self.lengths.len() + 2) * 8);
+            buffer.write_u64::<LittleEndian>(self.lengths.len() as u64).unwrap();
+            for length in self.lengths.iter() {
+                buffer.write_u64::<LittleEndian>(*length).unwrap();
+            }
+            buffer.write_u64::<LittleEndian>(CommittedLengths::checksum(& self.lengths)).unwrap();
+            use std::os::unix::fs::FileExt;
+            f.write_all_at(& buffer, 0).unwrap();
+        }
+    }
+
+    fn decode(mut data : & [u8], count : usize) -> Option<Vec<u64>> {
+        if data.read_u64::<LittleEndian>().ok()? as usize != count {
+            return None;
+        }
+        let mut lengths = Vec::with_capacity(count);
+        for _ in 0..count {
+            lengths.push(data.read_u64::<LittleEndian>().ok()?);
+        }
+        if data.read_u64::<LittleEndian>().ok()? != CommittedLengths::checksum(& lengths) {
+            return None;
+        }
+        return Some(lengths);
+    }
+
+    fn filename(root : & str, name : & str) -> String {
+        return format!("{}/{}.committed", root, name);
+    }
+
+    fn checksum(lengths : & [u64]) -> u64 {
+        return lengths.iter().fold(0xcbf29ce484222325u64, |hash, length| (hash ^ length).wrapping_mul(0x100000001b3));
+    }
+}
+
 /** Holds indices for each id.
 
     The idsn are expected to be mostly consecutive, i.e. if an id `N` is added all ids from `0` to `N-1` either must exist, or will be created. 
@@ -244,8 +548,9 @@ impl<T : Table> Iterator for TableOwningIterator<T> {
     The indexer is usually not used alone, but as part of more complex structures. 
  */
 pub struct Indexer<T : Indexable + Serializable<Item = T> = u64, ID : Id = u64 > {
+    root : String,
     name : String, 
-    f : File, 
+    f : DataFile, 
     size : u64,
     why_oh_why : std::marker::PhantomData<(T, ID)>
 }
@@ -258,14 +563,13 @@ impl<T : Indexable + Serializable<Item = T>, ID : Id> TableImplementation for In
 
 impl<T : Indexable + Serializable<Item = T>, ID : Id> Indexer<T, ID> {
     pub fn new(root : & str, name : & str, readonly : bool) -> Indexer<T, ID> {
-        let mut f;
-        if readonly {
-            f = OpenOptions::new().read(true).open(format!("{}/{}.idx", root, name)).unwrap();
-        } else {
-            f = OpenOptions::new().read(true).write(true).create(true).open(format!("{}/{}.idx", root, name)).unwrap();
-        }
+        return Indexer::open(root, name, AccessMode::from_readonly(readonly));
+    }
+
+    pub fn open(root : & str, name : & str, mode : AccessMode) -> Indexer<T, ID> {
+        let mut f = DataFile::open(& format!("{}/{}.idx", root, name), mode).unwrap();
         let size = f.seek(SeekFrom::End(0)).unwrap() / T::SIZE;
-        return Indexer{ name : name.to_owned(), f, size, why_oh_why : std::marker::PhantomData{} };
+        return Indexer{ root : root.to_owned(), name : name.to_owned(), f, size, why_oh_why : std::marker::PhantomData{} };
     } 
 
     pub fn get(& mut self, id : ID) -> Option<T> {
@@ -300,6 +604,27 @@ impl<T : Indexable + Serializable<Item = T>, ID : Id> Indexer<T, ID> {
         return self.size as usize;
     }
 
+    /** Returns the length of the index file. 
+     */
+    pub fn byte_len(& self) -> u64 {
+        return self.size * T::SIZE;
+    }
+
+    /** Limits the indexer to the given length of its file, i.e. to the committed ids. 
+     */
+    pub fn set_limit(& mut self, length : u64) {
+        self.f.set_limit(length);
+        self.size = std::cmp::min(self.size, length / T::SIZE);
+    }
+
+    /** Makes sure the indexer has at least given number of ids, filling any new ones with empty values. 
+     */
+    pub fn extend_to(& mut self, size : u64) {
+        if size > self.size {
+            self.set(ID::from(size - 1), & T::EMPTY);
+        }
+    }
+
     pub fn savepoint(& mut self, savepoint : & mut Savepoint) {
         savepoint.add_entry(
             format!("{}.idx", self.name),
@@ -309,7 +634,7 @@ impl<T : Indexable + Serializable<Item = T>, ID : Id> Indexer<T, ID> {
 
     pub fn revert_to_savepoint(& mut self, savepoint : & Savepoint) {
         self.f.set_len(savepoint.limit_for(& format!("{}.idx", self.name))).unwrap();
-        self.f.seek(SeekFrom::End(0)).unwrap();
+        self.size = self.f.seek(SeekFrom::End(0)).unwrap() / T::SIZE;
     }
 
     pub fn iter(& mut self) -> IndexerIterator<T, ID> {
@@ -350,13 +675,62 @@ impl<'a, T : Indexable + Serializable<Item = T>, ID : Id> Iterator for IndexerIt
     }
 }
 
+/** Suffix of the temporary files created when compacting stores. 
+ */
+const COMPACTION_SUFFIX : &'static str = ".compact";
+
+/** Removes any temporary compaction files for given store. 
+ */
+fn remove_compaction(root : & str, name : & str) -> Result<(), std::io::Error> {
+    for ext in ["store", "idx", "committed"].iter() {
+        let path = format!("{}/{}{}.{}", root, name, COMPACTION_SUFFIX, ext);
+        if std::path::Path::new(& path).exists() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    return Ok(());
+}
+
+/** Replaces store's data and index with their compacted versions. 
+ 
+    The data file is renamed first and the index second. If only the compacted index exists, the compaction was interrupted after the data file has been replaced and only the index must be renamed to finish it, which is what `recover_compaction` does.
+ */
+fn finish_compaction(root : & str, name : & str) -> Result<(), std::io::Error> {
+    std::fs::rename(format!("{}/{}{}.store", root, name, COMPACTION_SUFFIX), format!("{}/{}.store", root, name))?;
+    std::fs::rename(format!("{}/{}{}.idx", root, name, COMPACTION_SUFFIX), format!("{}/{}.idx", root, name))?;
+    return remove_compaction(root, name);
+}
+
+/** Makes sure that an interrupted compaction of given store is either finished, or rolled back. 
+ 
+    If both compacted files exist, the compaction was interrupted before the originals were touched and the compacted files are removed. If only the compacted index exists, the data file has already been replaced and the index is renamed as well. 
+ */
+fn recover_compaction(root : & str, name : & str) {
+    let store = format!("{}/{}{}.store", root, name, COMPACTION_SUFFIX);
+    let idx = format!("{}/{}{}.idx", root, name, COMPACTION_SUFFIX);
+    if std::path::Path::new(& idx).exists() {
+        if std::path::Path::new(& store).exists() {
+            LOG!("    {}: removing unfinished compaction", name);
+            remove_compaction(root, name).unwrap();
+        } else {
+            LOG!("    {}: finishing interrupted compaction", name);
+            std::fs::rename(idx, format!("{}/{}.idx", root, name)).unwrap();
+        }
+    } else if std::path::Path::new(& store).exists() {
+        remove_compaction(root, name).unwrap();
+    }
+}
+
 /** Store implementation. 
  
     Store is an indexed updatable container that keeps history of updates.
+
+    The lengths of the data and index files are committed after each update (see `CommittedLengths`). When the index of a reader points to a record added after the reader has been opened, the newer record is returned if it can be read, otherwise the id appears as not having a value. 
  */
 pub struct Store<T : Serializable<Item = T>, ID : Id = u64> {
     pub (crate) indexer : Indexer<u64, ID>,
-    pub (crate) f : File,
+    pub (crate) f : DataFile,
+    committed : CommittedLengths,
     why_oh_why : std::marker::PhantomData<T>,
 }
 
@@ -374,8 +748,7 @@ impl<T:Serializable<Item = T>, ID : Id> Table for Store<T, ID> {
 
     fn get(& mut self, id : ID) -> Option<Self::Value> {
         if let Some(offset) = self.indexer.get(id) {
-            self.f.seek(SeekFrom::Start(offset)).unwrap();
-            let (record_id, value) = Self::read_record(& mut self.f).unwrap();
+            let (record_id, value) = Self::read_record_at(& mut self.f, offset)?;
             assert_eq!(id, record_id, "Corrupted store or index");
             return Some(value);
         } else {
@@ -400,17 +773,25 @@ impl<T:Serializable<Item = T>, ID : Id> IntoIterator for Store<T, ID> {
 impl<T: Serializable<Item = T>, ID : Id> Store<T, ID> {
 
     pub fn new(root : & str, name : & str, readonly : bool) -> Store<T, ID> {
-        let f;
-        if readonly {
-            f = OpenOptions::new().read(true).open(format!("{}/{}.store", root, name))
-                .expect(&format!("Error opening file {}/{}.store", root, name))
-        } else {
-            f = OpenOptions::new().read(true).write(true).create(true).open(format!("{}/{}.store", root, name))
-                .expect(&format!("Error creating file {}/{}.store", root, name));
+        return Store::open(root, name, AccessMode::from_readonly(readonly));
+    }
+
+    /** Opens the store with given access mode. 
+     */
+    pub fn open(root : & str, name : & str, mode : AccessMode) -> Store<T, ID> {
+        let committed = if mode.is_readonly() { CommittedLengths::read(root, name, 2) } else { recover_compaction(root, name); None };
+        let mut f = DataFile::open(& format!("{}/{}.store", root, name), mode)
+            .expect(&format!("Error opening file {}/{}.store", root, name));
+        let mut indexer = Indexer::open(root, name, mode);
+        if let Some(lengths) = committed {
+            f.set_limit(lengths[0]);
+            indexer.set_limit(lengths[1]);
         }
+        let lengths = vec![f.seek(SeekFrom::End(0)).unwrap(), indexer.byte_len()];
         let mut result = Store{
-            indexer : Indexer::new(root, name, readonly),
+            indexer,
             f,
+            committed : CommittedLengths::open(root, name, mode, lengths),
             why_oh_why : std::marker::PhantomData{}
         };
         LOG!("    {}: indices {}, size {}", name, result.indexer.len(), result.f.seek(SeekFrom::End(0)).unwrap());
@@ -433,8 +814,16 @@ impl<T: Serializable<Item = T>, ID : Id> Store<T, ID> {
 
     pub fn revert_to_savepoint(& mut self, savepoint : & Savepoint) {
         self.f.set_len(savepoint.limit_for(& format!("{}.store", self.name()))).unwrap();
-        self.f.seek(SeekFrom::End(0)).unwrap();
+        let end = self.f.seek(SeekFrom::End(0)).unwrap();
         self.indexer.revert_to_savepoint(savepoint);
+        self.commit(end);
+    }
+
+    /** Makes sure the data and index are written to disk. 
+     */
+    pub fn sync_all(& mut self) -> Result<(), std::io::Error> {
+        self.f.sync_all()?;
+        return self.indexer.f.sync_all();
     }
 
     /** Verifies the store. 
@@ -466,6 +855,10 @@ impl<T: Serializable<Item = T>, ID : Id> Store<T, ID> {
         }
         // then check the index's integrity
         for (id, offset) in self.indexer.iter() {
+            // records added after a reader opened the store are not verified
+            if offset != u64::EMPTY && offset >= self.f.limit() {
+                continue;
+            }
             if offset == u64::EMPTY {
                 if latest_mappings.contains_key(& id.into()) {
                     return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Store index id {:?}, has empty index, but offset {} found in the store", id, latest_mappings[& id.into()])));
@@ -496,6 +889,81 @@ impl<T: Serializable<Item = T>, ID : Id> Store<T, ID> {
      */
     pub fn set(& mut self, id : ID, value : & T) {
         self.indexer.set(id, & Self::write_record(& mut self.f, id, value));
+        let end = self.f.seek(SeekFrom::Current(0)).unwrap();
+        self.commit(end);
+    }
+
+    /** Commits the lengths of the table given the end of the data file. 
+     
+        Must only be called after both the data and the index have been written. 
+     */
+    fn commit(& mut self, end : u64) {
+        self.committed.set(0, end);
+        self.committed.set(1, self.indexer.byte_len());
+        self.committed.commit();
+    }
+
+    /** Compacts the store by removing superseded records. 
+     
+        If no savepoint is given, only the latest record for each id is kept. Otherwise all records added after the savepoint are kept together with the record valid at the time of the savepoint for each id. The kept records are written to temporary files first, which then replace the store's data and index (see `finish_compaction`). The number of indexed ids is preserved. 
+        
+        Returns the size of the store's data file before and after the compaction. Note that any savepoints are invalidated by the compaction. 
+     */
+    pub fn compact(& mut self, since : Option<& Savepoint>) -> Result<(u64, u64), std::io::Error> {
+        return self.compact_ids(since, |_| true);
+    }
+
+    /** Compacts the store like `compact`, but only for the ids for which the given function returns true, all records of other ids are kept. 
+     */
+    pub fn compact_ids(& mut self, since : Option<& Savepoint>, compact : impl Fn(ID) -> bool) -> Result<(u64, u64), std::io::Error> {
+        let root = self.indexer.root.clone();
+        let name = self.name().to_owned();
+        let limit = since.map(|sp| sp.limit_for(& format!("{}.store", name))).unwrap_or(u64::MAX);
+        let before = self.f.seek(SeekFrom::End(0))?;
+        let at_savepoint = self.latest_offsets_before(limit, before)?;
+        remove_compaction(& root, & name)?;
+        {
+            let mut compacted = Store::<T, ID>::new(& root, & format!("{}{}", name, COMPACTION_SUFFIX), false);
+            let mut offset = self.f.seek(SeekFrom::Start(0))?;
+            while offset < before {
+                let (id, value) = Self::read_record(& mut self.f).unwrap();
+                let keep = if offset >= limit || ! compact(id) {
+                    true
+                } else if since.is_none() {
+                    self.indexer.get(id) == Some(offset)
+                } else {
+                    at_savepoint.get(& id.into()) == Some(& offset)
+                };
+                if keep {
+                    compacted.set(id, & value);
+                }
+                offset = self.f.seek(SeekFrom::Current(0))?;
+            }
+            compacted.indexer.extend_to(self.indexer.size);
+            compacted.f.sync_all()?;
+            compacted.indexer.f.sync_all()?;
+        }
+        finish_compaction(& root, & name)?;
+        *self = Store::new(& root, & name, false);
+        let after = self.f.seek(SeekFrom::End(0))?;
+        return Ok((before, after));
+    }
+
+    /** Returns offsets of the latest records for each id stored before the given limit. 
+     
+        Returns an empty map if the limit is past the end of the store as then the index already holds the latest offsets. 
+     */
+    fn latest_offsets_before(& mut self, limit : u64, end : u64) -> Result<HashMap<u64, u64>, std::io::Error> {
+        let mut result = HashMap::new();
+        if limit < end {
+            let mut offset = self.f.seek(SeekFrom::Start(0))?;
+            while offset < limit {
+                let (id, _) = Self::read_record(& mut self.f).unwrap();
+                result.insert(id.into(), offset);
+                offset = self.f.seek(SeekFrom::Current(0))?;
+            }
+        }
+        return Ok(result);
     }
 
     /** Returns the number of indexed ids. 
@@ -533,7 +1001,7 @@ impl<T: Serializable<Item = T>, ID : Id> Store<T, ID> {
      
         Returns tuple of the id associated with the record and the value stored. 
      */
-    fn read_record(f : & mut File) -> Option<(ID, T)> {
+    fn read_record(f : & mut DataFile) -> Option<(ID, T)> {
         if let Ok(id) = f.read_u64::<LittleEndian>() {
             return Some((ID::from(id), T::deserialize(f)));
         } else {
@@ -541,7 +1009,18 @@ impl<T: Serializable<Item = T>, ID : Id> Store<T, ID> {
         }
     }
 
-    fn write_record(f : & mut File, id : ID, value : & T) -> u64 {
+    /** Reads the record at given offset obtained from the index. 
+     
+        As the index might have been updated past the committed length of the data file, the limit is lifted if possible. Returns None if the record cannot be read. 
+     */
+    fn read_record_at(f : & mut DataFile, offset : u64) -> Option<(ID, T)> {
+        return f.unlimited(|f| {
+            f.seek(SeekFrom::Start(offset)).ok()?;
+            return Self::read_record(f);
+        });
+    }
+
+    fn write_record(f : & mut DataFile, id : ID, value : & T) -> u64 {
         let offset = f.seek(SeekFrom::End(0)).unwrap();
         f.write_u64::<LittleEndian>(id.into()).unwrap();
         T::serialize(f, value);
@@ -552,13 +1031,13 @@ impl<T: Serializable<Item = T>, ID : Id> Store<T, ID> {
 /** Latest store iterator does not support savepoints since the indices can be udpated. 
  */
 pub struct StoreIter<'a, T: Serializable<Item = T>, ID : Id> {
-    f : &'a mut File,
+    f : &'a mut DataFile,
     iiter : IndexerIterator<'a, u64,ID>,
     why_oh_why : std::marker::PhantomData<T>,
 }
 
 impl<'a, T : Serializable<Item = T>, ID : Id> StoreIter<'a, T, ID> {
-    fn new(f : &'a mut File, indexer : &'a mut Indexer<u64, ID>) -> StoreIter<'a, T, ID> {
+    fn new(f : &'a mut DataFile, indexer : &'a mut Indexer<u64, ID>) -> StoreIter<'a, T, ID> {
         return StoreIter{
             f : f,
             iiter : indexer.iter(),
@@ -571,14 +1050,14 @@ impl<'a, T : Serializable<Item = T>, ID : Id> Iterator for StoreIter<'a, T, ID>
     type Item = (ID, T);
 
     fn next(& mut self) -> Option<(ID, T)> {
-        if let Some((id, offset)) = self.iiter.next() {
-            self.f.seek(SeekFrom::Start(offset)).unwrap();
-            let (store_id, value) = Store::<T, ID>::read_record(self.f).unwrap();
-            assert_eq!(id, store_id, "Corrupted store or its indexing");
-            return Some((id, value)); 
-        } else {
-            return None;
+        while let Some((id, offset)) = self.iiter.next() {
+            // records added after the store has been opened by a reader might not be readable
+            if let Some((store_id, value)) = Store::<T, ID>::read_record_at(self.f, offset) {
+                assert_eq!(id, store_id, "Corrupted store or its indexing");
+                return Some((id, value)); 
+            }
         }
+        return None;
     }
 }
 
@@ -603,11 +1082,14 @@ impl<'a, T : Serializable<Item = T>, ID : Id> Iterator for StoreIterAll<'a, T, I
  
     Store is an indexed updatable container that keeps history of updates.
 
+    The lengths of the data and index files are committed after each update (see `CommittedLengths`). When the index of a reader points to a record added after the reader has been opened, the back links are followed to the latest record within the committed length so that the reader sees the value valid when it was opened. 
+
     TODO add savepoint
  */
 pub struct LinkedStore<T : Serializable<Item = T>, ID : Id = u64> {
     pub (crate) indexer : Indexer<u64, ID>,
-    pub (crate) f : File,
+    pub (crate) f : DataFile,
+    committed : CommittedLengths,
     why_oh_why : std::marker::PhantomData<T>,
 }
 
@@ -625,6 +1107,7 @@ impl<T: Serializable<Item = T>, ID : Id> Table for LinkedStore<T, ID> {
 
     fn get(& mut self, id : ID) -> Option<Self::Value> {
         if let Some(offset) = self.indexer.get(id) {
+            let offset = Self::committed_offset(& mut self.f, offset)?;
             self.f.seek(SeekFrom::Start(offset)).unwrap();
             let (record_id, _, value) = Self::read_record(& mut self.f).unwrap();
             assert_eq!(id, record_id, "Corrupted store or index");
@@ -652,15 +1135,24 @@ impl<T: Serializable<Item = T>, ID : Id> IntoIterator for LinkedStore<T, ID> {
 impl<T: Serializable<Item = T>, ID : Id> LinkedStore<T, ID> {
 
     pub fn new(root : & str, name : & str, readonly : bool) -> LinkedStore<T, ID> {
-        let f;
-        if readonly {
-            f = OpenOptions::new().read(true).open(format!("{}/{}.store", root, name)).unwrap();
-        } else {
-            f = OpenOptions::new().read(true).write(true).create(true).open(format!("{}/{}.store", root, name)).unwrap();
+        return LinkedStore::open(root, name, AccessMode::from_readonly(readonly));
+    }
+
+    /** Opens the linked store with given access mode. 
+     */
+    pub fn open(root : & str, name : & str, mode : AccessMode) -> LinkedStore<T, ID> {
+        let committed = if mode.is_readonly() { CommittedLengths::read(root, name, 2) } else { recover_compaction(root, name); None };
+        let mut f = DataFile::open(& format!("{}/{}.store", root, name), mode).unwrap();
+        let mut indexer = Indexer::open(root, name, mode);
+        if let Some(lengths) = committed {
+            f.set_limit(lengths[0]);
+            indexer.set_limit(lengths[1]);
         }
+        let lengths = vec![f.seek(SeekFrom::End(0)).unwrap(), indexer.byte_len()];
         let mut result = LinkedStore{
-            indexer : Indexer::new(root, name, readonly),
+            indexer,
             f,
+            committed : CommittedLengths::open(root, name, mode, lengths),
             why_oh_why : std::marker::PhantomData{}
         };
         LOG!("    {}: indices {}, size {}", name, result.indexer.len(), result.f.seek(SeekFrom::End(0)).unwrap());
@@ -683,8 +1175,9 @@ impl<T: Serializable<Item = T>, ID : Id> LinkedStore<T, ID> {
 
     pub fn revert_to_savepoint(& mut self, savepoint : & Savepoint) {
         self.f.set_len(savepoint.limit_for(& format!("{}.store", self.name()))).unwrap();
-        self.f.seek(SeekFrom::End(0)).unwrap();
+        let end = self.f.seek(SeekFrom::End(0)).unwrap();
         self.indexer.revert_to_savepoint(savepoint);
+        self.commit(end);
     }
 
     /** Verifies the linked store. 
@@ -734,6 +1227,8 @@ impl<T: Serializable<Item = T>, ID : Id> LinkedStore<T, ID> {
         }
         // then check the index's integrity
         for (id, offset) in self.indexer.iter() {
+            // records added after a reader opened the store are verified as of the time of opening
+            let offset = Self::committed_offset(& mut self.f, offset).unwrap_or(u64::EMPTY);
             if offset == u64::EMPTY {
                 if latest_mappings.contains_key(& id.into()) {
                     return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("LinkedStore index id {:?}, has empty index, but offset {} found in the store", id, latest_mappings[& id.into()])));
@@ -759,6 +1254,62 @@ impl<T: Serializable<Item = T>, ID : Id> LinkedStore<T, ID> {
     pub fn set(& mut self, id : ID, value : & T) {
         let previous_offset = self.indexer.get(id);
         self.indexer.set(id, & Self::write_record(& mut self.f, id, previous_offset, value));
+        let end = self.f.seek(SeekFrom::Current(0)).unwrap();
+        self.commit(end);
+    }
+
+    /** Commits the lengths of the table given the end of the data file. 
+     
+        Must only be called after both the data and the index have been written. 
+     */
+    fn commit(& mut self, end : u64) {
+        self.committed.set(0, end);
+        self.committed.set(1, self.indexer.byte_len());
+        self.committed.commit();
+    }
+
+    /** Compacts the linked store by removing superseded records. 
+     
+        Works like `Store::compact`, but a record is only superseded by a newer record of the same id *and* key, as given by the key function. This allows compacting stores that keep multiple values per id, such as metadata, where the latest record of each key must be kept. The back links of the kept records are updated to point to the previous kept record of the same id, if any. 
+     */
+    pub fn compact<K : Hash + Eq>(& mut self, since : Option<& Savepoint>, key : impl Fn(& T) -> K) -> Result<(u64, u64), std::io::Error> {
+        let root = self.indexer.root.clone();
+        let name = self.name().to_owned();
+        let limit = since.map(|sp| sp.limit_for(& format!("{}.store", name))).unwrap_or(u64::MAX);
+        let before = self.f.seek(SeekFrom::End(0))?;
+        let latest = self.latest_offsets_before(limit.min(before), & key)?;
+        remove_compaction(& root, & name)?;
+        {
+            let mut compacted = LinkedStore::<T, ID>::new(& root, & format!("{}{}", name, COMPACTION_SUFFIX), false);
+            let mut offset = self.f.seek(SeekFrom::Start(0))?;
+            while offset < before {
+                let (id, _, value) = Self::read_record(& mut self.f).unwrap();
+                if offset >= limit || latest.get(& (id.into(), key(& value))) == Some(& offset) {
+                    compacted.set(id, & value);
+                }
+                offset = self.f.seek(SeekFrom::Current(0))?;
+            }
+            compacted.indexer.extend_to(self.indexer.size);
+            compacted.f.sync_all()?;
+            compacted.indexer.f.sync_all()?;
+        }
+        finish_compaction(& root, & name)?;
+        *self = LinkedStore::new(& root, & name, false);
+        let after = self.f.seek(SeekFrom::End(0))?;
+        return Ok((before, after));
+    }
+
+    /** Returns offsets of the latest records for each id and key stored before the given limit. 
+     */
+    fn latest_offsets_before<K : Hash + Eq>(& mut self, limit : u64, key : & impl Fn(& T) -> K) -> Result<HashMap<(u64, K), u64>, std::io::Error> {
+        let mut result = HashMap::new();
+        let mut offset = self.f.seek(SeekFrom::Start(0))?;
+        while offset < limit {
+            let (id, _, value) = Self::read_record(& mut self.f).unwrap();
+            result.insert((id.into(), key(& value)), offset);
+            offset = self.f.seek(SeekFrom::Current(0))?;
+        }
+        return Ok(result);
     }
 
     /** Returns the number of indexed ids. 
@@ -797,15 +1348,33 @@ impl<T: Serializable<Item = T>, ID : Id> LinkedStore<T, ID> {
         The values are returned in the reverse order they were added, i.e. latest value first. 
      */
     pub fn iter_id(& mut self, id : ID) -> LinkedStoreIterId<T, ID> {
-        let offset = self.indexer.get(id);
+        let offset = self.indexer.get(id).and_then(|offset| Self::committed_offset(& mut self.f, offset));
         return LinkedStoreIterId{ store : self, offset };
     }
 
+    /** Given an offset from the index, returns the offset of the latest record for the same id within the committed length of the data file. 
+     
+        Follows the back links of records added after the store has been opened by a reader. Returns None if there is no such record, or if the back links cannot be read. 
+     */
+    fn committed_offset(f : & mut DataFile, offset : u64) -> Option<u64> {
+        let mut offset = offset;
+        while offset >= f.limit() {
+            if offset == u64::EMPTY {
+                return None;
+            }
+            offset = f.unlimited(|f| {
+                f.seek(SeekFrom::Start(offset + 8)).ok()?;
+                return f.read_u64::<LittleEndian>().ok();
+            })?;
+        }
+        return Some(offset);
+    }
+
     /** Reads the record from a file. 
      
         Returns tuple of the id associated with the record, offset of the previous record associated with the id and the value stored. 
      */
-    fn read_record(f : & mut File) -> Option<(ID, Option<u64>, T)> {
+    fn read_record(f : & mut DataFile) -> Option<(ID, Option<u64>, T)> {
         if let Ok(id) = f.read_u64::<LittleEndian>() {
             let previous_offset = f.read_u64::<LittleEndian>().unwrap();
             return Some((ID::from(id), if previous_offset == u64::EMPTY { None } else { Some(previous_offset) }, T::deserialize(f)));
@@ -814,7 +1383,7 @@ impl<T: Serializable<Item = T>, ID : Id> LinkedStore<T, ID> {
         }
     }
 
-    fn write_record(f : & mut File, id : ID, previous_offset : Option<u64>, value : & T) -> u64 {
+    fn write_record(f : & mut DataFile, id : ID, previous_offset : Option<u64>, value : & T) -> u64 {
         let offset = f.seek(SeekFrom::End(0)).unwrap();
         f.write_u64::<LittleEndian>(id.into()).unwrap();
         match previous_offset {
@@ -826,14 +1395,29 @@ impl<T: Serializable<Item = T>, ID : Id> LinkedStore<T, ID> {
     }
 }
 
+impl<ID : Id> LinkedStore<Savepoint, ID> {
+    /** Updates the savepoint's information about this store as if the savepoint was already stored in it under given id. 
+     
+        This allows storing the savepoint so that it stays intact when the store is reverted to it. The savepoint must already contain the information about this store (see `savepoint`), which is replaced, so that its serialized size does not change. 
+     */
+    pub fn savepoint_including_self(& mut self, savepoint : & mut Savepoint, id : ID) {
+        let mut record = Vec::new();
+        Savepoint::serialize(& mut record, savepoint);
+        let end = self.f.seek(SeekFrom::End(0)).unwrap();
+        let index_len = std::cmp::max(self.indexer.byte_len(), (id.into() + 1) * u64::SIZE);
+        savepoint.sizes.insert(format!("{}.store", self.name()), end + 16 + record.len() as u64);
+        savepoint.sizes.insert(format!("{}.idx", self.name()), index_len);
+    }
+}
+
 pub struct LinkedStoreIter<'a, T: Serializable<Item = T>, ID : Id> {
-    f : &'a mut File,
+    f : &'a mut DataFile,
     iiter : IndexerIterator<'a, u64, ID>,
     why_oh_why : std::marker::PhantomData<T>,
 }
 
 impl<'a, T : Serializable<Item = T>, ID : Id> LinkedStoreIter<'a, T, ID> {
-    fn new(f : &'a mut File, indexer : &'a mut Indexer<u64, ID>) -> LinkedStoreIter<'a, T, ID> {
+    fn new(f : &'a mut DataFile, indexer : &'a mut Indexer<u64, ID>) -> LinkedStoreIter<'a, T, ID> {
         return LinkedStoreIter{
             f : f,
             iiter : indexer.iter(),
@@ -846,14 +1430,16 @@ impl<'a, T : Serializable<Item = T>, ID : Id> Iterator for LinkedStoreIter<'a, T
     type Item = (ID, T);
 
     fn next(& mut self) -> Option<(ID, T)> {
-        if let Some((id, offset)) = self.iiter.next() {
-            self.f.seek(SeekFrom::Start(offset)).unwrap();
-            let (store_id, _, value) = LinkedStore::<T, ID>::read_record(self.f).unwrap();
-            assert_eq!(id, store_id, "Corrupted store or its indexing");
-            return Some((id, value)); 
-        } else {
-            return None;
+        while let Some((id, offset)) = self.iiter.next() {
+            // ids whose only records were added after the store has been opened by a reader are skipped
+            if let Some(offset) = LinkedStore::<T, ID>::committed_offset(self.f, offset) {
+                self.f.seek(SeekFrom::Start(offset)).unwrap();
+                let (store_id, _, value) = LinkedStore::<T, ID>::read_record(self.f).unwrap();
+                assert_eq!(id, store_id, "Corrupted store or its indexing");
+                return Some((id, value)); 
+            }
         }
+        return None;
     }
 }
 
@@ -898,18 +1484,292 @@ impl<'a, T : Serializable<Item = T>, ID : Id> Iterator for LinkedStoreIterId<'a,
     }
 }
 
+/** Persistent hash index from values to ids used by the mappings. 
+ 
+    The index is an open addressing hash table stored in `NAME.hidx`. The file starts with a header of the number of slots, the number of ids indexed so far and the number of used slots, followed by the slots. Each slot holds the hash of a value and its id + 1 (0 denotes an empty slot). Only the hashes are kept in the index, so a lookup returns the candidate ids whose values must be compared against the mapping itself. When the index becomes too full, it is rebuilt into a file with twice as many slots, which then replaces the old one. 
+
+    The index is derived from its mapping and may lag behind it (such as after a crash, or for mappings created by older versions), in which case the missing ids are indexed before the mapping is next written to. Readers never write the index, they instead compare the values of ids not yet indexed one by one. As readers are limited to the committed ids of the mapping, they ignore any ids added to the index after they opened the mapping. 
+
+    The header is only written every `HEADER_INTERVAL` indexed ids and when flushed (see `flush`), so that additions do not rewrite it every time. Ids indexed after the header was last written are indexed again after a crash, which only adds duplicate slots, and the slots they occupy are recounted first (see `recount`). 
+ */
+pub struct HashIndex {
+    path : String,
+    /* None for readers if the index does not exist */
+    f : Option<DataFile>,
+    mode : AccessMode,
+    capacity : u64,
+    indexed : u64,
+    used : u64,
+    /* number of indexed ids when the header was last written */
+    header_indexed : u64,
+}
+
+impl HashIndex {
+    /** Number of slots of a new index. 
+     */
+    const INITIAL_CAPACITY : u64 = 1024;
+    /** The header (capacity, indexed ids and used slots) and slot (hash and id) sizes in bytes. 
+     */
+    const HEADER_SIZE : u64 = 24;
+    const SLOT_SIZE : u64 = 16;
+    /** Number of slots read at once when the index is rebuilt. 
+     */
+    const REBUILD_BATCH : usize = 65536;
+    /** Number of ids indexed after which the header is written. 
+     */
+    const HEADER_INTERVAL : u64 = 1024;
+
+    /** Opens the index of given table, creating an empty one for writers if it does not exist. 
+     */
+    pub fn open(root : & str, name : & str, mode : AccessMode) -> HashIndex {
+        let path = format!("{}/{}.hidx", root, name);
+        let mut result = HashIndex{ path, f : None, mode, capacity : 0, indexed : 0, used : 0, header_indexed : 0 };
+        if mode.is_readonly() {
+            if let Ok(f) = DataFile::open(& result.path, mode) {
+                result.f = Some(f);
+                result.read_header();
+            }
+        } else if std::path::Path::new(& result.path).exists() {
+            result.f = Some(DataFile::open(& result.path, mode).unwrap());
+            result.read_header();
+        } else {
+            result.reset();
+        }
+        return result;
+    }
+
+    /** Calculates the hash of given value. 
+     
+        The hash must be stable across runs as it is persisted, so it is calculated from the serialized value (FNV-1a). 
+     */
+    pub fn hash<T : Serializable<Item = T>>(value : & T) -> u64 {
+        let mut buffer = Vec::new();
+        T::serialize(& mut buffer, value);
+        return buffer.iter().fold(0xcbf29ce484222325u64, |hash, x| (hash ^ (*x as u64)).wrapping_mul(0x100000001b3));
+    }
+
+    /** Returns the number of ids indexed, i.e. ids from 0 to the returned value are guaranteed to be in the index. 
+     */
+    pub fn indexed(& self) -> u64 {
+        return self.indexed;
+    }
+
+    /** Returns the ids of all values with given hash. 
+     */
+    pub fn candidates(& mut self, hash : u64) -> Vec<u64> {
+        let mut result = Vec::new();
+        if self.capacity == 0 {
+            return result;
+        }
+        let capacity = self.capacity;
+        if let Some(f) = & mut self.f {
+            let mut slot = hash % capacity;
+            for _ in 0..capacity {
+                f.seek(SeekFrom::Start(HashIndex::HEADER_SIZE + slot * HashIndex::SLOT_SIZE)).unwrap();
+                let slot_hash = match f.read_u64::<LittleEndian>() {
+                    Ok(x) => x,
+                    Err(_) => break, 
+                };
+                let id = f.read_u64::<LittleEndian>().unwrap_or(0);
+                if id == 0 {
+                    break;
+                } else if slot_hash == hash {
+                    result.push(id - 1);
+                }
+                slot = (slot + 1) % capacity;
+            }
+        }
+        return result;
+    }
+
+    /** Adds given id with given hash to the index, growing the index if necessary. 
+     
+        Does not change the number of indexed ids, see `set_indexed`. 
+     */
+    pub fn insert(& mut self, hash : u64, id : u64) {
+        assert!(! self.mode.is_readonly(), "Read only hash index cannot be updated");
+        self.reserve(self.used + 1);
+        HashIndex::insert_into(self.f.as_mut().unwrap(), self.capacity, hash, id);
+        self.used += 1;
+    }
+
+    /** Sets the number of indexed ids, writing the header if it was not written for `HEADER_INTERVAL` ids. 
+     */
+    pub fn set_indexed(& mut self, indexed : u64) {
+        self.indexed = indexed;
+        if indexed >= self.header_indexed + HashIndex::HEADER_INTERVAL || indexed < self.header_indexed {
+            self.write_header();
+        }
+    }
+
+    /** Writes the header if it does not reflect the current state of the index. 
+     */
+    pub fn flush(& mut self) {
+        if self.f.is_some() && ! self.mode.is_readonly() && self.header_indexed != self.indexed {
+            self.write_header();
+        }
+    }
+
+    /** Makes sure the index has enough slots for given number of values, rebuilding it if it does not. 
+     
+        The index is kept at most 70% full so that the probe sequences stay short. 
+     */
+    pub fn reserve(& mut self, values : u64) {
+        if values * 10 <= self.capacity * 7 {
+            return;
+        }
+        let mut capacity = std::cmp::max(self.capacity, HashIndex::INITIAL_CAPACITY);
+        while values * 10 > capacity * 7 {
+            capacity *= 2;
+        }
+        // write the rebuilt index to a temporary file first so that the old index stays valid if interrupted
+        let tmp_path = format!("{}.tmp", self.path);
+        let mut tmp = HashIndex::create(& tmp_path, capacity);
+        let old = self.f.as_mut().unwrap();
+        old.seek(SeekFrom::Start(HashIndex::HEADER_SIZE)).unwrap();
+        let mut remaining = self.capacity;
+        let mut buffer = Vec::new();
+        while remaining > 0 {
+            let batch = std::cmp::min(remaining, HashIndex::REBUILD_BATCH as u64);
+            buffer.resize((batch * HashIndex::SLOT_SIZE) as usize, 0);
+            old.read_exact(& mut buffer).unwrap();
+            let mut slots = & buffer[..];
+            for _ in 0..batch {
+                let hash = slots.read_u64::<LittleEndian>().unwrap();
+                let id = slots.read_u64::<LittleEndian>().unwrap();
+                if id != 0 {
+                    HashIndex::insert_into(& mut tmp, capacity, hash, id - 1);
+                }
+            }
+            remaining -= batch;
+        }
+        self.f = Some(tmp);
+        self.capacity = capacity;
+        self.write_header();
+        std::fs::rename(& tmp_path, & self.path).unwrap();
+    }
+
+    /** Recounts the used slots of the index. 
+     
+        The header may undercount the used slots if the index was not closed properly, as the slots of ids indexed after the header was last written are not included. Must be called before indexing the ids missing from such index, otherwise the index could fill up without being rebuilt. 
+     */
+    pub fn recount(& mut self) {
+        let f = self.f.as_mut().unwrap();
+        f.seek(SeekFrom::Start(HashIndex::HEADER_SIZE)).unwrap();
+        let mut remaining = self.capacity;
+        let mut buffer = Vec::new();
+        let mut used = 0;
+        while remaining > 0 {
+            let batch = std::cmp::min(remaining, HashIndex::REBUILD_BATCH as u64);
+            buffer.resize((batch * HashIndex::SLOT_SIZE) as usize, 0);
+            f.read_exact(& mut buffer).unwrap();
+            let mut slots = & buffer[..];
+            for _ in 0..batch {
+                slots.read_u64::<LittleEndian>().unwrap();
+                if slots.read_u64::<LittleEndian>().unwrap() != 0 {
+                    used += 1;
+                }
+            }
+            remaining -= batch;
+        }
+        self.used = used;
+    }
+
+    /** Clears the index so that it will be rebuilt. 
+     */
+    pub fn reset(& mut self) {
+        assert!(! self.mode.is_readonly(), "Read only hash index cannot be updated");
+        // readers may have the old index open, so replace it instead of truncating it
+        let tmp_path = format!("{}.tmp", self.path);
+        self.f = Some(HashIndex::create(& tmp_path, HashIndex::INITIAL_CAPACITY));
+        self.capacity = HashIndex::INITIAL_CAPACITY;
+        self.indexed = 0;
+        self.used = 0;
+        self.write_header();
+        std::fs::rename(& tmp_path, & self.path).unwrap();
+    }
+
+    /** Creates an empty index file with given number of slots. 
+     */
+    fn create(path : & str, capacity : u64) -> DataFile {
+        let f = DataFile::open(path, AccessMode::ReadWrite).unwrap();
+        f.set_len(0).unwrap();
+        f.set_len(HashIndex::HEADER_SIZE + capacity * HashIndex::SLOT_SIZE).unwrap();
+        return f;
+    }
+
+    fn insert_into(f : & mut DataFile, capacity : u64, hash : u64, id : u64) {
+        let mut slot = hash % capacity;
+        loop {
+            let offset = HashIndex::HEADER_SIZE + slot * HashIndex::SLOT_SIZE;
+            f.seek(SeekFrom::Start(offset + 8)).unwrap();
+            if u64::deserialize(f) == 0 {
+                f.seek(SeekFrom::Start(offset)).unwrap();
+                u64::serialize(f, & hash);
+                u64::serialize(f, & (id + 1));
+                return;
+            }
+            slot = (slot + 1) % capacity;
+        }
+    }
+
+    fn read_header(& mut self) {
+        let f = self.f.as_mut().unwrap();
+        f.seek(SeekFrom::Start(0)).unwrap();
+        // incomplete header can only be observed by readers of a new index, which is treated as empty
+        match (f.read_u64::<LittleEndian>(), f.read_u64::<LittleEndian>(), f.read_u64::<LittleEndian>()) {
+            (Ok(capacity), Ok(indexed), Ok(used)) => {
+                self.capacity = capacity;
+                self.indexed = indexed;
+                self.used = used;
+                self.header_indexed = indexed;
+            },
+            _ => {
+                self.capacity = 0;
+                self.indexed = 0;
+                self.used = 0;
+            }
+        }
+    }
+
+    fn write_header(& mut self) {
+        let f = self.f.as_mut().unwrap();
+        f.seek(SeekFrom::Start(0)).unwrap();
+        u64::serialize(f, & self.capacity);
+        u64::serialize(f, & self.indexed);
+        u64::serialize(f, & self.used);
+        self.header_indexed = self.indexed;
+    }
+}
+
+/** Returns the number of bytes taken by the hash map of the mapping caches with given capacity, i.e. its values, ids and control bytes, taking the load factor of the hash map into account. 
+ 
+    Heap memory owned by the values, if any, is not included. 
+ */
+fn cache_map_bytes<T, ID>(capacity : usize) -> usize {
+    return capacity * 8 / 7 * (std::mem::size_of::<T>() + std::mem::size_of::<ID>() + 1);
+}
+
 /** Mapping from values to ids. 
  
-    Unlike store, mapping does not allow updates to added values. 
+    Unlike store, mapping does not allow updates to added values. The length of the mapping file is committed after each added value (see `CommittedLengths`). 
+
+    Values are looked up via a persistent hash index (see `HashIndex`), so that the mapping does not have to be loaded in memory and lookups are available to readers as well. Once loaded, the values found and added are also cached in memory, so that repeated lookups do not go to the index, until the mapping is cleared. 
  */
 pub struct Mapping<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id = u64> {
     name : String,
-    f : File,
-    mapping : HashMap<T, ID>,
+    f : DataFile,
+    committed : CommittedLengths,
+    index : HashIndex,
+    /** Values found, or added since the mapping was loaded, None if the mapping is not loaded. 
+     */
+    cache : Option<HashMap<T, ID>>,
+    readonly : bool,
     size : u64,
     /** Because seeking a file in rust is really expensive, the read index is cached.
      */
     read_index : u64,
+    why_oh_why : std::marker::PhantomData<(T, ID)>
 }
 
 impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> Table for Mapping<T, ID> {
@@ -964,19 +1824,30 @@ impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> IntoItera
 impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> Mapping<T, ID> {
 
     pub fn new(root : & str, name : & str, readonly : bool) -> Mapping<T, ID> {
-        let mut f;
-        if readonly {
-            f = OpenOptions::new().read(true).open(format!("{}/{}.mapping", root, name)).unwrap();
-        } else {
-            f = OpenOptions::new().read(true).write(true).create(true).open(format!("{}/{}.mapping", root, name)).unwrap();
+        return Mapping::open(root, name, AccessMode::from_readonly(readonly));
+    }
+
+    /** Opens the mapping with given access mode. 
+     */
+    pub fn open(root : & str, name : & str, mode : AccessMode) -> Mapping<T, ID> {
+        let filename = format!("{}.mapping", name);
+        let committed = if mode.is_readonly() { CommittedLengths::read(root, & filename, 1) } else { None };
+        let mut f = DataFile::open(& format!("{}/{}", root, filename), mode).unwrap();
+        if let Some(lengths) = committed {
+            // only whole values are committed, but be defensive
+            f.set_limit(lengths[0] / T::SIZE * T::SIZE);
         }
         let size = f.seek(SeekFrom::End(0)).unwrap() / T::SIZE;
         let mut result = Mapping{
             name : name.to_owned(),
             f, 
-            mapping : HashMap::new(),
+            committed : CommittedLengths::open(root, & filename, mode, vec![size * T::SIZE]),
+            index : HashIndex::open(root, & filename, mode),
+            cache : None,
+            readonly : mode.is_readonly(),
             size,
             read_index : 0,
+            why_oh_why : std::marker::PhantomData{},
 
         };
         LOG!("    {}: indices {}, size {}", name, result.size, result.f.seek(SeekFrom::End(0)).unwrap());
@@ -990,6 +1861,7 @@ impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> Mapping<T
     /** Updates the savepoint with own information. 
      */
     pub fn savepoint(& mut self, savepoint : & mut Savepoint) {
+        self.flush();
         savepoint.add_entry(
             format!("{}.mapping", self.name()),
             self.f.seek(SeekFrom::End(0)).unwrap()
@@ -998,7 +1870,18 @@ impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> Mapping<T
 
     pub fn revert_to_savepoint(& mut self, savepoint : & Savepoint) {
         self.f.set_len(savepoint.limit_for(& format!("{}.mapping", self.name))).unwrap();
-        self.f.seek(SeekFrom::End(0)).unwrap();
+        self.size = self.f.seek(SeekFrom::End(0)).unwrap() / T::SIZE;
+        self.read_index = self.size;
+        let size = self.size;
+        if let Some(cache) = self.cache.as_mut() {
+            cache.retain(|_, id| (*id).into() < size);
+        }
+        self.committed.set(0, self.size * T::SIZE);
+        self.committed.publish();
+        // the index refers to values that no longer exist, rebuild it
+        if self.index.indexed() > self.size {
+            self.index.reset();
+        }
     }
 
     /** Verifies the mapping's integrity. 
@@ -1019,67 +1902,162 @@ impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> Mapping<T
         return Ok(());
     }
 
-    /** Loads the mapping into from disk to the hashmap. 
+    /** Prepares the mapping for lookups and additions by indexing any values not yet in its index and enabling the cache. 
+     
+        Does nothing for readers, which cannot update the index. 
      */
     pub fn load(& mut self) {
-        // we have to create the iterator ourselves here otherwise rust would complain of double mutable borrow
-        self.f.seek(SeekFrom::Start(0)).unwrap();
-        let iter = MappingIter{f : & mut self.f, index : 0, size : self.size, why_oh_why : std::marker::PhantomData{} };
-        self.mapping.clear();
-        for (id, value) in iter {
-            self.mapping.insert(value, id);
+        if self.readonly {
+            return;
+        }
+        self.update_index();
+        self.index.flush();
+        if self.cache.is_none() {
+            self.cache = Some(HashMap::new());
         }
     }
 
-    /** Clears the loaded mapping and shrinks the hashmap to free up as much memory as possible. 
+    /** Drops the cached values and writes the index header. 
      */
     pub fn clear(& mut self) {
-        self.mapping.clear();
-        self.mapping.shrink_to_fit();
+        self.cache = None;
+        if ! self.readonly {
+            self.index.flush();
+        }
     }
 
-    pub fn get_mapping(& mut self, value : & T) -> Option<ID> {
-        match self.mapping.get(value) {
-            Some(id) => Some(*id),
-            None => None
+    /** Writes the index header if there are values added since. 
+     */
+    pub fn flush(& mut self) {
+        self.index.flush();
+    }
+
+    /** Returns the number of bytes taken by the cached values. 
+     */
+    pub fn memory_size(& self) -> usize {
+        return self.cache.as_ref().map(|x| cache_map_bytes::<T, ID>(x.capacity())).unwrap_or(0);
+    }
+
+    /** Returns the number of bytes the cached values would take if all values of the mapping were cached. 
+     */
+    pub fn memory_estimate(& self) -> usize {
+        return cache_map_bytes::<T, ID>(self.size as usize);
+    }
+
+    /** Indexes the values not yet in the index, if any. 
+     */
+    fn update_index(& mut self) {
+        if self.readonly || self.index.indexed() >= self.size {
+            return;
+        }
+        self.index.recount();
+        self.index.reserve(self.size);
+        let start = self.index.indexed();
+        self.f.seek(SeekFrom::Start(start * T::SIZE)).unwrap();
+        for id in start..self.size {
+            let value = T::deserialize(& mut self.f);
+            self.index.insert(HashIndex::hash(& value), id);
         }
+        self.index.set_indexed(self.size);
+        self.f.seek(SeekFrom::End(0)).unwrap();
+        self.read_index = self.size;
+    }
+
+    pub fn get_mapping(& mut self, value : & T) -> Option<ID> {
+        return self.find(value, HashIndex::hash(value));
     }
 
     pub fn get_or_create_mapping(& mut self, value : & T) -> (ID, bool) {
-        match self.mapping.get(value) {
-            Some(id) => (*id, false),
+        let hash = HashIndex::hash(value);
+        match self.find(value, hash) {
+            Some(id) => (id, false),
             None => {
-                let next_id = ID::from(self.mapping.len() as u64);
-                self.mapping.insert(value.to_owned(), next_id);
+                let next_id = ID::from(self.size);
                 // serialize the value and increase size
+                self.f.seek(SeekFrom::End(0)).unwrap();
                 T::serialize(& mut self.f, value);
                 self.size += 1;
                 self.read_index = self.size;
+                self.committed.set(0, self.size * T::SIZE);
+                self.committed.commit();
+                self.index.insert(hash, next_id.into());
+                self.index.set_indexed(self.size);
+                if let Some(cache) = self.cache.as_mut() {
+                    cache.insert(value.clone(), next_id);
+                }
                 return (next_id, true);
             }
         }
     }
 
+    /** Returns the id of given value with given hash, if the value is in the mapping. 
+     
+        The cache is consulted first, values found in the mapping are then cached. 
+     */
+    fn find(& mut self, value : & T, hash : u64) -> Option<ID> {
+        if let Some(id) = self.cache.as_ref().and_then(|x| x.get(value)) {
+            return Some(*id);
+        }
+        let result = self.find_in_index(value, hash);
+        if let (Some(id), Some(cache)) = (result, self.cache.as_mut()) {
+            cache.insert(value.clone(), id);
+        }
+        return result;
+    }
+
+    /** Returns the id of given value with given hash using the index. 
+     
+        Writers first index any values missing from the index, readers compare the values not yet indexed one by one. 
+     */
+    fn find_in_index(& mut self, value : & T, hash : u64) -> Option<ID> {
+        self.update_index();
+        for id in self.index.candidates(hash) {
+            if id < self.size && self.get(ID::from(id)).as_ref() == Some(value) {
+                return Some(ID::from(id));
+            }
+        }
+        let indexed = std::cmp::min(self.index.indexed(), self.size);
+        if indexed < self.size {
+            self.f.seek(SeekFrom::Start(indexed * T::SIZE)).unwrap();
+            for id in indexed..self.size {
+                if T::deserialize(& mut self.f) == * value {
+                    self.f.seek(SeekFrom::End(0)).unwrap();
+                    self.read_index = self.size;
+                    return Some(ID::from(id));
+                }
+            }
+            self.read_index = self.size;
+        }
+        return None;
+    }
+
     /** Updates the already stored mapping. 
      */
     pub fn update(& mut self, id : ID, value : & T) {
         assert!(id.into() < self.size);
+        if self.cache.is_some() {
+            let old = self.get(id).unwrap();
+            let cache = self.cache.as_mut().unwrap();
+            cache.remove(& old);
+            cache.insert(value.clone(), id);
+        }
         let offset = T::SIZE * id.into();
         self.f.seek(SeekFrom::Start(offset)).unwrap();
         T::serialize(& mut self.f, value);
         self.f.seek(SeekFrom::End(0)).unwrap();
         self.read_index = self.size;
-        // now that the file has been changed, update the mapping
-        self.mapping.remove(value);
-        self.mapping.insert(value.to_owned(), id);
+        // now that the file has been changed, update the index, the entry of the old value no longer matches and will be ignored
+        self.index.insert(HashIndex::hash(value), id.into());
     }
 
     pub fn len(& self) -> usize {
         return self.size as usize;
     }
 
+    /** Returns the number of values in the mapping's index. 
+     */
     pub fn mapping_len(& self) -> usize {
-        return self.mapping.len();
+        return self.index.indexed() as usize;
     }
 
     pub fn iter(& mut self) -> MappingIter<T, ID> {
@@ -1095,7 +2073,7 @@ impl<T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> Mapping<T
 }
 
 pub struct MappingIter<'a, T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id = u64> {
-    f : &'a mut File,
+    f : &'a mut DataFile,
     index : u64,
     size : u64,
     why_oh_why : std::marker::PhantomData<(T, ID)>
@@ -1117,10 +2095,17 @@ impl<'a, T : FixedSizeSerializable<Item = T> + Eq + Hash + Clone, ID : Id> Itera
 }
 
 /** Mapping from values to ids where the values require indexing. 
+ 
+    Like the mapping, values are looked up via a persistent hash index (see `HashIndex`) and cached in memory once loaded. 
  */
 pub struct IndirectMapping<T : Serializable<Item = T> + Eq + Hash + Clone, ID : Id = u64> {
     pub (crate) store : Store<T, ID>,
-    mapping : HashMap<T, ID>
+    index : HashIndex,
+    /** Values found, or added since the mapping was loaded, None if the mapping is not loaded, and the serialized size of the cached values. 
+     */
+    cache : Option<HashMap<T, ID>>,
+    cache_bytes : usize,
+    readonly : bool,
 }
 
 impl<T : Serializable<Item = T> + Eq + Hash + Clone, ID : Id> Table for IndirectMapping<T, ID> {
@@ -1175,9 +2160,18 @@ impl<T : Serializable<Item = T> + Eq + Hash + Clone, ID : Id> IndirectMapping<T,
     /** Creates new mapping. 
      */
     pub fn new(root : & str, name : & str, readonly : bool) -> IndirectMapping<T, ID> {
+        return IndirectMapping::open(root, name, AccessMode::from_readonly(readonly));
+    }
+
+    /** Opens the mapping with given access mode. 
+     */
+    pub fn open(root : & str, name : & str, mode : AccessMode) -> IndirectMapping<T, ID> {
         return IndirectMapping{
-            store : Store::new(root, & format!("{}.mapping", name), readonly),
-            mapping : HashMap::new(),
+            store : Store::open(root, & format!("{}.mapping", name), mode),
+            index : HashIndex::open(root, & format!("{}.mapping", name), mode),
+            cache : None,
+            cache_bytes : 0,
+            readonly : mode.is_readonly(),
         }
     }
 
@@ -1188,52 +2182,159 @@ impl<T : Serializable<Item = T> + Eq + Hash + Clone, ID : Id> IndirectMapping<T,
     /** Updates the savepoint with own information. 
      */
     pub fn savepoint(& mut self, savepoint : & mut Savepoint) {
+        self.index.flush();
         self.store.savepoint(savepoint);
     }
 
+    /** Writes the index header if there are values added since. 
+     */
+    pub fn flush(& mut self) {
+        self.index.flush();
+    }
+
     pub fn revert_to_savepoint(& mut self, savepoint : & Savepoint) {
         self.store.revert_to_savepoint(savepoint);
+        let size = self.store.len() as u64;
+        if let Some(cache) = self.cache.as_mut() {
+            cache.retain(|_, id| (*id).into() < size);
+        }
+        // the index refers to values that no longer exist, rebuild it
+        if self.index.indexed() > self.store.len() as u64 {
+            self.index.reset();
+        }
     }
 
     /** Verifies the mapping's integrity. 
 
-        Simply verifies the integrity of the store as mapping is just a hashmap and a store.
+        Simply verifies the integrity of the store as mapping is just an index and a store.
      */
     pub fn verify(& mut self, checker : & mut dyn FnMut(T) -> Result<(), std::io::Error>) -> Result<(), std::io::Error> {
         return self.store.verify(checker);
     }
 
+    /** Prepares the mapping for lookups and additions by indexing any values not yet in its index and enabling the cache. 
+     
+        Does nothing for readers, which cannot update the index. 
+     */
     pub fn load(& mut self) {
-        self.mapping.clear();
-        for (id, value) in self.store.iter() {
-            self.mapping.insert(value, id);
+        if self.readonly {
+            return;
+        }
+        self.update_index();
+        self.index.flush();
+        if self.cache.is_none() {
+            self.cache = Some(HashMap::new());
+            self.cache_bytes = 0;
         }
     }
 
+    /** Drops the cached values and writes the index header. 
+     */
     pub fn clear(& mut self) {
-        self.mapping.clear();
-        self.mapping.shrink_to_fit();
+        self.cache = None;
+        self.cache_bytes = 0;
+        if ! self.readonly {
+            self.index.flush();
+        }
     }
 
-    pub fn get_mapping(& mut self, value : & T) -> Option<ID> {
-        match self.mapping.get(value) {
-            Some(id) => Some(*id),
-            None => None
+    /** Returns the number of bytes taken by the cached values, estimated from their serialized size. 
+     */
+    pub fn memory_size(& self) -> usize {
+        return self.cache.as_ref().map(|x| cache_map_bytes::<T, ID>(x.capacity()) + self.cache_bytes).unwrap_or(0);
+    }
+
+    /** Returns the number of bytes the cached values would take if all values of the mapping were cached, estimated from the size of the stored values. 
+     */
+    pub fn memory_estimate(& mut self) -> usize {
+        let values = self.store.len();
+        // every stored record is the id followed by the serialized value
+        let value_bytes = (self.store.filesize() as usize).saturating_sub(values * 8);
+        return cache_map_bytes::<T, ID>(values) + value_bytes;
+    }
+
+    /** Indexes the values not yet in the index, if any. 
+     */
+    fn update_index(& mut self) {
+        let size = self.store.len() as u64;
+        if self.readonly || self.index.indexed() >= size {
+            return;
+        }
+        self.index.recount();
+        self.index.reserve(size);
+        for id in self.index.indexed()..size {
+            if let Some(value) = self.store.get(ID::from(id)) {
+                self.index.insert(HashIndex::hash(& value), id);
+            }
         }
+        self.index.set_indexed(size);
+    }
+
+    /** Adds the value to the cache, if the mapping is loaded. 
+     */
+    fn cache_value(& mut self, value : & T, id : ID) {
+        if let Some(cache) = self.cache.as_mut() {
+            let mut buffer = Vec::new();
+            T::serialize(& mut buffer, value);
+            self.cache_bytes += buffer.len();
+            cache.insert(value.clone(), id);
+        }
+    }
+
+    pub fn get_mapping(& mut self, value : & T) -> Option<ID> {
+        return self.find(value, HashIndex::hash(value));
     }
 
     pub fn get_or_create_mapping(& mut self, value : & T) -> (ID, bool) {
-        match self.mapping.get(value) {
-            Some(id) => (*id, false),
+        let hash = HashIndex::hash(value);
+        match self.find(value, hash) {
+            Some(id) => (id, false),
             None => {
-                let next_id = ID::from(self.mapping.len() as u64);
+                let next_id = ID::from(self.store.len() as u64);
                 self.store.set(next_id, value);
-                self.mapping.insert(value.to_owned(), next_id);
+                self.index.insert(hash, next_id.into());
+                self.index.set_indexed(self.store.len() as u64);
+                self.cache_value(value, next_id);
                 return (next_id, true);
             }
         }
     }
 
+    /** Returns the id of given value with given hash, if the value is in the mapping. 
+     
+        The cache is consulted first, values found in the mapping are then cached. 
+     */
+    fn find(& mut self, value : & T, hash : u64) -> Option<ID> {
+        if let Some(id) = self.cache.as_ref().and_then(|x| x.get(value)) {
+            return Some(*id);
+        }
+        let result = self.find_in_index(value, hash);
+        if let Some(id) = result {
+            self.cache_value(value, id);
+        }
+        return result;
+    }
+
+    /** Returns the id of given value with given hash using the index. 
+     
+        Writers first index any values missing from the index, readers compare the values not yet indexed one by one. 
+     */
+    fn find_in_index(& mut self, value : & T, hash : u64) -> Option<ID> {
+        self.update_index();
+        let size = self.store.len() as u64;
+        for id in self.index.candidates(hash) {
+            if id < size && self.store.get(ID::from(id)).as_ref() == Some(value) {
+                return Some(ID::from(id));
+            }
+        }
+        for id in std::cmp::min(self.index.indexed(), size)..size {
+            if self.store.get(ID::from(id)).as_ref() == Some(value) {
+                return Some(ID::from(id));
+            }
+        }
+        return None;
+    }
+
     /*
     pub fn get_value(& mut self, id : ID) -> Option<T> {
         return self.store.get(id);
@@ -1244,8 +2345,10 @@ impl<T : Serializable<Item = T> + Eq + Hash + Clone, ID : Id> IndirectMapping<T,
         return self.store.len();
     }
 
+    /** Returns the number of values in the mapping's index. 
+     */
     pub fn mapping_len(& self) -> usize {
-        return self.mapping.len();
+        return self.index.indexed() as usize;
     }
 
     pub fn iter(& mut self) -> StoreIterAll<T, ID> {
@@ -1316,19 +2419,19 @@ pub (crate) struct SplitOffset<KIND : SplitKind<Item = KIND>> {
 impl<KIND : SplitKind<Item = KIND>> Serializable for SplitOffset<KIND> {
     type Item = SplitOffset<KIND>;
 
-    fn serialize(f : & mut File, value : & SplitOffset<KIND>) {
+    fn serialize<W : Write>(f : & mut W, value : & SplitOffset<KIND>) {
         u64::serialize(f, & value.offset);
         KIND::serialize(f, & value.kind);
     }
 
-    fn deserialize(f : & mut File) -> SplitOffset<KIND> {
+    fn deserialize<R : Read>(f : & mut R) -> SplitOffset<KIND> {
         return SplitOffset{
             offset : u64::deserialize(f),
             kind : KIND::deserialize(f)
         };
     }
 
-    fn verify(f : & mut File) -> Result<SplitOffset<KIND>, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<SplitOffset<KIND>, std::io::Error> {
         return Ok(SplitOffset{
             offset : u64::verify(f)?,
             kind : KIND::verify(f)?
@@ -1350,19 +2453,14 @@ impl<KIND : SplitKind<Item = KIND>> Indexable for SplitOffset<KIND> {
     TODO the internal code does not yet use this type, update the code.
  */
 pub struct SplitStorePart<T : Serializable<Item = T>, ID : Id = u64> {
-    pub (crate) f : File,
+    pub (crate) f : DataFile,
     why_oh_why : std::marker::PhantomData<(T,ID)>
 }
 
 impl<T : Serializable<Item = T>, ID : Id> SplitStorePart<T, ID> {
-    fn new<KIND : SplitKind<Item = KIND>>(root : & str, name : & str, kind : KIND, readonly : bool) -> SplitStorePart<T,ID> {
+    fn new<KIND : SplitKind<Item = KIND>>(root : & str, name : & str, kind : KIND, mode : AccessMode) -> SplitStorePart<T,ID> {
         let path = format!("{}/{}-{:?}.splitstore", root, name, kind);
-        let f;
-        if readonly {
-            f = OpenOptions::new().read(true).open(path).unwrap();
-        } else {
-            f = OpenOptions::new().read(true).write(true).create(true).open(path).unwrap();
-        }
+        let f = DataFile::open(& path, mode).unwrap();
         return SplitStorePart::<T,ID>{f, why_oh_why : std::marker::PhantomData{}};
     } 
 
@@ -1395,11 +2493,14 @@ pub trait SplitTable : Table + Sized {
 }
 
 /** Split store contains single index, but multiple files that store the data based on its kind. 
+ 
+    The lengths of the index and of all the files are committed after each update (see `CommittedLengths`), the index length first. Like with `Store`, readers get the newer record if the index points past the committed length and the record can be read. 
  */
 pub struct SplitStore<T : Serializable<Item = T>, KIND : SplitKind<Item = KIND>, ID : Id = u64> {
     name : String,
     pub (crate) indexer : Indexer<SplitOffset<KIND>, ID>,
     pub (crate) files : Vec<SplitStorePart<T,ID>>,
+    committed : CommittedLengths,
     //why_oh_why : std::marker::PhantomData<T>
     /* Which file is used by the iterator, if any. */
     file_index : usize
@@ -1419,9 +2520,8 @@ impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> Table fo
             Some(offset) => {
                 self.file_index = offset.kind.to_number() as usize;
                 let f = self.files.get_mut(self.file_index).unwrap();
-                f.f.seek(SeekFrom::Start(offset.offset)).unwrap();
                 // we can use default store reader
-                let (record_id, value) = Store::<T, ID>::read_record(& mut f.f).unwrap();
+                let (record_id, value) = Store::<T, ID>::read_record_at(& mut f.f, offset.offset)?;
                 assert_eq!(id, record_id, "Corrupted store or index");
                 return Some((KIND::from_number(self.file_index as u64), value));
             },
@@ -1472,14 +2572,33 @@ impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> SplitTab
 
 impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> SplitStore<T, KIND, ID> {
     pub fn new(root : & str, name : & str, readonly : bool) -> SplitStore<T, KIND, ID> {
+        return SplitStore::open(root, name, AccessMode::from_readonly(readonly));
+    }
+
+    /** Opens the split store with given access mode. 
+     */
+    pub fn open(root : & str, name : & str, mode : AccessMode) -> SplitStore<T, KIND, ID> {
+        let committed = if mode.is_readonly() { CommittedLengths::read(root, name, KIND::COUNT as usize + 1) } else { None };
         let mut files = Vec::<SplitStorePart<T,ID>>::new();
         for i in 0..KIND::COUNT {
-            files.push(SplitStorePart::<T, ID>::new(root, name, KIND::from_number(i), readonly));
+            files.push(SplitStorePart::<T, ID>::new(root, name, KIND::from_number(i), mode));
+        }
+        let mut indexer = Indexer::open(root, name, mode);
+        if let Some(lengths) = committed {
+            indexer.set_limit(lengths[0]);
+            for (f, length) in files.iter_mut().zip(lengths[1..].iter()) {
+                f.f.set_limit(*length);
+            }
+        }
+        let mut lengths = vec![indexer.byte_len()];
+        for f in files.iter_mut() {
+            lengths.push(f.filesize());
         }
         let result = SplitStore{
             name : name.to_owned(),
-            indexer : Indexer::new(root, name, readonly),
+            indexer,
             files, 
+            committed : CommittedLengths::open(root, name, mode, lengths),
             file_index : 0,
             //why_oh_why : std::marker::PhantomData{}
         };
@@ -1509,10 +2628,12 @@ impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> SplitSto
         let mut i = 0;
         for f in self.files.iter_mut() {
             f.f.set_len(savepoint.limit_for(& format!("{}-{}.store", self.name, i))).unwrap();
-            f.f.seek(SeekFrom::End(0)).unwrap();
             i += 1;
+            self.committed.set(i, f.f.seek(SeekFrom::End(0)).unwrap());
         }
         self.indexer.revert_to_savepoint(savepoint);
+        self.committed.set(0, self.indexer.byte_len());
+        self.committed.publish();
     }
 
     /** Verifies the split store's integrity
@@ -1548,6 +2669,10 @@ impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> SplitSto
         }
         // then check the index's integrity
         for (id, offset) in self.indexer.iter() {
+            // records added after a reader opened the store are not verified
+            if offset != SplitOffset::<KIND>::EMPTY && offset.offset >= self.files[offset.kind.to_number() as usize].f.limit() {
+                continue;
+            }
             if offset == SplitOffset::<KIND>::EMPTY {
                 let mut i = 0;
                 for mapping in latest_mappings.iter() {
@@ -1593,11 +2718,13 @@ impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> SplitSto
             },
             None => {}
         }
-        let f = self.files.get_mut(kind.to_number() as usize).unwrap();
+        let split = kind.to_number() as usize;
+        let f = self.files.get_mut(split).unwrap();
         self.indexer.set(id, & SplitOffset{
             offset : Store::<T, ID>::write_record(& mut f.f, id, value),
             kind
         });
+        self.commit(split);
     }
 
     pub (crate) fn set_raw(& mut self, id : ID, kind : KIND, length : usize, data: & [u8]) {
@@ -1607,12 +2734,24 @@ impl<T : Serializable<Item = T>, KIND: SplitKind<Item = KIND>, ID : Id> SplitSto
             },
             None => {}
         }
-        let f = self.files.get_mut(kind.to_number() as usize).unwrap();
+        let split = kind.to_number() as usize;
+        let f = self.files.get_mut(split).unwrap();
         let offset = f.f.seek(SeekFrom::End(0)).unwrap();
-        self.indexer.set(id, & SplitOffset{offset, kind});
+        // the data must be written before the index so that readers never see index pointing to incomplete record
         f.f.write_u64::<LittleEndian>(id.into()).unwrap();
         f.f.write_u64::<LittleEndian>(length as u64).unwrap();
-        f.f.write(data).unwrap();
+        f.f.write_all(data).unwrap();
+        self.indexer.set(id, & SplitOffset{offset, kind});
+        self.commit(split);
+    }
+
+    /** Commits the lengths after a record has been written to given split. 
+     */
+    fn commit(& mut self, split : usize) {
+        let end = self.files[split].f.seek(SeekFrom::Current(0)).unwrap();
+        self.committed.set(0, self.indexer.byte_len());
+        self.committed.set(split + 1, end);
+        self.committed.commit();
     }
 
     pub fn len(&self) -> usize {
@@ -1728,7 +2867,7 @@ impl std::fmt::Display for Savepoint {
 impl Serializable for Savepoint {
     type Item = Savepoint;
 
-    fn serialize(f : & mut File, value : & Savepoint) {
+    fn serialize<W : Write>(f : & mut W, value : & Savepoint) {
         i64::serialize(f, & value.time);
         String::serialize(f, & value.name);
         u32::serialize(f, & (value.sizes.len() as u32));
@@ -1738,7 +2877,7 @@ impl Serializable for Savepoint {
         }
     }
 
-    fn deserialize(f : & mut File) -> Savepoint {
+    fn deserialize<R : Read>(f : & mut R) -> Savepoint {
         let time = i64::deserialize(f);
         let name = String::deserialize(f);
         let mut records = u32::deserialize(f);
@@ -1756,7 +2895,7 @@ impl Serializable for Savepoint {
         return result;
     }
 
-    fn verify(f : & mut File) -> Result<Savepoint, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<Savepoint, std::io::Error> {
         let time = i64::verify(f)?;
         let name = String::verify(f)?;
         let mut records = u32::verify(f)?;
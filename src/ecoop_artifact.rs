@@ -20,6 +20,15 @@ mod datastore_maintenance_tasks;
 mod task_update_repo;
 mod task_update_substore;
 mod task_verify_substore;
+mod task_migrate_project;
+mod task_gc_substore;
+mod task_update_github_items;
+mod task_update_users;
+mod task_merge_aliases;
+#[allow(dead_code)]
+mod remote_control;
+mod metrics;
+mod scheduling;
 mod github;
 mod settings;
 #[allow(dead_code)]
@@ -1,8 +1,10 @@
 use std::sync::*;
+use std::collections::HashMap;
 
 use crate::helpers;
 use crate::settings::SETTINGS;
 use crate::updater::*;
+use crate::metrics::*;
 use crate::LOG;
 
 /** Access to github api. 
@@ -15,28 +17,223 @@ use crate::LOG;
 
 pub struct Github {
     tokens : Mutex<TokensManager>,
+    /** Repository metadata obtained in batches ahead of their use by their lowercased `user/repo` names, together with the time they were obtained. None while the batch is still being fetched, Some(None) if the repository was not found. 
+     */
+    prefetched : Mutex<HashMap<String, (i64, Option<Option<json::JsonValue>>)>>,
+    /** Notified whenever a batch of prefetched metadata is finished. 
+     */
+    prefetched_cv : Condvar,
 }
 
 impl Github {
 
+    /** Number of seconds after which prefetched metadata that were not used are discarded. 
+     */
+    pub const PREFETCH_TIMEOUT : i64 = 600;
+
     pub fn new(tokens : & str) -> Github {
         return Github{
             tokens : Mutex::new(TokensManager::new(tokens)),
+            prefetched : Mutex::new(HashMap::new()),
+            prefetched_cv : Condvar::new(),
+        }
+    }
+
+    /** Returns the remaining number of requests for each token as last reported by GitHub, None for tokens not used yet. 
+     */
+    pub fn tokens_remaining(& self) -> Vec<Option<u64>> {
+        return self.tokens.lock().unwrap().tokens.iter().map(|x| x.remaining).collect();
+    }
+
+    /** Returns the health of the tokens as JSON, i.e. for each token its state, remaining requests and the time of the rate limit reset, if known. 
+     
+        The tokens themselves are not included. 
+     */
+    pub fn tokens_status(& self) -> json::JsonValue {
+        let now = helpers::now();
+        let mut result = json::JsonValue::new_array();
+        for token in self.tokens.lock().unwrap().tokens.iter() {
+            let mut x = json::JsonValue::new_object();
+            x["state"] = token.state(now).into();
+            x["remaining"] = token.remaining.into();
+            x["reset"] = token.reset.into();
+            x["requests"] = token.requests.into();
+            result.push(x).unwrap();
+        }
+        return result;
+    }
+
+    /** Returns a one line summary of the tokens' health, i.e. the number of tokens in each state and the total remaining requests. 
+     */
+    pub fn tokens_summary(& self) -> String {
+        let now = helpers::now();
+        let tokens = self.tokens.lock().unwrap();
+        let count = |state| tokens.tokens.iter().filter(|x| x.state(now) == state).count();
+        return format!("{}t: {} ok, {} exhausted, {} revoked, remaining {}",
+            tokens.len(),
+            count("ok"),
+            count("exhausted"),
+            count("revoked"),
+            helpers::pretty_value(tokens.tokens.iter().filter(|x| x.state(now) == "ok").map(|x| x.remaining.unwrap_or(0) as usize).sum::<usize>()),
+        );
+    }
+
+    /** Gets the repository information for given repository if it has changed since the information with given ETag was obtained. 
+     */
+    pub fn get_repo_if_modified(& self, user_and_repo : & str, etag : Option<& str>, task : Option<& TaskStatus>) -> Result<GithubResponse, std::io::Error> {
+        return self.request_if_modified(& format!("https://api.github.com/repos/{}", user_and_repo), etag, task);
+    }
+
+    /** Gets the information about given commit of the repository, including the GitHub account of its author, if the author's email is linked to one. 
+     */
+    pub fn get_commit(& self, user_and_repo : & str, hash : & str, task : Option<& TaskStatus>) -> Result<json::JsonValue, std::io::Error> {
+        return self.request(& format!("https://api.github.com/repos/{}/commits/{}", user_and_repo, hash), task);
+    }
+
+    /** Gets the profile of given GitHub user. 
+     */
+    pub fn get_user(& self, login : & str, task : Option<& TaskStatus>) -> Result<json::JsonValue, std::io::Error> {
+        return self.request(& format!("https://api.github.com/users/{}", login), task);
+    }
+
+    /** Gets the repository information for given repositories in a single GraphQL query. 
+     
+        For each repository returns its metadata in the same format as `get_repo_if_modified` (only the subset of fields available via GraphQL, plus the `languages` breakdown and `topics`), or None if the repository was not found. 
+     */
+    pub fn get_repos(& self, user_and_repos : & [String], task : Option<& TaskStatus>) -> Result<Vec<Option<json::JsonValue>>, std::io::Error> {
+        let mut query = String::from("query {");
+        for (i, user_and_repo) in user_and_repos.iter().enumerate() {
+            let mut parts = user_and_repo.splitn(2, "/");
+            let owner = parts.next().unwrap_or("");
+            let name = parts.next().unwrap_or("");
+            query.push_str(& format!(" r{}: repository(owner: {}, name: {}) {{ ...repo }}", i, json::stringify(owner), json::stringify(name)));
+        }
+        query.push_str(" } ");
+        query.push_str(GRAPHQL_REPOSITORY_FRAGMENT);
+        let result = self.graphql(& query, task)?;
+        return Ok((0..user_and_repos.len()).map(|i| {
+            let x = & result["data"][format!("r{}", i)];
+            if x.is_object() { Some(graphql_to_rest(x)) } else { None }
+        }).collect());
+    }
+
+    /** Performs a GraphQL query and returns its result. 
+     
+        Partial results are returned as they are (missing repositories are reported as errors alongside the data), but a result without any data is an error. 
+     */
+    pub fn graphql(& self, query : & str, task : Option<& TaskStatus>) -> Result<json::JsonValue, std::io::Error> {
+        let mut body = json::JsonValue::new_object();
+        body["query"] = query.into();
+        match self.send("https://api.github.com/graphql", Some(& body.dump()), None, task)? {
+            GithubResponse::Modified{value, etag : _} => {
+                if value["data"].is_null() {
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("GraphQL query failed: {}", value["errors"])));
+                }
+                return Ok(value);
+            },
+            GithubResponse::NotModified => return Err(std::io::Error::new(std::io::ErrorKind::Other, "Unexpected 304 Not Modified")),
+        }
+    }
+
+    /** Obtains metadata of given repositories in batches and keeps them until they are taken by `take_prefetched`. 
+     
+        Repositories already prefetched, or being prefetched by another thread are skipped. If the query fails, the repositories are forgotten so that their metadata are requested individually. 
+     */
+    pub fn prefetch_repos(& self, user_and_repos : & [String], task : Option<& TaskStatus>) -> Result<(), std::io::Error> {
+        let now = helpers::now();
+        let todo : Vec<String> = {
+            let mut prefetched = self.prefetched.lock().unwrap();
+            prefetched.retain(|_, (time, _)| *time + Github::PREFETCH_TIMEOUT > now);
+            let mut todo = Vec::new();
+            for x in user_and_repos.iter().map(|x| x.to_lowercase()) {
+                if ! prefetched.contains_key(& x) {
+                    prefetched.insert(x.clone(), (now, None));
+                    todo.push(x);
+                }
+            }
+            todo
+        };
+        if todo.is_empty() {
+            return Ok(());
+        }
+        let result = self.get_repos(& todo, task);
+        let mut prefetched = self.prefetched.lock().unwrap();
+        match result {
+            Ok(result) => {
+                for (x, metadata) in todo.into_iter().zip(result.into_iter()) {
+                    prefetched.insert(x, (now, Some(metadata)));
+                }
+                self.prefetched_cv.notify_all();
+                return Ok(());
+            },
+            Err(e) => {
+                for x in todo.iter() {
+                    prefetched.remove(x);
+                }
+                self.prefetched_cv.notify_all();
+                return Err(e);
+            }
         }
     }
 
-    /** Gets the repository information for given repository. 
+    /** Returns true if the metadata of given repository have been prefetched, or are being prefetched. 
      */
-    pub fn get_repo(& self, user_and_repo : & str, task : Option<& TaskStatus>) -> Result<json::JsonValue, std::io::Error> {
-        return self.request(& format!("https://api.github.com/repos/{}", user_and_repo), task);
+    pub fn is_prefetched(& self, user_and_repo : & str) -> bool {
+        return self.prefetched.lock().unwrap().contains_key(& user_and_repo.to_lowercase());
+    }
+
+    /** Removes and returns the prefetched metadata of given repository, if available. 
+     
+        If the batch of the repository is still being fetched, waits for it to finish (at most until the prefetched metadata would time out). Returns None if the repository has not been prefetched, or was not found by the query (so that the request for it can report the proper error). 
+     */
+    pub fn take_prefetched(& self, user_and_repo : & str) -> Option<json::JsonValue> {
+        let key = user_and_repo.to_lowercase();
+        let mut prefetched = self.prefetched.lock().unwrap();
+        loop {
+            let wait = match prefetched.get(& key) {
+                Some((time, None)) => *time + Github::PREFETCH_TIMEOUT - helpers::now(),
+                _ => break,
+            };
+            if wait <= 0 {
+                break;
+            }
+            prefetched = self.prefetched_cv.wait_timeout(prefetched, std::time::Duration::from_secs(wait as u64)).unwrap().0;
+        }
+        return prefetched.remove(& key).and_then(|(_, x)| x.flatten());
     }
 
     /** Performs a github request of the specified url and returns the result string.  
      */
     pub fn request(& self, url : & str, task : Option<& TaskStatus>) -> Result<json::JsonValue, std::io::Error> {
-        let mut attempts = 0;
-        let max_attempts = self.tokens.lock().unwrap().len();
+        match self.request_if_modified(url, None, task)? {
+            GithubResponse::Modified{value, etag : _} => return Ok(value),
+            // cannot happen without the ETag
+            GithubResponse::NotModified => return Err(std::io::Error::new(std::io::ErrorKind::Other, "Unexpected 304 Not Modified")),
+        }
+    }
+
+    /** Performs a conditional github request of the specified url. 
+     
+        If the ETag of the previous response is given, it is sent in the `If-None-Match` header and GitHub responds with `304 Not Modified` if the result has not changed since, which does not count against the rate limit. Otherwise the result is returned together with its ETag, if any. 
+     
+        The request uses the token with most remaining requests. Tokens that exhaust their rate limit are not used until their limit resets, tokens hit by the secondary rate limit are not used for the time GitHub asks for and revoked tokens (401) are not used at all. When no token is available, the request waits until the earliest token becomes available again. If the request is executed as part of a task, the wait is interrupted when the task is cancelled. 
+     */
+    pub fn request_if_modified(& self, url : & str, etag : Option<& str>, task : Option<& TaskStatus>) -> Result<GithubResponse, std::io::Error> {
+        return self.send(url, None, etag, task);
+    }
+
+    /** Sends the request to given url, posting the body, if any. 
+     */
+    fn send(& self, url : & str, body : Option<& str>, etag : Option<& str>, task : Option<& TaskStatus>) -> Result<GithubResponse, std::io::Error> {
         loop {
+            let token = self.tokens.lock().unwrap().get_token();
+            let token = match token {
+                Some(token) => token,
+                None => {
+                    self.wait_for_token(task)?;
+                    continue;
+                }
+            };
             let mut response = Vec::new();
             let mut response_headers = Vec::new();
             let mut conn = Easy::new();
@@ -44,9 +241,15 @@ impl Github {
             conn.follow_location(true)?;
             let mut headers = List::new();
             headers.append("User-Agent: dcd").unwrap();
-            let token = self.tokens.lock().unwrap().get_token();
             headers.append(& format!("Authorization: token {}", token.0)).unwrap();
+            if let Some(etag) = etag {
+                headers.append(& format!("If-None-Match: {}", etag)).unwrap();
+            }
             conn.http_headers(headers)?;
+            if let Some(body) = body {
+                conn.post(true)?;
+                conn.post_fields_copy(body.as_bytes())?;
+            }
             {
                 let mut ct = conn.transfer();
                 ct.write_function(|data| {
@@ -59,55 +262,236 @@ impl Github {
                 })?;
                 ct.perform()?;
             }
-            let rhdr = helpers::to_string(& response_headers).to_lowercase();
-            if rhdr.starts_with("http/1.1 200") || rhdr.starts_with("http/1.1 301") || rhdr.starts_with("http/2 200") || rhdr.starts_with("http/2 301") {
-                let result = json::parse(& helpers::to_string(& response));
-                match result {
-                    Ok(value) => return Ok(value),
-                    Err(_) => {
-                        return Err(std::io::Error::new(std::io::ErrorKind::Other, "Cannot parse json result"));
-                    }
-                }
-            } else if rhdr.starts_with("http/1.1 401") || rhdr.starts_with("http/1.1 403") || rhdr.starts_with("http/2 401") || rhdr.starts_with("http/2 403") {
-                if rhdr.contains("x-ratelimit-remaining: 0") {
-                    // move to next token
-                    self.tokens.lock().unwrap().next_token(token.1);
-                    task.map(|t| { t.info("moving to next Github API token") });
-                // check for the secondary rate limit:)
-                } else {
+            Metrics::add(& METRICS.github_requests, 1);
+            let response_headers = helpers::to_string(& response_headers);
+            let rhdr = response_headers.to_lowercase();
+            self.tokens.lock().unwrap().update(token.1, & rhdr);
+            let status_line = rhdr.split("\n").next().unwrap().trim();
+            match status_line.split(" ").nth(1).unwrap_or("") {
+                "200" | "301" => {
                     let result = json::parse(& helpers::to_string(& response));
                     match result {
-                        Ok(value) => {
-                            if value["message"].is_string() && value["message"].as_str().unwrap() == "You have exceeded a secondary rate limit. Please wait a few minutes before you try again." {
-                                println!("Secondary rate limit: sleep 1m");
-                                std::thread::sleep(std::time::Duration::from_millis(1000 * 60));
-                            }
-                        }
+                        Ok(value) => return Ok(GithubResponse::Modified{value, etag : header(& response_headers, "etag").map(|x| x.to_owned())}),
                         Err(_) => {
+                            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Cannot parse json result"));
                         }
                     }
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, rhdr.split("\n").next().unwrap()));
+                },
+                "304" => {
+                    return Ok(GithubResponse::NotModified);
+                },
+                "401" => {
+                    // bad credentials, the token has been revoked, or has expired
+                    self.tokens.lock().unwrap().revoke(token.1);
+                    task.map(|t| { t.info("Github API token revoked, moving to next token") });
+                },
+                "403" | "429" => {
+                    if header(& rhdr, "x-ratelimit-remaining") == Some("0") {
+                        // the token is exhausted until its reset, move to next token
+                        task.map(|t| { t.info("moving to next Github API token") });
+                    } else if let Some(retry_after) = secondary_rate_limit(& rhdr, & response) {
+                        task.map(|t| { t.info(format!("secondary rate limit, retrying after {} seconds", retry_after)) });
+                        self.tokens.lock().unwrap().retry_after(token.1, retry_after);
+                    } else {
+                        return Err(GithubError::new(status_line));
+                    }
+                },
+                _ => {
+                    return Err(GithubError::new(status_line));
                 }
-            } else{
-                return Err(std::io::Error::new(std::io::ErrorKind::Other, rhdr.split("\n").next().unwrap()));
             }
-            attempts += 1;
-            // if we have too many attempts, it likely means that the tokens are all used up, wait 10 minutes is primitive and should work alright...
-            if attempts == max_attempts {
-                task.map(|t| { t.info(format!("all Github API tokens ({}) exhausted, sleeping for 10 minutes", max_attempts)) });
-                std::thread::sleep(std::time::Duration::from_millis(1000 * 60 * 10));
-                attempts = 0;
+        }
+    }
+
+    /** Waits until a token becomes available. 
+     
+        Returns an error if there are no valid tokens left, or if the task has been cancelled while waiting. 
+     */
+    fn wait_for_token(& self, task : Option<& TaskStatus>) -> Result<(), std::io::Error> {
+        let available = self.tokens.lock().unwrap().available_time();
+        let wait = match available {
+            Some(time) => std::cmp::max(time - helpers::now(), 1),
+            None => return Err(std::io::Error::new(std::io::ErrorKind::Other, "No valid Github API tokens")),
+        };
+        let tokens = self.tokens.lock().unwrap().len();
+        match task {
+            Some(t) => t.info(format!("all Github API tokens ({}) exhausted, sleeping for {}", tokens, helpers::pretty_duration(wait))),
+            None => println!("All Github API tokens ({}) exhausted, sleeping for {}", tokens, helpers::pretty_duration(wait)),
+        }
+        // sleep in short intervals so that cancelled tasks do not have to wait for the reset
+        for _ in 0..wait {
+            if let Some(t) = task {
+                t.check_cancelled()?;
             }
+            std::thread::sleep(std::time::Duration::from_millis(1000));
+        }
+        return Ok(());
+    }
+}
+
+/** Result of a conditional github request. 
+ */
+pub enum GithubResponse {
+    /** The result has changed, or the request was not conditional. 
+     */
+    Modified{value : json::JsonValue, etag : Option<String>},
+    NotModified,
+}
+
+/** Error of a github request answered with an unexpected HTTP status. 
+ 
+    The requests return it wrapped in `std::io::Error` so that it propagates like any other error, `GithubError::status_of` obtains the status back. The error displays as the response's status line. 
+ */
+#[derive(Debug)]
+pub struct GithubError {
+    pub status : u16,
+    pub status_line : String,
+}
+
+impl GithubError {
+    fn new(status_line : & str) -> std::io::Error {
+        let status = status_line.split(" ").nth(1).and_then(|x| x.parse::<u16>().ok()).unwrap_or(0);
+        return std::io::Error::new(std::io::ErrorKind::Other, GithubError{ status, status_line : status_line.to_owned() });
+    }
+
+    /** Returns the HTTP status of given error if it is a github error. 
+     */
+    pub fn status_of(e : & std::io::Error) -> Option<u16> {
+        return e.get_ref()?.downcast_ref::<GithubError>().map(|x| x.status);
+    }
+}
+
+impl std::fmt::Display for GithubError {
+    fn fmt(& self, f : & mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}", self.status_line);
+    }
+}
+
+impl std::error::Error for GithubError {}
+
+/** Fields of repositories obtained by the GraphQL queries. 
+ */
+const GRAPHQL_REPOSITORY_FRAGMENT : &'static str = "fragment repo on Repository { databaseId nameWithOwner url createdAt pushedAt isFork parent { nameWithOwner } isArchived isDisabled stargazerCount forkCount diskUsage watchers { totalCount } primaryLanguage { name } languages(first: 20, orderBy: {field: SIZE, direction: DESC}) { edges { size node { name } } } defaultBranchRef { name } licenseInfo { key name spdxId } repositoryTopics(first: 20) { nodes { topic { name } } } }";
+
+/** Converts repository obtained by GraphQL to the format of the REST API so that both can be stored and used the same. 
+ */
+fn graphql_to_rest(x : & json::JsonValue) -> json::JsonValue {
+    let mut result = json::JsonValue::new_object();
+    result["id"] = x["databaseId"].clone();
+    result["full_name"] = x["nameWithOwner"].clone();
+    result["html_url"] = x["url"].clone();
+    result["created_at"] = x["createdAt"].clone();
+    result["pushed_at"] = x["pushedAt"].clone();
+    result["fork"] = x["isFork"].clone();
+    if x["parent"].is_object() {
+        let mut parent = json::JsonValue::new_object();
+        parent["full_name"] = x["parent"]["nameWithOwner"].clone();
+        result["parent"] = parent;
+    }
+    result["archived"] = x["isArchived"].clone();
+    result["disabled"] = x["isDisabled"].clone();
+    result["stargazers_count"] = x["stargazerCount"].clone();
+    result["forks_count"] = x["forkCount"].clone();
+    result["subscribers_count"] = x["watchers"]["totalCount"].clone();
+    result["size"] = x["diskUsage"].clone();
+    result["language"] = x["primaryLanguage"]["name"].clone();
+    let mut languages = json::JsonValue::new_object();
+    for edge in x["languages"]["edges"].members() {
+        if let Some(name) = edge["node"]["name"].as_str() {
+            languages[name] = edge["size"].clone();
+        }
+    }
+    result["languages"] = languages;
+    result["default_branch"] = x["defaultBranchRef"]["name"].clone();
+    if x["licenseInfo"].is_object() {
+        let mut license = json::JsonValue::new_object();
+        license["key"] = x["licenseInfo"]["key"].clone();
+        license["name"] = x["licenseInfo"]["name"].clone();
+        license["spdx_id"] = x["licenseInfo"]["spdxId"].clone();
+        result["license"] = license;
+    } else {
+        result["license"] = json::JsonValue::Null;
+    }
+    let mut topics = json::JsonValue::new_array();
+    for node in x["repositoryTopics"]["nodes"].members() {
+        topics.push(node["topic"]["name"].clone()).unwrap();
+    }
+    result["topics"] = topics;
+    return result;
+}
+
+/** Returns the value of given header (its name in lowercase) in the response headers, if present. 
+ 
+    When redirects are followed, the headers of all responses are present, the last value is returned. 
+ */
+fn header<'a>(headers : &'a str, name : & str) -> Option<&'a str> {
+    return headers.lines()
+        .filter(|x| x.get(..name.len()).map(|x| x.eq_ignore_ascii_case(name)).unwrap_or(false))
+        .filter_map(|x| x[name.len()..].strip_prefix(":"))
+        .last()
+        .map(|x| x.trim());
+}
+
+/** Returns the number of seconds to wait if the response is a secondary rate limit, None otherwise. 
+ 
+    GitHub either sends the `retry-after` header, or only explains the limit in the response message, in which case a minute is waited. 
+ */
+fn secondary_rate_limit(headers : & str, response : & [u8]) -> Option<i64> {
+    if let Some(retry_after) = header(headers, "retry-after").and_then(|x| x.parse::<i64>().ok()) {
+        return Some(retry_after);
+    }
+    let message = json::parse(& helpers::to_string(response)).ok()?["message"].as_str()?.to_lowercase();
+    if message.contains("secondary rate limit") || message.contains("abuse detection") {
+        return Some(TokensManager::SECONDARY_RATE_LIMIT_WAIT);
+    }
+    return None;
+}
+
+/** State of a single token. 
+ */
+struct Token {
+    token : String,
+    /* Remaining requests and the time of the rate limit reset as reported by the last response, if any. */
+    remaining : Option<u64>,
+    reset : Option<i64>,
+    /* Time until which the token should not be used because of a secondary rate limit. */
+    retry_after : Option<i64>,
+    revoked : bool,
+    requests : u64,
+}
+
+impl Token {
+    /** Returns the time from which the token can be used, None if it can never be used again. 
+     */
+    fn available_time(& self) -> Option<i64> {
+        if self.revoked {
+            return None;
+        }
+        let mut result = self.retry_after.unwrap_or(0);
+        if self.remaining == Some(0) {
+            result = std::cmp::max(result, self.reset.unwrap_or(0));
+        }
+        return Some(result);
+    }
+
+    fn state(& self, now : i64) -> &'static str {
+        match self.available_time() {
+            None => return "revoked",
+            Some(time) if time > now => return "exhausted",
+            _ => return "ok",
         }
     }
 }
 
 struct TokensManager {
-    tokens : Vec<String>,
-    current : usize,
+    tokens : Vec<Token>,
 }
 
 impl TokensManager {
+    /** Number of seconds to wait after a secondary rate limit when GitHub does not say how long. 
+     */
+    const SECONDARY_RATE_LIMIT_WAIT : i64 = 60;
+
     fn new(filename : & str) -> TokensManager {
         LOG!("Loading github access tokens from {}", filename);
         let mut reader = csv::ReaderBuilder::new()
@@ -115,33 +499,73 @@ impl TokensManager {
             .double_quote(false)
             .escape(Some(b'\\'))
             .from_path(filename).unwrap();
-        let mut tokens = Vec::<String>::new();
+        let mut tokens = Vec::<Token>::new();
         for x in reader.records() {
-            tokens.push(String::from(& x.unwrap()[0]));
+            tokens.push(Token{
+                token : String::from(& x.unwrap()[0]),
+                remaining : None,
+                reset : None,
+                retry_after : None,
+                revoked : false,
+                requests : 0,
+            });
         }
         LOG!("    {} tokens found", tokens.len());
-        return TokensManager{
-            tokens, 
-            current : 0,
-        };
+        return TokensManager{ tokens };
     }
 
     fn len(& self) -> usize {
         return self.tokens.len();
     }
 
-    /** Returns a possibly valid token that should be used for the request and its id. 
+    /** Returns the token that should be used for the request and its id, or None if no token is available at the moment. 
+     
+        Picks the available token with most remaining requests, tokens not used yet are preferred as their remaining requests are not known. 
      */ 
-    fn get_token(& mut self) -> (String, usize) {
-        return (self.tokens[self.current].clone(), self.current);
+    fn get_token(& mut self) -> Option<(String, usize)> {
+        let now = helpers::now();
+        let id = (0..self.tokens.len())
+            .filter(|i| self.tokens[*i].available_time().map(|x| x <= now).unwrap_or(false))
+            .max_by_key(|i| self.tokens[*i].remaining.unwrap_or(u64::MAX))?;
+        self.tokens[id].requests += 1;
+        return Some((self.tokens[id].token.clone(), id));
+    }
+
+    /** Returns the earliest time at which any token becomes available, None if all tokens have been revoked. 
+     */
+    fn available_time(& self) -> Option<i64> {
+        return self.tokens.iter().filter_map(|x| x.available_time()).min();
     }
 
-    fn next_token(& mut self, id : usize) {
-        if self.current == id {
-            self.current += 1;
-            if self.current == self.tokens.len() {
-                self.current = 0;
+    /** Updates the token's rate limit information from the (lowercased) response headers. 
+     
+        The remaining requests are tracked for the REST API (the `core` resource). Other resources (such as `graphql`) have their own limits, which only make the token unavailable when exhausted. 
+     */
+    fn update(& mut self, id : usize, headers : & str) {
+        let token = & mut self.tokens[id];
+        if header(headers, "x-ratelimit-resource").map(|x| x != "core").unwrap_or(false) {
+            if header(headers, "x-ratelimit-remaining") == Some("0") {
+                token.retry_after = header(headers, "x-ratelimit-reset").and_then(|x| x.parse::<i64>().ok()).or(Some(helpers::now() + TokensManager::SECONDARY_RATE_LIMIT_WAIT));
             }
+            return;
+        }
+        if let Some(remaining) = header(headers, "x-ratelimit-remaining").and_then(|x| x.parse::<u64>().ok()) {
+            token.remaining = Some(remaining);
+        }
+        if let Some(reset) = header(headers, "x-ratelimit-reset").and_then(|x| x.parse::<i64>().ok()) {
+            token.reset = Some(reset);
+        }
+        // if the reset time is not known, make sure an exhausted token is not retried too soon
+        if token.remaining == Some(0) && token.reset.map(|x| x <= helpers::now()).unwrap_or(true) {
+            token.reset = Some(helpers::now() + TokensManager::SECONDARY_RATE_LIMIT_WAIT);
         }
     }
+
+    fn retry_after(& mut self, id : usize, seconds : i64) {
+        self.tokens[id].retry_after = Some(helpers::now() + seconds);
+    }
+
+    fn revoke(& mut self, id : usize) {
+        self.tokens[id].revoked = true;
+    }
 }
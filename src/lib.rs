@@ -1,6 +1,5 @@
 use std::collections::*;
 
-use std::fs::{OpenOptions};
 use byteorder::*;
 use std::io::{Read};
 
@@ -23,6 +22,15 @@ mod datastore_maintenance_tasks;
 mod task_update_repo;
 mod task_update_substore;
 mod task_verify_substore;
+mod task_migrate_project;
+mod task_gc_substore;
+mod task_update_github_items;
+mod task_update_users;
+mod task_merge_aliases;
+#[allow(dead_code)]
+mod remote_control;
+mod metrics;
+mod scheduling;
 mod github;
 #[allow(dead_code)]
 mod settings;
@@ -33,6 +41,7 @@ pub use db::Id;
 pub use db::Table;
 pub use db::TableOwningIterator;
 pub use db::SplitTable;
+pub use db::AccessMode;
 pub use crate::records::*;
 use db::*;
 
@@ -41,11 +50,21 @@ use crate::datastore::*;
 
 
 
+/** Node of the fork family graph, either a project, or a related repository that is not in the datastore. 
+ */
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum ForkNode {
+    Project(ProjectId),
+    Url(ProjectUrl),
+}
+
 /** A simple, read-only view into the datastore. 
  
+    The view can be used while the datastore is being updated, in which case every table obtained from it only reads the data committed by the updater when the table was obtained (see `db::CommittedLengths`). The updater commits the tables after every append, so the view does not lag behind it. 
  */
 pub struct DatastoreView {
-    root : String
+    root : String,
+    mode : AccessMode,
 }
 
 
@@ -55,74 +74,250 @@ impl DatastoreView {
     pub fn from(root : & str) -> DatastoreView {
         // TODO check that there is a valid datastore on the path first
         return DatastoreView{
-            root : root.to_owned()
+            root : root.to_owned(),
+            mode : AccessMode::ReadOnly,
         };
     } 
 
+    /** Returns new datastore with given root whose tables will be memory mapped. 
+     
+        Memory mapped tables are considerably faster for random access, such as when iterating over project commits via `ProjectCommitsIterator`. 
+     */
+    pub fn from_mmap(root : & str) -> DatastoreView {
+        return DatastoreView{
+            root : root.to_owned(),
+            mode : AccessMode::Mapped,
+        };
+    }
+
     pub fn project_urls(& self) -> impl Table<Id = ProjectId, Value = ProjectUrl>  {// impl Iterator<Item = (ProjectId, ProjectUrl)> {
-        return db::Store::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECTS), true); //.into_iter();
+        return db::Store::open(& self.root, & DatastoreView::table_filename(Datastore::PROJECTS), self.mode); //.into_iter();
     }
 
     pub fn project_substores(& self) -> impl Iterator<Item = (ProjectId, StoreKind)> {
-        return db::Store::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_SUBSTORES), true).into_iter();
+        return db::Store::open(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_SUBSTORES), self.mode).into_iter();
     }
 
     pub fn project_updates(& self) -> impl Iterator<Item = (ProjectId, ProjectLog)> {
-        return db::LinkedStore::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_UPDATES), true).into_iter();
+        return db::LinkedStore::open(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_UPDATES), self.mode).into_iter();
     }
 
     pub fn project_heads(& self) -> impl Iterator<Item = (ProjectId, ProjectHeads)> {
-        return db::Store::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_HEADS), true).into_iter();
+        return db::Store::open(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_HEADS), self.mode).into_iter();
     }
 
     pub fn project_metadata(& self) -> impl Iterator<Item = (ProjectId, Metadata)> {
-        return db::LinkedStore::new(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_METADATA), true).into_iter();
+        return db::LinkedStore::open(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_METADATA), self.mode).into_iter();
+    }
+
+    /** Issues of GitHub projects, in the order they were obtained. 
+     
+        Each update of an issue adds a new record, so the history of the issue can be reconstructed (see `GithubItem`). 
+     */
+    pub fn project_issues(& self) -> impl Iterator<Item = (ProjectId, GithubItem)> {
+        return db::LinkedStore::open(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_ISSUES), self.mode).into_iter();
+    }
+
+    /** Pull requests of GitHub projects, in the order they were obtained. 
+     
+        The commits referenced by the pull requests are kept as hashes, which can be translated to commit ids by `pull_request_commits`. 
+     */
+    pub fn project_pull_requests(& self) -> impl Iterator<Item = (ProjectId, GithubItem)> {
+        return db::LinkedStore::open(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_PULL_REQUESTS), self.mode).into_iter();
+    }
+
+    /** Releases of GitHub projects, in the order they were obtained. 
+     */
+    pub fn project_releases(& self) -> impl Iterator<Item = (ProjectId, GithubItem)> {
+        return db::LinkedStore::open(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_RELEASES), self.mode).into_iter();
+    }
+
+    /** Relations of projects to other projects (GitHub forks and projects sharing root commits), in the order they were found. 
+     */
+    pub fn project_relations(& self) -> impl Iterator<Item = (ProjectId, ProjectRelation)> {
+        return db::LinkedStore::open(& self.root, & DatastoreView::table_filename(Datastore::PROJECT_RELATIONS), self.mode).into_iter();
+    }
+
+    /** Returns the fork family of given project, i.e. all projects connected to it by project relations, including the project itself. 
+     
+        Relations are followed in both directions. Parent and source repositories are matched to projects by any of their current, or past urls and repositories that are not in the datastore still connect the projects related to them, such as forks of the same repository. The projects are returned in the order of their ids. 
+     */
+    pub fn fork_family(& self, id : ProjectId) -> Vec<ProjectId> {
+        let mut ids = HashMap::<ProjectUrl, ProjectId>::new();
+        for (project, url) in db::Store::<ProjectUrl, ProjectId>::open(& self.root, & DatastoreView::table_filename(Datastore::PROJECTS), self.mode).iter_all() {
+            ids.insert(url, project);
+        }
+        let mut edges = HashMap::<ForkNode, Vec<ForkNode>>::new();
+        for (project, relation) in self.project_relations() {
+            let other = match relation {
+                ProjectRelation::Parent{url} | ProjectRelation::Source{url} => match ids.get(& url) {
+                    Some(other) => ForkNode::Project(*other),
+                    None => ForkNode::Url(url),
+                },
+                ProjectRelation::SharedRoot{id} => ForkNode::Project(id),
+            };
+            edges.entry(ForkNode::Project(project)).or_insert_with(Vec::new).push(other.clone());
+            edges.entry(other).or_insert_with(Vec::new).push(ForkNode::Project(project));
+        }
+        let mut visited = HashSet::new();
+        let mut q = vec!(ForkNode::Project(id));
+        let mut result = Vec::new();
+        while let Some(node) = q.pop() {
+            if ! visited.insert(node.clone()) {
+                continue;
+            }
+            if let ForkNode::Project(project) = node {
+                result.push(project);
+            }
+            if let Some(next) = edges.get(& node) {
+                q.extend(next.iter().cloned());
+            }
+        }
+        result.sort_by_key(|x| u64::from(*x));
+        return result;
     }
 
     pub fn savepoints(& self) -> impl Iterator<Item = db::Savepoint> {
-        return db::LinkedStore::<db::Savepoint, u64>::new(& self.root, & DatastoreView::table_filename(Datastore::SAVEPOINTS), true).into_iter().map(|(_, sp)| sp);
+        return db::LinkedStore::<db::Savepoint, u64>::open(& self.root, & DatastoreView::table_filename(Datastore::SAVEPOINTS), self.mode).into_iter().map(|(_, sp)| sp);
     }
 
     /* Substore contents getters and iterators. 
      */
     pub fn commits(& self, substore : StoreKind) -> impl Table<Id = CommitId, Value = SHA> {
-        return db::Mapping::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS), true);
+        return db::Mapping::open(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS), self.mode);
     }
 
     pub fn commits_info(& self, substore : StoreKind) -> impl Table<Id = CommitId, Value = CommitInfo> {
-        return db::Store::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS_INFO), true);
+        return db::Store::open(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS_INFO), self.mode);
     }
 
     pub fn commits_metadata(& self, substore : StoreKind) -> impl Iterator<Item = (CommitId, Metadata)> {
-        return db::LinkedStore::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS_METADATA), true).into_iter();
+        return db::LinkedStore::open(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS_METADATA), self.mode).into_iter();
     }
 
     pub fn hashes(& self, substore : StoreKind) -> impl Table<Id = HashId, Value = SHA> {
-        return db::Mapping::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::HASHES), true);
+        return db::Mapping::open(& self.root, & DatastoreView::substore_table_filename(substore, Substore::HASHES), self.mode);
     }
 
     pub fn contents(& self, substore : StoreKind) -> impl SplitTable<Id = HashId, Value = (ContentsKind, FileContents), Kind = ContentsKind, SplitIterator = db::SplitStorePart<FileContents, HashId>> {
-        return db::SplitStore::<FileContents, ContentsKind, HashId>::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::CONTENTS),true);
+        return db::SplitStore::<FileContents, ContentsKind, HashId>::open(& self.root, & DatastoreView::substore_table_filename(substore, Substore::CONTENTS), self.mode);
     }
 
     pub fn contents_metadata(& self, substore : StoreKind) -> impl Iterator<Item = (HashId, Metadata)> {
-        return db::LinkedStore::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::CONTENTS_METADATA), true).into_iter().into_iter();
+        return db::LinkedStore::open(& self.root, & DatastoreView::substore_table_filename(substore, Substore::CONTENTS_METADATA), self.mode).into_iter().into_iter();
     }
 
     pub fn paths(& self, substore : StoreKind) -> impl Table<Id = PathId, Value = SHA> {
-        return db::Mapping::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::PATHS), true);
+        return db::Mapping::open(& self.root, & DatastoreView::substore_table_filename(substore, Substore::PATHS), self.mode);
     }
 
     pub fn paths_strings(& self, substore : StoreKind) -> impl Table<Id = PathId, Value = PathString> {
-        return db::Store::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::PATHS_STRINGS), true);
+        return db::Store::open(& self.root, & DatastoreView::substore_table_filename(substore, Substore::PATHS_STRINGS), self.mode);
     }
 
     pub fn users(& self, substore : StoreKind) -> impl Table<Id = UserId, Value = String> {
-        return db::IndirectMapping::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::USERS), true);
+        return db::IndirectMapping::open(& self.root, & DatastoreView::substore_table_filename(substore, Substore::USERS), self.mode);
     }
 
     pub fn users_metadata(& self, substore : StoreKind) -> impl Iterator<Item = (UserId, Metadata)> {
-        return db::LinkedStore::new(& self.root, & DatastoreView::substore_table_filename(substore, Substore::USERS_METADATA), true).into_iter();
+        return db::LinkedStore::open(& self.root, & DatastoreView::substore_table_filename(substore, Substore::USERS_METADATA), self.mode).into_iter();
+    }
+
+    /** GitHub logins of the users of the substore, in the order they were obtained. 
+     
+        Only users linked to GitHub accounts are returned (see the `users` updater command). 
+     */
+    pub fn github_logins(& self, substore : StoreKind) -> impl Iterator<Item = (UserId, String)> {
+        return self.users_metadata(substore).filter(|(_, x)| x.key == Metadata::GITHUB_LOGIN && ! x.value.is_empty()).map(|(id, x)| (id, x.value));
+    }
+
+    /** GitHub profiles of the users of the substore, in the order they were obtained. 
+     
+        A new profile is stored every time it changes, so the history of the profiles is available. 
+     */
+    pub fn github_users(& self, substore : StoreKind) -> impl Iterator<Item = (UserId, GithubUser)> {
+        return self.users_metadata(substore).filter(|(_, x)| x.key == Metadata::GITHUB_PROFILE).filter_map(|(id, x)| GithubUser::from_json(& x.value).map(|user| (id, user)));
+    }
+
+    /** Canonical identities of the users of the substore. 
+     
+        Only users merged with another user (see the `aliases` updater command) are present, all other users are their own canonical identity. 
+     */
+    pub fn user_aliases(& self, substore : StoreKind) -> HashMap<UserId, UserId> {
+        return db::Store::<u64, UserId>::open(& self.root, & DatastoreView::substore_table_filename(substore, Substore::USERS_ALIASES), self.mode).into_iter().map(|(id, canonical)| (id, UserId::from(canonical))).collect();
+    }
+
+    /** Commits of the substore grouped by their canonical authors. 
+     
+        Returns each canonical author together with all commits authored by any of their aliases. The commits of each author are in the order of their ids. 
+     */
+    pub fn canonical_author_commits(& self, substore : StoreKind) -> impl Iterator<Item = (UserId, Vec<CommitId>)> {
+        let aliases = self.user_aliases(substore);
+        let mut result = HashMap::<UserId, Vec<CommitId>>::new();
+        for (id, cinfo) in db::Store::<CommitInfo, CommitId>::open(& self.root, & DatastoreView::substore_table_filename(substore, Substore::COMMITS_INFO), self.mode) {
+            let author = aliases.get(& cinfo.author).copied().unwrap_or(cinfo.author);
+            result.entry(author).or_insert_with(Vec::new).push(id);
+        }
+        return result.into_iter();
+    }
+
+    /** Returns the id of the commit with given hash and the substore in which it was found. 
+     
+        If no substore is given, all substores are searched and the first one containing the commit is returned. The lookup uses the mapping's hash index so that the mapping does not have to be read. 
+     */
+    pub fn commit_id(& self, substore : Option<StoreKind>, hash : & SHA) -> Option<(StoreKind, CommitId)> {
+        return self.find_in_substores(substore, |kind| {
+            return db::Mapping::<SHA, CommitId>::open(& self.root, & DatastoreView::substore_table_filename(kind, Substore::COMMITS), self.mode).get_mapping(hash);
+        });
+    }
+
+    /** Returns the ids of the commits referenced by given pull request and the substores in which they were found. 
+     
+        The commits are looked up like in `commit_id`, so the current substore of the pull request's project should be given as the ids are only valid within their substore. Commits not in the datastore (such as head commits of pull requests from forks that were never fetched) are skipped. 
+     */
+    pub fn pull_request_commits(& self, substore : Option<StoreKind>, pull_request : & GithubItem) -> Vec<(StoreKind, CommitId)> {
+        return pull_request.commits.iter().filter_map(|hash| self.commit_id(substore, hash)).collect();
+    }
+
+    /** Returns the id of the file contents with given hash and the substore in which it was found. 
+     
+        If no substore is given, all substores are searched. 
+     */
+    pub fn hash_id(& self, substore : Option<StoreKind>, hash : & SHA) -> Option<(StoreKind, HashId)> {
+        return self.find_in_substores(substore, |kind| {
+            return db::Mapping::<SHA, HashId>::open(& self.root, & DatastoreView::substore_table_filename(kind, Substore::HASHES), self.mode).get_mapping(hash);
+        });
+    }
+
+    /** Returns the id of given path and the substore in which it was found. 
+     
+        Paths are mapped by the hashes of their strings. If no substore is given, all substores are searched. 
+     */
+    pub fn path_id(& self, substore : Option<StoreKind>, path : & str) -> Option<(StoreKind, PathId)> {
+        let hash = Datastore::hash_of(path.as_bytes());
+        return self.find_in_substores(substore, |kind| {
+            return db::Mapping::<SHA, PathId>::open(& self.root, & DatastoreView::substore_table_filename(kind, Substore::PATHS), self.mode).get_mapping(& hash);
+        });
+    }
+
+    /** Returns the id of the user with given email and the substore in which it was found. 
+     
+        If no substore is given, all substores are searched. 
+     */
+    pub fn user_id(& self, substore : Option<StoreKind>, email : & str) -> Option<(StoreKind, UserId)> {
+        let email = email.to_owned();
+        return self.find_in_substores(substore, |kind| {
+            return db::IndirectMapping::<String, UserId>::open(& self.root, & DatastoreView::substore_table_filename(kind, Substore::USERS), self.mode).get_mapping(& email);
+        });
+    }
+
+    /** Searches given substore, or all substores if none is given, for the first substore in which the lookup succeeds. 
+     */
+    fn find_in_substores<ID>(& self, substore : Option<StoreKind>, lookup : impl Fn(StoreKind) -> Option<ID>) -> Option<(StoreKind, ID)> {
+        match substore {
+            Some(kind) => return lookup(kind).map(|id| (kind, id)),
+            None => return db::SplitKindIter::<StoreKind>::new().find_map(|kind| lookup(kind).map(|id| (kind, id))),
+        }
     }
 
     fn table_filename(table : & str) -> String {
@@ -171,6 +366,31 @@ impl<T : Table<Id = CommitId, Value = CommitInfo>> ProjectCommitsIterator<T> {
     }
 }
 
+/** GitHub profile of a user. 
+ */
+pub struct GithubUser {
+    pub login : String,
+    pub name : Option<String>,
+    pub location : Option<String>,
+    pub company : Option<String>,
+    /** Time the account was created. 
+     */
+    pub created : Option<i64>,
+}
+
+impl GithubUser {
+    fn from_json(value : & str) -> Option<GithubUser> {
+        let json = json::parse(value).ok()?;
+        return Some(GithubUser{
+            login : json["login"].as_str()?.to_owned(),
+            name : json["name"].as_str().map(|x| x.to_owned()),
+            location : json["location"].as_str().map(|x| x.to_owned()),
+            company : json["company"].as_str().map(|x| x.to_owned()),
+            created : json["created_at"].as_str().and_then(|x| chrono::NaiveDateTime::parse_from_str(x, "%Y-%m-%dT%H:%M:%SZ").ok()).map(|x| x.timestamp()),
+        });
+    }
+}
+
 /** Information about an assembled project. 
  */
 pub struct Project {
@@ -187,8 +407,8 @@ impl Project {
         return Project{
             url,
             substore,
-            latest_status : ProjectLog::Error{time : 0, version : datastore::Datastore::VERSION, error : "no_data".to_owned()},
-            latest_valid_status : ProjectLog::Error{time : 0, version : datastore::Datastore::VERSION, error : "no_data".to_owned()},
+            latest_status : ProjectLog::Error{time : 0, version : datastore::Datastore::VERSION, code : ErrorCode::Unknown, error : "no_data".to_owned()},
+            latest_valid_status : ProjectLog::Error{time : 0, version : datastore::Datastore::VERSION, code : ErrorCode::Unknown, error : "no_data".to_owned()},
             heads : ProjectHeads::new(),
         };
     }
@@ -202,6 +422,23 @@ impl Project {
 
     }
 
+    /** Returns true if the project's repository has been deleted, or is permanently unavailable. 
+     */
+    pub fn is_deleted(& self) -> bool {
+        return self.latest_status.is_tombstone();
+    }
+
+    /** Returns the time at which the project was found deleted, if it is deleted. 
+     
+        This is the time of the update that found the repository deleted, the actual deletion happened between this and the previous update. 
+     */
+    pub fn deletion_time(& self) -> Option<i64> {
+        match self.latest_status {
+            ProjectLog::Tombstone{time, version : _, reason : _} => return Some(time),
+            _ => return None,
+        }
+    }
+
     pub fn latest_valid_update_time(& self) -> Option<i64> {
         match self.latest_valid_status {
             ProjectLog::NoChange{time, version : _} => return Some(time),
@@ -381,7 +618,7 @@ impl DatastoreMerger {
         for i in 0..ContentsKind::COUNT {
             let contents_kind = ContentsKind::from_number(i);
             println!("    {:?}", contents_kind);
-            let mut f = /*BufReader::new(*/ OpenOptions::new().read(true).open(format!("{}/{:?}/{:?}-contents-{:?}.splitstore", self.source.root, context.source_substore,context.source_substore, contents_kind)).unwrap() /*)*/;
+            let mut f = DataFile::open(& format!("{}/{:?}/{:?}-contents-{:?}.splitstore", self.source.root, context.source_substore,context.source_substore, contents_kind), self.source.mode).unwrap();
             while let Ok(id) = f.read_u64::<LittleEndian>() {
                 // if the id was ok, get the compressed contents and its length
                 let source_id = HashId::from(id);
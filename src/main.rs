@@ -18,6 +18,14 @@ mod datastore_maintenance_tasks;
 mod task_update_repo;
 mod task_update_substore;
 mod task_verify_substore;
+mod task_migrate_project;
+mod task_gc_substore;
+mod task_update_github_items;
+mod task_update_users;
+mod task_merge_aliases;
+mod remote_control;
+mod metrics;
+mod scheduling;
 mod github;
 mod settings;
 #[allow(dead_code)]
@@ -32,6 +40,7 @@ use reporter::*;
 
 use settings::SETTINGS;
 use task_update_repo::*;
+use remote_control::*;
 
 use crate::db::Serializable;
 use crate::db::Indexable;
@@ -80,6 +89,12 @@ fn execute_command() {
         "add" => datastore_add(SETTINGS.command.get(1).unwrap()),
         "create-savepoint" => datastore_create_savepoint(SETTINGS.command.get(1).unwrap()),
         "revert-to-savepoint" => datastore_revert_to_savepoint(SETTINGS.command.get(1).unwrap()),
+        "gc" => datastore_gc(SETTINGS.command.get(1).unwrap()),
+        "compact" => datastore_compact(
+            SETTINGS.command.get(1).unwrap(), // table
+            SETTINGS.command.get(2) // savepoint
+        ),
+        "control" => updater_control(SETTINGS.command[1..].join(" ")),
         "update-project" => datastore_update_project(
             SETTINGS.command.get(1).unwrap(),
             SETTINGS.command.get(2),
@@ -205,12 +220,48 @@ fn datastore_revert_to_savepoint(name : & str) {
     {
         let ds = Datastore::new(& SETTINGS.datastore_root, false);
         let sp = ds.get_savepoint(name).unwrap();
+        if let Some(rewrite) = ds.get_rewrite_savepoint_after(& sp) {
+            println!("ERROR: Cannot revert to savepoint {}, datastore files were rewritten since (savepoint {})", sp.name(), rewrite.name());
+            return;
+        }
         ds.revert_to_savepoint(&sp);
     }
     datastore_size();
 }
 
-/** Forces the update of given project. 
+/** Garbage collects given substore, removing all data not reachable from its projects. 
+ */
+fn datastore_gc(store : & str) {
+    let kind = records::StoreKind::from_string(store).unwrap();
+    TerminalReporter::report(|reporter : & TerminalReporter| {
+        let ds = Datastore::new(& SETTINGS.datastore_root, false);
+        reporter.run_task(Task::GarbageCollect{store : kind}, |ts| {
+            return task_gc_substore::task_gc_substore(& ds, kind, ts);
+        });
+    });
+}
+
+/** Compacts given table, optionally keeping its history since given savepoint. 
+ */
+fn datastore_compact(table : & str, savepoint : Option<& String>) {
+    TerminalReporter::report(|reporter : & TerminalReporter| {
+        let ds = Datastore::new(& SETTINGS.datastore_root, false);
+        reporter.run_task(Task::CompactTable{table : table.to_owned(), savepoint : savepoint.cloned()}, |ts| {
+            return datastore_maintenance_tasks::task_compact_table(& ds, ts);
+        });
+    });
+}
+
+/** Sends given command to the updater running with a control socket and prints its response.
+ */
+fn updater_control(command : String) {
+    let path = SETTINGS.control_socket.as_ref().expect("Control socket path must be specified");
+    if let Err(e) = remote_command(path, & command) {
+        println!("ERROR: Unable to control updater at {}: {}", path, e);
+    }
+}
+
+/** Forces the update of given project.
  */
 fn datastore_update_project(project : & str, force_opt : Option<& String>) {
     let mut force = false;
@@ -228,7 +279,7 @@ fn datastore_update_project(project : & str, force_opt : Option<& String>) {
         if let Some((id, _)) = p {
             reporter.run_task(Task::UpdateRepo{
                 id : id, 
-                last_update_time : ds.get_project_last_update(id).map(|x| x.time()).or(Some(0)).unwrap()
+                due_time : ds.get_project_last_update(id).map(|x| x.time()).or(Some(0)).unwrap()
             }, |ts| {
                 return task_update_repo(& ds, & gh, ts, force, true);
             });
@@ -32,6 +32,21 @@ mod datastore_maintenance_tasks;
 mod task_update_substore;
 #[allow(dead_code)]
 mod task_update_repo;
+#[allow(dead_code)]
+mod task_migrate_project;
+#[allow(dead_code)]
+mod task_gc_substore;
+#[allow(dead_code)]
+mod task_update_github_items;
+#[allow(dead_code)]
+mod task_update_users;
+#[allow(dead_code)]
+mod task_merge_aliases;
+#[allow(dead_code)]
+mod remote_control;
+mod metrics;
+#[allow(dead_code)]
+mod scheduling;
 
 use settings::SETTINGS;
 use github::Github;
@@ -100,23 +115,51 @@ type Tx = crossbeam_channel::Sender<UpdateInfo>;
 
 fn metadata_scrapper(projects : & Mutex<Vec<(i64, String)>>, gh : &Github, tx : Tx) {
     let mut limit = 100000;
-    while let Some((id, full_name)) = next_project_to_update(projects) { 
-        let metadata_request = format!("https://api.github.com/repos/{}", full_name);
+    let batch_size = std::cmp::max(SETTINGS.graphql_batch, 1);
+    loop {
+        let mut batch = Vec::new();
+        while batch.len() < batch_size {
+            match next_project_to_update(projects) {
+                Some(x) => batch.push(x),
+                None => break,
+            }
+        }
+        if batch.is_empty() {
+            break;
+        }
         std::thread::sleep(std::time::Duration::from_millis(1000));
-        match gh.request(& metadata_request, None) {
-            Ok(json) => {
-                if let Some(pinfo) = ProjectInfo::from_json(& json) {
-                    tx.send(UpdateInfo::Ok{id, csv_row : pinfo.to_csv()}).unwrap();
-                } else {
-                    tx.send(UpdateInfo::Fail{id, err : format!("{}",json)}).unwrap();
+        // get the metadata of the whole batch with a single GraphQL query, projects not found, or all projects if the query fails are requested individually so that the proper error is reported
+        let names : Vec<String> = batch.iter().map(|(_, full_name)| full_name.clone()).collect();
+        let results = if SETTINGS.graphql_batch > 0 {
+            gh.get_repos(& names, None).unwrap_or_else(|e| {
+                println!("GraphQL query failed: {}", e);
+                vec!(None; batch.len())
+            })
+        } else {
+            vec!(None; batch.len())
+        };
+        for ((id, full_name), json) in batch.into_iter().zip(results.into_iter()) {
+            let json = match json {
+                Some(json) => json,
+                None => {
+                    let metadata_request = format!("https://api.github.com/repos/{}", full_name);
+                    match gh.request(& metadata_request, None) {
+                        Ok(json) => json,
+                        Err(e) => {
+                            tx.send(UpdateInfo::Fail{id, err : format!("{}",e)}).unwrap();
+                            continue;
+                        }
+                    }
                 }
-            },
-            Err(e) =>{
-                tx.send(UpdateInfo::Fail{id, err : format!("{}",e)}).unwrap();
+            };
+            if let Some(pinfo) = ProjectInfo::from_json(& json) {
+                tx.send(UpdateInfo::Ok{id, csv_row : pinfo.to_csv()}).unwrap();
+            } else {
+                tx.send(UpdateInfo::Fail{id, err : format!("{}",json)}).unwrap();
             }
+            limit -= 1;
         }
-        limit -= 1;
-        if limit == 0 {
+        if limit <= 0 {
             println!("limit reached!");
             break;
         }
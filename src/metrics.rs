@@ -0,0 +1,207 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::io::{Read, Write};
+use std::collections::VecDeque;
+
+use crate::updater::*;
+use crate::datastore::*;
+use crate::helpers;
+use crate::settings::SETTINGS;
+use crate::LOG;
+
+lazy_static! {
+    pub static ref METRICS : Metrics = Metrics::new();
+}
+
+/** Counters updated by the tasks and exported together with the updater's status by the metrics exporter.
+ */
+pub struct Metrics {
+    pub tasks_done : AtomicU64,
+    pub tasks_error : AtomicU64,
+    pub bytes_fetched : AtomicU64,
+    pub commits_added : AtomicU64,
+    pub snapshots_added : AtomicU64,
+    pub github_requests : AtomicU64,
+}
+
+impl Metrics {
+    fn new() -> Metrics {
+        return Metrics{
+            tasks_done : AtomicU64::new(0),
+            tasks_error : AtomicU64::new(0),
+            bytes_fetched : AtomicU64::new(0),
+            commits_added : AtomicU64::new(0),
+            snapshots_added : AtomicU64::new(0),
+            github_requests : AtomicU64::new(0),
+        };
+    }
+
+    pub fn add(counter : & AtomicU64, value : u64) {
+        counter.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn get(counter : & AtomicU64) -> u64 {
+        return counter.load(Ordering::Relaxed);
+    }
+}
+
+/** Exports the updater's metrics in the Prometheus text format.
+
+    If the target is an address (`host:port`, or just `:port` for localhost), the metrics are served over HTTP, otherwise the target is a file that is rewritten every few seconds (suitable for the textfile collector of the node exporter). Errors accepting the connections, or writing the file are logged and retried later so that they do not bring the updater down. The exporter terminates when the updater stops.
+ */
+pub (crate) fn metrics_exporter(updater : & Updater, target : & str) {
+    let mut exporter = MetricsExporter::new();
+    match metrics_address(target) {
+        Some(address) => {
+            let listener = std::net::TcpListener::bind(& address).expect(& format!("Unable to bind metrics endpoint to {}", address));
+            listener.set_nonblocking(true).unwrap();
+            while ! updater.should_stop() {
+                match listener.accept() {
+                    Ok((mut stream, _)) => {
+                        // we do not care about the request itself, read what is available and always respond with the metrics
+                        if stream.set_nonblocking(false).is_err() || stream.set_read_timeout(Some(std::time::Duration::from_millis(100))).is_err() {
+                            continue;
+                        }
+                        let mut request = [0; 1024];
+                        let _ = stream.read(& mut request);
+                        let body = exporter.export(updater);
+                        let _ = write!(stream, "HTTP/1.0 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+                    },
+                    Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        exporter.sample();
+                        std::thread::sleep(std::time::Duration::from_millis(500));
+                    },
+                    Err(e) => {
+                        LOG!("Metrics endpoint error: {:?}", e);
+                        std::thread::sleep(std::time::Duration::from_millis(500));
+                    }
+                }
+            }
+        },
+        None => {
+            let tmp = format!("{}.tmp", target);
+            let mut failing = false;
+            while ! updater.should_stop() {
+                // write to a temporary file first so that the collector never reads incomplete metrics
+                match std::fs::write(& tmp, exporter.export(updater)).and_then(|_| std::fs::rename(& tmp, target)) {
+                    Ok(_) => failing = false,
+                    Err(e) => {
+                        // only log the first of consecutive failures
+                        if ! failing {
+                            LOG!("Unable to write metrics to {}: {:?}", target, e);
+                        }
+                        failing = true;
+                    }
+                }
+                std::thread::sleep(std::time::Duration::from_millis(1000 * MetricsExporter::FILE_INTERVAL));
+            }
+        }
+    }
+}
+
+/** Returns the address to bind to if the metrics target is an address, None if it is a file.
+ */
+fn metrics_address(target : & str) -> Option<String> {
+    let (host, port) = target.split_at(target.rfind(':')?);
+    if host.contains('/') || port[1..].parse::<u16>().is_err() {
+        return None;
+    }
+    return Some(format!("{}{}", if host.is_empty() { "127.0.0.1" } else { host }, port));
+}
+
+/** Keeps the samples of the counters required to calculate the per minute rates.
+ */
+struct MetricsExporter {
+    start_time : i64,
+    /* time, commits and snapshots added */
+    samples : VecDeque<(i64, u64, u64)>,
+}
+
+impl MetricsExporter {
+    /** Seconds between updates of the textfile.
+     */
+    const FILE_INTERVAL : u64 = 5;
+
+    fn new() -> MetricsExporter {
+        return MetricsExporter{
+            start_time : helpers::now(),
+            samples : VecDeque::new(),
+        };
+    }
+
+    /** Takes a sample of the counters, keeping the samples from the last minute.
+     */
+    fn sample(& mut self) {
+        let now = helpers::now();
+        if self.samples.back().map(|x| x.0 != now).unwrap_or(true) {
+            self.samples.push_back((now, Metrics::get(& METRICS.commits_added), Metrics::get(& METRICS.snapshots_added)));
+        }
+        while self.samples.front().map(|x| now - x.0 > 60).unwrap_or(false) {
+            self.samples.pop_front();
+        }
+    }
+
+    /** Returns commits and snapshots added per minute.
+     */
+    fn per_minute(& self) -> (f64, f64) {
+        if let (Some(first), Some(last)) = (self.samples.front(), self.samples.back()) {
+            if last.0 > first.0 {
+                let minutes = (last.0 - first.0) as f64 / 60.0;
+                return ((last.1 - first.1) as f64 / minutes, (last.2 - first.2) as f64 / minutes);
+            }
+        }
+        return (0.0, 0.0);
+    }
+
+    fn export(& mut self, updater : & Updater) -> String {
+        self.sample();
+        let mut result = String::new();
+        metric(& mut result, "parasite_uptime_seconds", "gauge", "Time since the updater started.", & [("", (helpers::now() - self.start_time) as f64)]);
+        {
+            let pool = updater.pool.lock().unwrap();
+            metric(& mut result, "parasite_workers", "gauge", "Number of worker threads by their state.", & [
+                ("state=\"running\"", pool.running_workers as f64),
+                ("state=\"idle\"", pool.idle_workers as f64),
+                ("state=\"paused\"", pool.paused_workers as f64),
+            ]);
+            metric(& mut result, "parasite_queue_size", "gauge", "Number of tasks waiting in the queue.", & [("", pool.queue.len() as f64)]);
+        }
+        metric(& mut result, "parasite_tasks_done_total", "counter", "Number of successfully finished tasks.", & [("", Metrics::get(& METRICS.tasks_done) as f64)]);
+        metric(& mut result, "parasite_tasks_error_total", "counter", "Number of tasks finished with an error.", & [("", Metrics::get(& METRICS.tasks_error) as f64)]);
+        metric(& mut result, "parasite_projects", "gauge", "Number of projects in the datastore.", & [("", updater.ds.num_projects() as f64)]);
+        let substores : Vec<(String, & Substore)> = updater.ds.substores_iter().map(|x| (format!("substore=\"{:?}\"", x.prefix), x)).collect();
+        metric(& mut result, "parasite_substore_loaded", "gauge", "Whether the substore is loaded in memory.",
+            & substores.iter().map(|(label, x)| (label.as_str(), if x.is_loaded() { 1.0 } else { 0.0 })).collect::<Vec<(& str, f64)>>()
+        );
+        let (requests, tokens) = (Metrics::get(& METRICS.github_requests), updater.github.tokens_remaining());
+        metric(& mut result, "parasite_github_requests_total", "counter", "Number of GitHub API requests.", & [("", requests as f64)]);
+        metric(& mut result, "parasite_github_tokens", "gauge", "Number of GitHub API tokens.", & [("", tokens.len() as f64)]);
+        let labels : Vec<String> = (0..tokens.len()).map(|i| format!("token=\"{}\"", i)).collect();
+        metric(& mut result, "parasite_github_token_remaining", "gauge", "Remaining GitHub API requests of the token as last reported by GitHub.",
+            & tokens.iter().enumerate().filter_map(|(i, x)| x.map(|remaining| (labels[i].as_str(), remaining as f64))).collect::<Vec<(& str, f64)>>()
+        );
+        metric(& mut result, "parasite_fetched_bytes_total", "counter", "Bytes fetched from the cloned repositories.", & [("", Metrics::get(& METRICS.bytes_fetched) as f64)]);
+        metric(& mut result, "parasite_commits_added_total", "counter", "Number of commits added to the datastore.", & [("", Metrics::get(& METRICS.commits_added) as f64)]);
+        metric(& mut result, "parasite_snapshots_added_total", "counter", "Number of file snapshots added to the datastore.", & [("", Metrics::get(& METRICS.snapshots_added) as f64)]);
+        let (commits, snapshots) = self.per_minute();
+        metric(& mut result, "parasite_commits_added_per_minute", "gauge", "Commits added during the last minute.", & [("", commits)]);
+        metric(& mut result, "parasite_snapshots_added_per_minute", "gauge", "Snapshots added during the last minute.", & [("", snapshots)]);
+        let (pid, mem, cpu) = helpers::process_resources();
+        metric(& mut result, "parasite_process_id", "gauge", "Process id of the updater.", & [("", pid as f64)]);
+        metric(& mut result, "parasite_process_cpu_percent", "gauge", "CPU usage of the updater.", & [("", cpu as f64)]);
+        metric(& mut result, "parasite_process_memory_percent", "gauge", "Memory usage of the updater.", & [("", mem as f64)]);
+        return result;
+    }
+}
+
+/** Appends a metric with its help, type and values for given labels.
+ */
+fn metric(output : & mut String, name : & str, kind : & str, help : & str, values : & [(& str, f64)]) {
+    output.push_str(& format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+    for (labels, value) in values {
+        if labels.is_empty() {
+            output.push_str(& format!("{} {}\n", name, value));
+        } else {
+            output.push_str(& format!("{}{{{}}} {}\n", name, labels, value));
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use std::fs::{File};
 use std::io::{Read, Write};
 use std::collections::*;
 use byteorder::*;
@@ -307,15 +306,15 @@ impl SplitKind for StoreKind {
 
 impl Serializable for StoreKind {
     type Item = StoreKind;
-    fn serialize(f : & mut File, value : & StoreKind) {
+    fn serialize<W : Write>(f : & mut W, value : & StoreKind) {
         f.write_u16::<LittleEndian>(value.to_number() as u16).unwrap();
     }
 
-    fn deserialize(f : & mut File) -> StoreKind {
+    fn deserialize<R : Read>(f : & mut R) -> StoreKind {
         return StoreKind::from_number(f.read_u16::<LittleEndian>().unwrap() as u64);
     }
 
-    fn verify(f : & mut File) -> Result<StoreKind, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<StoreKind, std::io::Error> {
         let index = u16::verify(f)? as u64;
         if index >= Self::COUNT {
             return Err(std::io::Error::new(std::io::ErrorKind::Other, "Invalid store kind index"));
@@ -448,7 +447,7 @@ impl ProjectUrl {
 
 impl Serializable for ProjectUrl {
     type Item = ProjectUrl;
-    fn serialize(f : & mut File, value : & ProjectUrl) {
+    fn serialize<W : Write>(f : & mut W, value : & ProjectUrl) {
         match value {
             ProjectUrl::Git{url} => {
                 u8::serialize(f, & 0);
@@ -461,7 +460,7 @@ impl Serializable for ProjectUrl {
         }
     }
 
-    fn deserialize(f : & mut File) -> ProjectUrl {
+    fn deserialize<R : Read>(f : & mut R) -> ProjectUrl {
         match u8::deserialize(f) {
             0 => {
                 let url = String::deserialize(f);
@@ -475,7 +474,7 @@ impl Serializable for ProjectUrl {
         }
     }
 
-    fn verify(f : & mut File) -> Result<ProjectUrl, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<ProjectUrl, std::io::Error> {
         match u8::verify(f)? {
             0 => {
                 let url = String::verify(f)?;
@@ -490,6 +489,136 @@ impl Serializable for ProjectUrl {
     }
 }
 
+/** Classification of errors encountered while updating a project. 
+ 
+    The class determines whether and when the project update is retried (see `retry_interval`). 
+ */
+#[derive(Clone, Copy, Debug, std::cmp::PartialEq, std::cmp::Eq, std::hash::Hash)]
+pub enum ErrorCode {
+    Unknown, // 0
+    /** The repository does not exist (anymore), or is not available (such as DMCA blocked). 
+     */
+    NotFound, // 1
+    /** The repository requires authentication, i.e. it is private, or deleted (GitHub asks for credentials when cloning a deleted repository). 
+     */
+    AuthRequired, // 2
+    /** GitHub API rate limit has been exceeded. 
+     */
+    RateLimit, // 3
+    /** Network errors, such as timeouts, unresolved addresses, or broken connections. 
+     */
+    Network, // 4
+    /** The repository is too large to be processed. 
+     */
+    TooLarge, // 5
+    /** Internal libgit2 errors. 
+     */
+    Internal, // 6
+}
+
+impl ErrorCode {
+    /** Number of consecutive not found errors after which the project is considered permanently gone and is no longer retried. 
+     */
+    pub const NOT_FOUND_ATTEMPTS : u32 = 3;
+
+    /** The longest interval between retries of a failed project update (90 days). 
+     */
+    pub const MAX_RETRY_INTERVAL : i64 = 90 * 24 * 3600;
+
+    pub fn to_number(& self) -> u8 {
+        match self {
+            ErrorCode::Unknown => 0,
+            ErrorCode::NotFound => 1,
+            ErrorCode::AuthRequired => 2,
+            ErrorCode::RateLimit => 3,
+            ErrorCode::Network => 4,
+            ErrorCode::TooLarge => 5,
+            ErrorCode::Internal => 6,
+        }
+    }
+
+    pub fn from_number(value : u8) -> Option<ErrorCode> {
+        match value {
+            0 => Some(ErrorCode::Unknown),
+            1 => Some(ErrorCode::NotFound),
+            2 => Some(ErrorCode::AuthRequired),
+            3 => Some(ErrorCode::RateLimit),
+            4 => Some(ErrorCode::Network),
+            5 => Some(ErrorCode::TooLarge),
+            6 => Some(ErrorCode::Internal),
+            _ => None,
+        }
+    }
+
+    /** Classifies the error from the HTTP status in its message. 
+     
+        Used for HTTP errors of libgit2 (`unexpected http status code: N`) and for errors recorded before the classification was introduced, whose messages may contain the status lines of GitHub API responses. Errors of the GitHub API requests themselves carry their status (see `from_io_error`). Messages without HTTP status are not classified as their text does not identify the error reliably (e.g. libgit2's `object not found` does not mean that the repository does not exist). 
+     */
+    pub fn from_message(message : & str) -> ErrorCode {
+        let message = message.to_lowercase();
+        // the status line may be wrapped, such as in the debug format of the I/O error stored in the project log
+        let status = if let Some(i) = message.find("http/") {
+            message[i..].split(" ").nth(1)
+        } else if let Some(i) = message.find("status code: ") {
+            message[i + 13..].split(|x : char| ! x.is_ascii_digit()).next()
+        } else {
+            None
+        };
+        match status.and_then(|x| x.parse::<u16>().ok()) {
+            Some(status) => return ErrorCode::from_status(status),
+            None => return ErrorCode::Unknown,
+        }
+    }
+
+    /** Classifies the error from its HTTP status. 
+     */
+    pub fn from_status(status : u16) -> ErrorCode {
+        match status {
+            404 | 410 | 451 => return ErrorCode::NotFound,
+            401 => return ErrorCode::AuthRequired,
+            403 | 429 => return ErrorCode::RateLimit,
+            500..=599 => return ErrorCode::Network,
+            _ => return ErrorCode::Unknown,
+        }
+    }
+
+    /** Classifies the I/O error, using the HTTP status of github errors (see `GithubError`), its kind if specific enough and its message otherwise. 
+     */
+    pub fn from_io_error(e : & std::io::Error) -> ErrorCode {
+        if let Some(status) = crate::github::GithubError::status_of(e) {
+            return ErrorCode::from_status(status);
+        }
+        match e.kind() {
+            std::io::ErrorKind::NotFound => return ErrorCode::NotFound,
+            std::io::ErrorKind::PermissionDenied => return ErrorCode::AuthRequired,
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::ConnectionAborted | std::io::ErrorKind::NotConnected => return ErrorCode::Network,
+            std::io::ErrorKind::OutOfMemory => return ErrorCode::TooLarge,
+            _ => return ErrorCode::from_message(& format!("{}", e)),
+        }
+    }
+
+    /** Returns the number of seconds to wait before retrying the update after given number of consecutive errors of this class, or None if the update should not be retried anymore. 
+     
+        The interval doubles with each attempt, up to `MAX_RETRY_INTERVAL`. Not found repositories are retried a few times in case they are only temporarily unavailable (see `NOT_FOUND_ATTEMPTS`). 
+     */
+    pub fn retry_interval(& self, attempts : u32) -> Option<i64> {
+        let base : i64 = match self {
+            ErrorCode::NotFound => {
+                if attempts >= ErrorCode::NOT_FOUND_ATTEMPTS {
+                    return None;
+                }
+                7 * 24 * 3600
+            },
+            ErrorCode::AuthRequired => 7 * 24 * 3600,
+            ErrorCode::RateLimit => 3600,
+            ErrorCode::Network => 3600,
+            ErrorCode::TooLarge => 30 * 24 * 3600,
+            ErrorCode::Internal | ErrorCode::Unknown => 24 * 3600,
+        };
+        return Some(base.saturating_mul(1 << attempts.max(1).min(16).saturating_sub(1)).min(ErrorCode::MAX_RETRY_INTERVAL));
+    }
+}
+
 /** Project update status. 
  
     Every time a repository is updated, an update status message is added to the projects update status so that the history of updates and repository lifetime can be reconstructed:
@@ -504,7 +633,15 @@ impl Serializable for ProjectUrl {
 
     # Tombstone
 
+    The project's repository has been deleted, or is permanently unavailable (blocked due to a DMCA takedown, disabled by GitHub, or not found repeatedly). The `reason` argument describes why the project was tombstoned. Tombstoned projects are not updated anymore unless their update is forced, in which case a successful update revives them. 
+
+    # LimitExceeded
+
+    The update was aborted because the project exceeded one of the limits of a single project update (time, number of commits, fetched bytes, or snapshot bytes), which is described by the `limit` argument. Such projects are skipped by regular updates and can be updated in a dedicated pass with different limits (the `updateoversized` command). 
+
     # Error
+
+    The update failed. Apart from the error message, the error's class is recorded, which determines when the update will be retried (see `ErrorCode`). Errors recorded before the classification was introduced are classified from their messages. 
  */
 pub enum ProjectLog {
     NoChange{time : i64, version : u16}, // 0
@@ -513,7 +650,9 @@ pub enum ProjectLog {
      */
     Rename{time : i64, version : u16, old_offset : u64}, // 2
     ChangeStore{time : i64, version : u16, new_kind : StoreKind }, // 3
-    Error{time : i64, version : u16, error : String }, // 255
+    Tombstone{time : i64, version : u16, reason : String }, // 4
+    LimitExceeded{time : i64, version : u16, limit : String }, // 5
+    Error{time : i64, version : u16, code : ErrorCode, error : String }, // 254 (255 for errors without code)
 }
 
 impl ProjectLog {
@@ -523,7 +662,9 @@ impl ProjectLog {
             ProjectLog::Ok{time : _, version} => return *version,
             ProjectLog::Rename{time : _, version, old_offset: _} => return *version,
             ProjectLog::ChangeStore{time : _, version, new_kind : _ } => return *version,
-            ProjectLog::Error{time : _, version, error: _ } => return *version,
+            ProjectLog::Tombstone{time : _, version, reason : _ } => return *version,
+            ProjectLog::LimitExceeded{time : _, version, limit : _ } => return *version,
+            ProjectLog::Error{time : _, version, code : _, error: _ } => return *version,
         }
     }
 
@@ -533,21 +674,46 @@ impl ProjectLog {
             ProjectLog::Ok{time, version : _} => return *time,
             ProjectLog::Rename{time, version : _, old_offset: _} => return *time,
             ProjectLog::ChangeStore{time, version : _, new_kind : _ } => return *time,
-            ProjectLog::Error{time, version : _, error: _ } => return *time,
+            ProjectLog::Tombstone{time, version : _, reason : _ } => return *time,
+            ProjectLog::LimitExceeded{time, version : _, limit : _ } => return *time,
+            ProjectLog::Error{time, version : _, code : _, error: _ } => return *time,
         }
     }
 
     pub fn is_error(& self) -> bool {
         match self {
-            ProjectLog::Error{time : _, version : _, error : _} => return true, 
+            ProjectLog::Error{time : _, version : _, code : _, error : _} => return true, 
+            _ => return false,
+        }
+    }
+
+    pub fn is_tombstone(& self) -> bool {
+        match self {
+            ProjectLog::Tombstone{time : _, version : _, reason : _} => return true, 
+            _ => return false,
+        }
+    }
+
+    pub fn is_limit_exceeded(& self) -> bool {
+        match self {
+            ProjectLog::LimitExceeded{time : _, version : _, limit : _} => return true, 
             _ => return false,
         }
     }
+
+    /** Returns the error code if the update failed, None otherwise. 
+     */
+    pub fn error_code(& self) -> Option<ErrorCode> {
+        match self {
+            ProjectLog::Error{time : _, version : _, code, error : _} => return Some(*code), 
+            _ => return None,
+        }
+    }
 }
 
 impl Serializable for ProjectLog {
     type Item = ProjectLog;
-    fn serialize(f : & mut File, value : & ProjectLog) {
+    fn serialize<W : Write>(f : & mut W, value : & ProjectLog) {
         match value {
             ProjectLog::NoChange{time , version } => {
                 u8::serialize(f, & 0);
@@ -571,16 +737,29 @@ impl Serializable for ProjectLog {
                 u16::serialize(f, version);
                 StoreKind::serialize(f, new_kind);
             },
-            ProjectLog::Error{time , version, error } =>  {
-                u8::serialize(f, & 255);
+            ProjectLog::Tombstone{time , version, reason } =>  {
+                u8::serialize(f, & 4);
+                i64::serialize(f, time);
+                u16::serialize(f, version);
+                String::serialize(f, reason);
+            },
+            ProjectLog::LimitExceeded{time , version, limit } =>  {
+                u8::serialize(f, & 5);
                 i64::serialize(f, time);
                 u16::serialize(f, version);
+                String::serialize(f, limit);
+            },
+            ProjectLog::Error{time , version, code, error } =>  {
+                u8::serialize(f, & 254);
+                i64::serialize(f, time);
+                u16::serialize(f, version);
+                u8::serialize(f, & code.to_number());
                 String::serialize(f, error);
             },
         }
     }
 
-    fn deserialize(f : & mut File) -> ProjectLog {
+    fn deserialize<R : Read>(f : & mut R) -> ProjectLog {
         let kind = u8::deserialize(f);
         let time = i64::deserialize(f);
         let version = u16::deserialize(f);
@@ -597,17 +776,28 @@ impl Serializable for ProjectLog {
             3 => {
                 return ProjectLog::ChangeStore{time, version, new_kind : StoreKind::deserialize(f)};
             },
+            4 => {
+                return ProjectLog::Tombstone{time, version, reason : String::deserialize(f)};
+            },
+            5 => {
+                return ProjectLog::LimitExceeded{time, version, limit : String::deserialize(f)};
+            },
+            254 => {
+                let code = ErrorCode::from_number(u8::deserialize(f)).unwrap();
+                return ProjectLog::Error{time, version, code, error : String::deserialize(f)};
+            },
             255 => {
-                return ProjectLog::Error{time, version, error : String::deserialize(f)};
+                let error = String::deserialize(f);
+                return ProjectLog::Error{time, version, code : ErrorCode::from_message(& error), error};
             },
-            _ => panic!("Unknown project update status kind"),
+            _ => panic!("Unknown project update status kind {} (version {}), the datastore was likely written by a newer version", kind, version),
         }
     }
 
-    fn verify(f : & mut File) -> Result<ProjectLog, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<ProjectLog, std::io::Error> {
         let kind = u8::verify(f)?;
         match kind {
-            0 | 1 | 2 | 3 | 255 => {
+            0 | 1 | 2 | 3 | 4 | 5 | 254 | 255 => {
                 let time = i64::verify(f)?;
                 let version = u16::verify(f)?;
                 match kind {
@@ -623,8 +813,19 @@ impl Serializable for ProjectLog {
                     3 => {
                         return Ok(ProjectLog::ChangeStore{time, version, new_kind : StoreKind::deserialize(f)});
                     },
+                    4 => {
+                        return Ok(ProjectLog::Tombstone{time, version, reason : String::verify(f)?});
+                    },
+                    5 => {
+                        return Ok(ProjectLog::LimitExceeded{time, version, limit : String::verify(f)?});
+                    },
+                    254 => {
+                        let code = ErrorCode::from_number(u8::verify(f)?).ok_or(std::io::Error::new(std::io::ErrorKind::Other, "Invalid error code"))?;
+                        return Ok(ProjectLog::Error{time, version, code, error : String::verify(f)?});
+                    },
                     255 => {
-                        return Ok(ProjectLog::Error{time, version, error : String::deserialize(f)});
+                        let error = String::verify(f)?;
+                        return Ok(ProjectLog::Error{time, version, code : ErrorCode::from_message(& error), error});
                     },
                     _ => unreachable!(),
                 }
@@ -650,8 +851,14 @@ impl std::fmt::Display for ProjectLog {
             ProjectLog::ChangeStore{time , version, new_kind } =>  {
                 return write!(f, "{}: substore: {:?} (v {})", helpers::pretty_timestamp(*time), new_kind, version);
             },
-            ProjectLog::Error{time , version, error } =>  {
-                return write!(f, "{}: error: {} (v {})", helpers::pretty_timestamp(*time), error, version);
+            ProjectLog::Tombstone{time , version, reason } =>  {
+                return write!(f, "{}: deleted: {} (v {})", helpers::pretty_timestamp(*time), reason, version);
+            },
+            ProjectLog::LimitExceeded{time , version, limit } =>  {
+                return write!(f, "{}: limit exceeded: {} (v {})", helpers::pretty_timestamp(*time), limit, version);
+            },
+            ProjectLog::Error{time , version, code, error } =>  {
+                return write!(f, "{}: error: {:?}: {} (v {})", helpers::pretty_timestamp(*time), code, error, version);
             },
         }
     }
@@ -667,7 +874,7 @@ pub type ProjectHeads = HashMap<String, (CommitId, SHA)>;
 
 impl Serializable for ProjectHeads {
     type Item = ProjectHeads;
-    fn serialize(f : & mut File, value : & ProjectHeads) {
+    fn serialize<W : Write>(f : & mut W, value : & ProjectHeads) {
         u32::serialize(f, & (value.len() as u32));
         for (name, (id, hash)) in value {
             String::serialize(f, name);
@@ -676,7 +883,7 @@ impl Serializable for ProjectHeads {
         }
     }
 
-    fn deserialize(f : & mut File) -> ProjectHeads {
+    fn deserialize<R : Read>(f : & mut R) -> ProjectHeads {
         let mut records = u32::deserialize(f);
         let mut result = ProjectHeads::new();
         while records > 0 {
@@ -689,7 +896,7 @@ impl Serializable for ProjectHeads {
         return result;
     }
 
-    fn verify(f : & mut File) -> Result<ProjectHeads, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<ProjectHeads, std::io::Error> {
         let mut records = u32::verify(f)?;
         if records as u64 > MAX_BUFFER_LENGTH {
             return Err(std::io::Error::new(std::io::ErrorKind::Other, "Invalid length of project heads"));
@@ -710,17 +917,17 @@ pub type SHA = git2::Oid;
 
 impl Serializable for SHA {
     type Item = SHA;
-    fn serialize(f : & mut File, value : & SHA) {
+    fn serialize<W : Write>(f : & mut W, value : & SHA) {
         f.write(value.as_bytes()).unwrap();
     }
 
-    fn deserialize(f : & mut File) -> SHA {
+    fn deserialize<R : Read>(f : & mut R) -> SHA {
         let mut buffer = vec![0; 20];
         f.read(& mut buffer).unwrap();
         return git2::Oid::from_bytes(& buffer).unwrap();
     }
 
-    fn verify(f : & mut File) -> Result<SHA, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<SHA, std::io::Error> {
         let mut buffer = vec![0; 20];
         f.read(& mut buffer)?;
         match git2::Oid::from_bytes(& buffer) {
@@ -850,15 +1057,15 @@ impl SplitKind for ContentsKind {
 
 impl Serializable for ContentsKind {
     type Item = ContentsKind;
-    fn serialize(f : & mut File, value : & ContentsKind) {
+    fn serialize<W : Write>(f : & mut W, value : & ContentsKind) {
         f.write_u16::<LittleEndian>(value.to_number() as u16).unwrap();
     }
 
-    fn deserialize(f : & mut File) -> ContentsKind {
+    fn deserialize<R : Read>(f : & mut R) -> ContentsKind {
         return ContentsKind::from_number(f.read_u16::<LittleEndian>().unwrap() as u64);
     }
 
-    fn verify(f : & mut File) -> Result<ContentsKind, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<ContentsKind, std::io::Error> {
         let index = u16::verify(f)? as u64;
         if index >= Self::COUNT {
             return Err(std::io::Error::new(std::io::ErrorKind::Other, "Invalid contents kind index"));
@@ -889,7 +1096,7 @@ impl ReadOnly for FileContents {
 
 impl Serializable for FileContents {
     type Item = FileContents;
-    fn serialize(f : & mut File, value : & FileContents) {
+    fn serialize<W : Write>(f : & mut W, value : & FileContents) {
         let mut enc = flate2::write::GzEncoder::new(Vec::new(), Compression::best());
         enc.write_all(value).unwrap();
         let encoded = enc.finish().unwrap();
@@ -897,7 +1104,7 @@ impl Serializable for FileContents {
         f.write(& encoded).unwrap();
     }
 
-    fn deserialize(f : & mut File) -> FileContents {
+    fn deserialize<R : Read>(f : & mut R) -> FileContents {
         let len = f.read_u64::<LittleEndian>().unwrap() as usize;
         let mut encoded = vec![0; len];
         f.read(& mut encoded).unwrap();
@@ -907,7 +1114,7 @@ impl Serializable for FileContents {
         return result;
     }
 
-    fn verify(f : & mut File) -> Result<FileContents, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<FileContents, std::io::Error> {
         let len = u64::verify(f)?;
         if len > MAX_BUFFER_LENGTH {
             return Err(std::io::Error::new(std::io::ErrorKind::Other, "Compressed file contents too large"));
@@ -932,23 +1139,47 @@ pub struct Metadata {
 
 impl Metadata {
     pub const GITHUB_METADATA : &'static str = "github_metadata";
+    /** ETag of the GitHub response from which the stored GitHub metadata were obtained, used for conditional requests. 
+     */
+    pub const GITHUB_ETAG : &'static str = "github_etag";
+    /** Substore migration of the project that could not be finished at the time the substore change was detected, see `Datastore::set_project_pending_migration` for details. 
+     */
+    pub const PENDING_MIGRATION : &'static str = "pending_migration";
+    /** Name of the user as found in the commits authored, or committed by them. 
+     */
+    pub const USER_NAME : &'static str = "name";
+    /** GitHub login of the user as reported by GitHub for the commits authored by the user, empty if the email is not linked to any GitHub account. 
+     */
+    pub const GITHUB_LOGIN : &'static str = "github_login";
+    /** GitHub profile of the user (login, name, location, company and time of the account creation) as JSON. 
+     */
+    pub const GITHUB_PROFILE : &'static str = "github_profile";
+    /** Contents of the `.mailmap` file of the project's repository, used to merge the aliases of the project's authors. 
+     */
+    pub const MAILMAP : &'static str = "mailmap";
+    /** Hashes of the root commits (commits without parents) of the project's repository, separated by commas. 
+     */
+    pub const ROOTS : &'static str = "roots";
+    /** Whether the project is a fork (`true` or `false`), as reported by GitHub, or by the source the project was added from. 
+     */
+    pub const FORK : &'static str = "fork";
 }
 
 impl Serializable for Metadata {
     type Item = Metadata;
-    fn serialize(f : & mut File, value : & Metadata) {
+    fn serialize<W : Write>(f : & mut W, value : & Metadata) {
         String::serialize(f, & value.key);
         String::serialize(f, & value.value);
     }
 
-    fn deserialize(f : & mut File) -> Metadata {
+    fn deserialize<R : Read>(f : & mut R) -> Metadata {
         return Metadata {
             key : String::deserialize(f),
             value : String::deserialize(f),
         };
     }
 
-    fn verify(f : & mut File) -> Result<Metadata, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<Metadata, std::io::Error> {
         return Ok(Metadata{
             key : String::verify(f)?,
             value : String::verify(f)?,
@@ -985,7 +1216,7 @@ impl ReadOnly for CommitInfo {
 
 impl Serializable for CommitInfo {
     type Item = CommitInfo;
-    fn serialize(f : & mut File, value : & CommitInfo) {
+    fn serialize<W : Write>(f : & mut W, value : & CommitInfo) {
         u64::serialize(f, & u64::from(value.committer));
         i64::serialize(f, & value.committer_time);
         u64::serialize(f, & u64::from(value.author));
@@ -1002,7 +1233,7 @@ impl Serializable for CommitInfo {
         String::serialize(f, & value.message);
     }
 
-    fn deserialize(f : & mut File) -> CommitInfo {
+    fn deserialize<R : Read>(f : & mut R) -> CommitInfo {
         let mut result = CommitInfo::new();
         result.committer = UserId::from(u64::deserialize(f));
         result.committer_time = i64::deserialize(f);
@@ -1024,7 +1255,7 @@ impl Serializable for CommitInfo {
         return result;
     }
 
-    fn verify(f : & mut File) -> Result<CommitInfo, std::io::Error> {
+    fn verify<R : Read>(f : & mut R) -> Result<CommitInfo, std::io::Error> {
         let mut result = CommitInfo::new();
         result.committer = UserId::from(u64::verify(f)?);
         result.committer_time = i64::verify(f)?;
@@ -1053,5 +1284,137 @@ impl Serializable for CommitInfo {
     }
 }
 
+/** Kinds of GitHub project items stored by the datastore, each kind in its own table. 
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GithubItemKind {
+    Issues,
+    PullRequests,
+    Releases,
+}
+
+impl GithubItemKind {
+    pub const ALL : [GithubItemKind; 3] = [GithubItemKind::Issues, GithubItemKind::PullRequests, GithubItemKind::Releases];
+
+    /** Returns the key of the project metadata that holds the time of the latest item of the kind stored, i.e. the time since which the items have to be updated. 
+     */
+    pub fn since_key(& self) -> &'static str {
+        match self {
+            GithubItemKind::Issues => "github_issues_since",
+            GithubItemKind::PullRequests => "github_pull_requests_since",
+            GithubItemKind::Releases => "github_releases_since",
+        }
+    }
+}
+
+/** An issue, pull request or release of a GitHub project. 
+ 
+    The number identifies the item within its project (for releases, which are not numbered, this is their id) and time is the time the item was last updated (for releases the time they were created). Each update of an item adds a new record, so that the history of the items is kept. The item itself is kept as JSON returned by the GitHub API with redundant urls removed. 
+    
+    Pull requests also keep hashes of the commits they reference (their head and merge commits). Hashes are kept instead of commit ids as the commits need not be part of the project's substore and the ids change when the project is migrated to another substore, or when the substore is garbage collected. Use `DatastoreView::pull_request_commits` to obtain their current commit ids. 
+ */
+pub struct GithubItem {
+    pub number : u64,
+    pub time : i64,
+    pub commits : Vec<SHA>,
+    pub json : String,
+}
+
+impl Serializable for GithubItem {
+    type Item = GithubItem;
+    fn serialize<W : Write>(f : & mut W, value : & GithubItem) {
+        u64::serialize(f, & value.number);
+        i64::serialize(f, & value.time);
+        u16::serialize(f, & (value.commits.len() as u16));
+        for commit in value.commits.iter() {
+            SHA::serialize(f, commit);
+        }
+        String::serialize(f, & value.json);
+    }
+
+    fn deserialize<R : Read>(f : & mut R) -> GithubItem {
+        let number = u64::deserialize(f);
+        let time = i64::deserialize(f);
+        let mut commits = Vec::new();
+        let mut num_commits = u16::deserialize(f);
+        while num_commits > 0 {
+            commits.push(SHA::deserialize(f));
+            num_commits -= 1;
+        }
+        return GithubItem{
+            number,
+            time,
+            commits,
+            json : String::deserialize(f),
+        };
+    }
+
+    fn verify<R : Read>(f : & mut R) -> Result<GithubItem, std::io::Error> {
+        let number = u64::verify(f)?;
+        let time = i64::verify(f)?;
+        let mut commits = Vec::new();
+        let mut num_commits = u16::verify(f)?;
+        if num_commits as u64 > MAX_BUFFER_LENGTH {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Too many pull request commits"));
+        }
+        while num_commits > 0 {
+            commits.push(SHA::verify(f)?);
+            num_commits -= 1;
+        }
+        return Ok(GithubItem{
+            number,
+            time,
+            commits,
+            json : String::verify(f)?,
+        });
+    }
+}
+
+/** Relation of a project to another project. 
+ 
+    Fork relations are obtained from the GitHub metadata of the project, where `parent` is the repository the project was forked from and `source` the root of its fork network. These are kept as urls as the repositories may not be part of the datastore. Projects whose repositories share a root commit with a project already in the datastore are related to it as well, regardless of their kind. 
+ */
+#[derive(Clone, Debug, std::cmp::PartialEq, std::cmp::Eq, std::hash::Hash)]
+pub enum ProjectRelation {
+    Parent{ url : ProjectUrl },
+    Source{ url : ProjectUrl },
+    SharedRoot{ id : ProjectId },
+}
+
+impl Serializable for ProjectRelation {
+    type Item = ProjectRelation;
+    fn serialize<W : Write>(f : & mut W, value : & ProjectRelation) {
+        match value {
+            ProjectRelation::Parent{url} => {
+                u8::serialize(f, & 0);
+                ProjectUrl::serialize(f, url);
+            },
+            ProjectRelation::Source{url} => {
+                u8::serialize(f, & 1);
+                ProjectUrl::serialize(f, url);
+            },
+            ProjectRelation::SharedRoot{id} => {
+                u8::serialize(f, & 2);
+                u64::serialize(f, & u64::from(*id));
+            },
+        }
+    }
 
+    fn deserialize<R : Read>(f : & mut R) -> ProjectRelation {
+        match u8::deserialize(f) {
+            0 => return ProjectRelation::Parent{ url : ProjectUrl::deserialize(f) },
+            1 => return ProjectRelation::Source{ url : ProjectUrl::deserialize(f) },
+            2 => return ProjectRelation::SharedRoot{ id : ProjectId::from(u64::deserialize(f)) },
+            _ => panic!("Unknown project relation kind"),
+        }
+    }
 
+    fn verify<R : Read>(f : & mut R) -> Result<ProjectRelation, std::io::Error> {
+        match u8::verify(f)? {
+            0 => return Ok(ProjectRelation::Parent{ url : ProjectUrl::verify(f)? }),
+            1 => return Ok(ProjectRelation::Source{ url : ProjectUrl::verify(f)? }),
+            2 => return Ok(ProjectRelation::SharedRoot{ id : ProjectId::from(u64::verify(f)?) }),
+            _ => return Err(std::io::Error::new(std::io::ErrorKind::Other, "Invalid project relation kind")),
+        }
+    }
+}
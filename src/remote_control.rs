@@ -0,0 +1,132 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+
+use crate::updater::*;
+use crate::settings::SETTINGS;
+use crate::LOG;
+
+/** How often the remote control threads check whether the updater is stopping.
+ */
+const POLL_INTERVAL : std::time::Duration = std::time::Duration::from_millis(500);
+
+/** Remote control of the running updater via a unix domain socket.
+
+    Each line sent to the socket is a command. Apart from the commands understood by the interactive controller (see `Updater::process_command`) the following commands are supported:
+
+    - `status` returns the status of the updater (workers, queue, loaded substores, etc.)
+    - `events` streams all task messages as JSON lines until the connection is closed
+
+    Every command is answered by a single JSON line with the `ok` field set to true or false and the command's `output`, `error`, or `status`.
+
+    The thread terminates when the updater stops. Any existing socket file is replaced.
+ */
+pub (crate) fn remote_controller<'a>(updater : &'a Updater, path : &'a str, scope : & crossbeam::thread::Scope<'a>) {
+    if std::path::Path::new(path).exists() {
+        std::fs::remove_file(path).unwrap();
+    }
+    let listener = UnixListener::bind(path).expect(& format!("Unable to create control socket {}", path));
+    // the listener is non blocking so that we can periodically check whether the updater has stopped
+    listener.set_nonblocking(true).unwrap();
+    while ! updater.should_stop() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                scope.spawn(move |_| {
+                    // errors are usually just closed connections, there is nothing to be done about them
+                    let _ = remote_connection(updater, stream);
+                });
+            },
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(POLL_INTERVAL);
+            },
+            Err(e) => {
+                // the updater must keep running, try again later
+                LOG!("Control socket error: {:?}", e);
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+    let _ = std::fs::remove_file(path);
+}
+
+/** Handles a single connection to the remote control.
+ */
+fn remote_connection(updater : & Updater, stream : UnixStream) -> Result<(), std::io::Error> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut output = stream.try_clone()?;
+    let mut input = BufReader::new(stream);
+    let mut line = String::new();
+    while ! updater.should_stop() {
+        match input.read_line(& mut line) {
+            // connection closed
+            Ok(0) => break,
+            Ok(_) => {
+                let command = line.trim().to_owned();
+                line.clear();
+                if command.is_empty() {
+                    continue;
+                }
+                if command == "events" {
+                    return remote_events(updater, & mut output);
+                }
+                let mut response = json::JsonValue::new_object();
+                response["command"] = command.as_str().into();
+                if command == "status" {
+                    response["ok"] = true.into();
+                    response["status"] = updater.status_json();
+                } else {
+                    match updater.process_command(command) {
+                        Ok(x) => {
+                            response["ok"] = true.into();
+                            response["output"] = x.into();
+                        },
+                        Err(x) => {
+                            response["ok"] = false.into();
+                            response["error"] = x.into();
+                        }
+                    }
+                }
+                writeln!(output, "{}", response.dump())?;
+            },
+            // timeouts keep the partially read line, if any
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => {},
+            Err(e) => return Err(e),
+        }
+    }
+    return Ok(());
+}
+
+/** Streams all task messages to the connection as JSON lines.
+ */
+fn remote_events(updater : & Updater, output : & mut UnixStream) -> Result<(), std::io::Error> {
+    let rx = updater.add_listener();
+    while ! updater.should_stop() {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(msg) => {
+                writeln!(output, "{}", msg.to_json().dump())?;
+            },
+            Err(crossbeam_channel::RecvTimeoutError::Timeout) => {},
+            Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    return Ok(());
+}
+
+/** Sends given command to the remote control of a running updater and prints the response(s).
+
+    Prints a single response for all commands except `events`, for which the events are printed until the updater stops.
+ */
+pub (crate) fn remote_command(path : & str, command : & str) -> Result<(), std::io::Error> {
+    let mut stream = UnixStream::connect(path)?;
+    writeln!(stream, "{}", command)?;
+    let mut input = BufReader::new(stream);
+    let mut line = String::new();
+    while input.read_line(& mut line)? > 0 {
+        print!("{}", line);
+        if command.trim() != "events" {
+            break;
+        }
+        line.clear();
+    }
+    return Ok(());
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::sync::Mutex;
+use std::io::Write;
 use crate::updater::*;
 
 pub type Tx = crossbeam_channel::Sender<TaskMessage>;
@@ -98,3 +99,54 @@ impl TerminalReporter {
     }
 }
 
+
+/** Log file with JSON lines and size based rotation. 
+ 
+    When the log grows over its maximum size, it is renamed to `PATH.1`, older logs are shifted (`PATH.1` to `PATH.2`, etc.) and the oldest log is deleted so that at most `MAX_LOGS` rotated logs are kept. 
+ */
+pub struct LogFile {
+    path : String,
+    f : std::fs::File,
+    size : u64,
+}
+
+impl LogFile {
+    /** Size of the log after which it is rotated. 
+     */
+    pub const MAX_SIZE : u64 = 64 * 1024 * 1024;
+    /** Number of rotated logs to keep. 
+     */
+    pub const MAX_LOGS : usize = 8;
+
+    pub fn open(path : & str) -> Result<LogFile, std::io::Error> {
+        let f = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        let size = f.metadata()?.len();
+        return Ok(LogFile{ path : path.to_owned(), f, size });
+    }
+
+    /** Writes given value as single line to the log, rotating the log if necessary. 
+     */
+    pub fn write(& mut self, value : & json::JsonValue) -> Result<(), std::io::Error> {
+        let line = format!("{}\n", value.dump());
+        self.f.write_all(line.as_bytes())?;
+        self.f.flush()?;
+        self.size += line.len() as u64;
+        if self.size >= LogFile::MAX_SIZE {
+            self.rotate()?;
+        }
+        return Ok(());
+    }
+
+    fn rotate(& mut self) -> Result<(), std::io::Error> {
+        for i in (1..LogFile::MAX_LOGS).rev() {
+            let from = format!("{}.{}", self.path, i);
+            if std::path::Path::new(& from).exists() {
+                std::fs::rename(from, format!("{}.{}", self.path, i + 1))?;
+            }
+        }
+        std::fs::rename(& self.path, format!("{}.1", self.path))?;
+        let path = self.path.clone();
+        *self = LogFile::open(& path)?;
+        return Ok(());
+    }
+}
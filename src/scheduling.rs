@@ -0,0 +1,153 @@
+use crate::datastore::*;
+use crate::records::*;
+use crate::updater::*;
+use crate::helpers;
+
+/** Determines when projects should be updated.
+
+    When a substore update schedules its projects, the policy calculates the time at which each project is due to be updated. This time is used as the priority of the project's update task so that the projects due earlier are updated first. Continuous updates skip projects that are not due yet, they will be checked again in the next round.
+
+    The policy is selected for the whole run of the updater via the `--policy` argument.
+ */
+pub (crate) trait SchedulingPolicy : Send + Sync {
+
+    /** Name of the policy as used on the command line.
+     */
+    fn name(& self) -> &'static str;
+
+    /** Returns the time at which the given project is due to be updated.
+
+        The last update is the latest record in the project's update log, or None if the project has never been updated.
+     */
+    fn due_time(& self, ds : & Datastore, id : ProjectId, last_update : Option<& ProjectLog>) -> i64;
+}
+
+/** Returns the scheduling policy of given name, or None if there is no such policy.
+ */
+pub (crate) fn scheduling_policy(name : & str) -> Option<Box<dyn SchedulingPolicy>> {
+    match name {
+        "oldest" => return Some(Box::new(OldestFirstPolicy{})),
+        "activity" => return Some(Box::new(ActivityPolicy{})),
+        _ => return None,
+    }
+}
+
+/** Returns the time at which a failed project update should be retried, or None if the update should not be retried. 
+ 
+    The retry interval depends on the class of the latest error and the number of consecutive errors of the same class (see `ErrorCode::retry_interval`). Returns None also if the last update did not fail. 
+ */
+pub (crate) fn retry_time(ds : & Datastore, id : ProjectId) -> Option<i64> {
+    let log = ds.get_project_log(id, 32);
+    let (time, code) = match log.first() {
+        Some(ProjectLog::Error{time, version : _, code, error : _}) => (*time, *code),
+        _ => return None,
+    };
+    let attempts = log.iter().take_while(|x| x.error_code() == Some(code)).count() as u32;
+    return Some(time + code.retry_interval(attempts)?);
+}
+
+/** The default policy, which updates the projects in order of their last update, oldest first.
+
+    All projects are always due, i.e. every round of a continuous update updates all projects.
+ */
+pub (crate) struct OldestFirstPolicy {}
+
+impl SchedulingPolicy for OldestFirstPolicy {
+    fn name(& self) -> &'static str {
+        return "oldest";
+    }
+
+    fn due_time(& self, _ds : & Datastore, _id : ProjectId, last_update : Option<& ProjectLog>) -> i64 {
+        return last_update.map(|x| x.time()).unwrap_or(Updater::NEVER);
+    }
+}
+
+/** Estimates how likely the project is to change from its history and backs off updates of dormant projects.
+
+    The interval between updates is the longer of the following:
+
+    - the backoff, which doubles with each consecutive update that found no change (or failed), starting from `MIN_INTERVAL`
+    - a fraction of the time since the project's last activity (the latest of its heads' commit times and the GitHub `pushed_at` time)
+
+    The interval is capped by `MAX_INTERVAL`. If GitHub reports a push newer than the last update, the project is due immediately. Projects that have never been updated are always due.
+ */
+pub (crate) struct ActivityPolicy {}
+
+impl ActivityPolicy {
+    /** The shortest interval between updates of a project (1 day).
+     */
+    pub const MIN_INTERVAL : i64 = 24 * 3600;
+
+    /** The longest interval between updates of a project (90 days).
+     */
+    pub const MAX_INTERVAL : i64 = 90 * 24 * 3600;
+
+    /** The interval based on activity is the time since the last activity divided by this factor.
+     */
+    pub const ACTIVITY_FACTOR : i64 = 10;
+
+    /** Returns the number of latest consecutive updates that found no change, or the number of latest consecutive errors if the last update failed.
+
+        Records not describing the outcome of an update (renames and substore changes) are skipped.
+     */
+    fn streak(ds : & Datastore, id : ProjectId) -> u32 {
+        let mut result = 0;
+        let mut errors = None;
+        // the backoff is capped anyways, so there is no need to look further in history
+        for log in ds.get_project_log(id, 32) {
+            match log {
+                ProjectLog::NoChange{time : _, version : _} if errors != Some(true) => errors = Some(false),
+                ProjectLog::Error{time : _, version : _, code : _, error : _} if errors != Some(false) => errors = Some(true),
+                ProjectLog::Rename{time : _, version : _, old_offset : _} | ProjectLog::ChangeStore{time : _, version : _, new_kind : _} => continue,
+                _ => break,
+            }
+            result += 1;
+        }
+        return result;
+    }
+
+    /** Returns the latest commit time of the project's heads, if any.
+     */
+    fn heads_time(ds : & Datastore, id : ProjectId) -> Option<i64> {
+        let mut result = None;
+        let store = ds.get_project_substore(id);
+        if let (true, Some(heads)) = (store.is_specified(), ds.get_project_heads(id)) {
+            let substore = ds.substore(store);
+            for (_, (commit_id, _)) in heads.iter() {
+                if let Some(commit) = substore.get_commit_info(*commit_id) {
+                    result = result.max(Some(commit.committer_time));
+                }
+            }
+        }
+        return result;
+    }
+
+    /** Returns the time of the last push as reported by GitHub metadata, if available.
+     */
+    fn pushed_at(ds : & Datastore, id : ProjectId) -> Option<i64> {
+        let metadata = json::parse(& ds.get_project_metadata(id, Metadata::GITHUB_METADATA)?).ok()?;
+        let pushed_at = chrono::DateTime::parse_from_rfc3339(metadata["pushed_at"].as_str()?).ok()?;
+        return Some(pushed_at.timestamp());
+    }
+}
+
+impl SchedulingPolicy for ActivityPolicy {
+    fn name(& self) -> &'static str {
+        return "activity";
+    }
+
+    fn due_time(& self, ds : & Datastore, id : ProjectId, last_update : Option<& ProjectLog>) -> i64 {
+        let last_update_time = match last_update {
+            Some(x) => x.time(),
+            None => return Updater::NEVER,
+        };
+        let pushed_at = ActivityPolicy::pushed_at(ds, id);
+        if pushed_at.map(|x| x > last_update_time).unwrap_or(false) {
+            return last_update_time;
+        }
+        let last_activity = ActivityPolicy::heads_time(ds, id).max(pushed_at);
+        let backoff = ActivityPolicy::MIN_INTERVAL.saturating_mul(1 << ActivityPolicy::streak(ds, id).min(16));
+        let inactivity = last_activity.map(|x| (helpers::now() - x) / ActivityPolicy::ACTIVITY_FACTOR).unwrap_or(0);
+        return last_update_time + backoff.max(inactivity).min(ActivityPolicy::MAX_INTERVAL);
+    }
+}
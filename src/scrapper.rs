@@ -1,5 +1,7 @@
+use std::collections::HashMap;
 use std::fs::{OpenOptions};
-use std::io::{Write};
+use std::io::{Write, BufRead};
+use std::sync::Mutex;
 
 #[macro_use]
 extern crate lazy_static;
@@ -30,83 +32,318 @@ mod datastore_maintenance_tasks;
 mod task_update_substore;
 #[allow(dead_code)]
 mod task_update_repo;
+#[allow(dead_code)]
+mod task_migrate_project;
+#[allow(dead_code)]
+mod task_gc_substore;
+#[allow(dead_code)]
+mod task_update_github_items;
+#[allow(dead_code)]
+mod task_update_users;
+#[allow(dead_code)]
+mod task_merge_aliases;
+#[allow(dead_code)]
+mod remote_control;
+mod metrics;
+#[allow(dead_code)]
+mod scheduling;
 
 use settings::SETTINGS;
 use github::Github;
+use datastore::Datastore;
+use records::{ProjectUrl, Metadata};
+
+/** Size of the id ranges into which the repository ids are split. 
+ */
+const RANGE_SIZE : i64 = 1000000;
+
+/** Number of attempts to get a single page before the worker gives up. 
+ 
+    The range the worker was scrapping is resumed from its checkpoint the next time the scrapper runs. 
+ */
+const MAX_ATTEMPTS : u32 = 10;
+
+const CSV_HEADER : &'static str = "id,name,fork,created_at,owner_type,private,archived";
 
+/** Terminator of the checkpoint lines so that a line truncated by a crash can be recognized. 
+ */
+const CHECKPOINT_END : &'static str = ";";
+
+/** Scraps all public GitHub repositories. 
+
+    The repository ids are split into ranges of `RANGE_SIZE` ids, which are scrapped in parallel by `--num-threads` workers. The workers send the pages they obtain to the main thread, which appends the repositories to the CSV file and then records a checkpoint of the range, i.e. the last id scrapped in it and the length of the CSV file, in the checkpoints file. After a crash, the CSV file is truncated to the length of its last checkpoint and all ranges continue from their checkpoints, so that no repository is missing, or repeated. If an output datastore is given, the repositories are added to it as well. 
+ */
 fn main() {
-    let (mut last_id, mut records) = get_latest_results();
-    let mut f = OpenOptions::new().create(true).append(true).open(& SETTINGS.datastore_root).unwrap();
-    if last_id == 0 {
-        //writeln!(& mut f, "id,name,language,created,fork,stars,forks,archived,disabled").unwrap();
-        writeln!(& mut f, "id,name,fork").unwrap();
+    let csv_filename = SETTINGS.datastore_root.clone();
+    let checkpoints_filename = format!("{}.checkpoints", csv_filename);
+    let (ranges, csv_len) = match load_checkpoints(& csv_filename, & checkpoints_filename) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    // drop any rows written after the last checkpoint
+    OpenOptions::new().create(true).write(true).open(& csv_filename).unwrap().set_len(csv_len).unwrap();
+    let mut f = OpenOptions::new().append(true).open(& csv_filename).unwrap();
+    if csv_len == 0 {
+        writeln!(& mut f, "{}", CSV_HEADER).unwrap();
     }
+    let mut checkpoints = OpenOptions::new().create(true).append(true).open(& checkpoints_filename).unwrap();
+    let ds = SETTINGS.output_datastore.as_ref().map(|root| {
+        println!("Loading output datastore {}...", root);
+        let ds = Datastore::new(root, false);
+        ds.load_all_project_urls();
+        ds
+    });
+    let ranges = Mutex::new(ranges);
+    let (tx, rx) = crossbeam_channel::unbounded::<ScrapperInfo>();
     let gh = Github::new(& SETTINGS.github_tokens);
-    let mut retries = 5;
-    while retries > 0 {
-        let request = format!("https://api.github.com/repositories?since={}", last_id);
-        //let request = format!("https://140.82.121.6/repositories?since={}", last_id);
-        match gh.request(& request, None) {
-            Ok(json::JsonValue::Array(repos)) => {
-                for repo in repos {
-                    // there are nulls scarcely distributed in the results, skip them 
-                    if repo.is_null() {
-                        continue;
+    crossbeam::thread::scope(|s| {
+        for _ in 0..SETTINGS.num_threads {
+            s.spawn(|_| {
+                scrapper(& ranges, & gh, tx.clone());
+            });
+        }
+        let mut active_threads = SETTINGS.num_threads;
+        let mut records = 0;
+        let mut added = 0;
+        while let Ok(msg) = rx.recv() {
+            match msg {
+                ScrapperInfo::Page{range, since, rows} => {
+                    for (row, _, _) in rows.iter() {
+                        writeln!(& mut f, "{}", row).unwrap();
+                    }
+                    f.flush().unwrap();
+                    if let Some(ds) = ds.as_ref() {
+                        for (_, name, fork) in rows.iter() {
+                            if let Some(id) = ds.add_project(& ProjectUrl::GitHub{ user_and_repo : name.to_lowercase() }) {
+                                ds.update_project_metadata_if_differ(id, Metadata::FORK.to_owned(), fork.to_string());
+                                added += 1;
+                            }
+                        }
+                    }
+                    // the checkpoint is only written once the rows are flushed to the CSV and added to the datastore
+                    let csv_len = f.metadata().unwrap().len();
+                    writeln!(& mut checkpoints, "{},{},{},{}{}", range.start, range.end, since, csv_len, CHECKPOINT_END).unwrap();
+                    checkpoints.flush().unwrap();
+                    records += rows.len();
+                    println!("Range {}..{} at {}, total records {}, added {}", range.start, range.end, since, records, added);
+                },
+                ScrapperInfo::Failed{range, err} => {
+                    println!("Range {}..{} failed: {}, will be resumed next time", range.start, range.end, err);
+                },
+                ScrapperInfo::Done => {
+                    println!("Worker done.");
+                    active_threads -= 1;
+                    if active_threads == 0 {
+                        break;
                     }
-                    let id = repo["id"].as_i64().unwrap();
-                    //println!("id: {}", id);
-                    let full_name = repo["full_name"].as_str().unwrap();
-                    let fork = repo["fork"].as_bool().unwrap();
-                    if last_id < id { last_id = id; }
-                    writeln!(& mut f, "{},\"{}\",{}", id, full_name, if fork { 1 } else { 0 }).unwrap();
-                    records += 1;
                 }
-                println!("Moving to last_id {}, total records {}", last_id, records);
-                retries = 5;
-            },
-            Ok(json) => {
-                if json["message"].is_string() && json["message"].as_str().unwrap() == "Not Found" {
-                    println!("No new projects found, exitting.");
+            }
+        }
+    }).unwrap();
+    println!("ALL DONE.");
+}
+
+/** Range of repository ids, from start (exclusive) to end (inclusive), together with the id since which the range is to be scrapped. 
+ */
+#[derive(Clone, Copy)]
+struct Range {
+    start : i64,
+    end : i64,
+    since : i64,
+}
+
+/** Ranges to be scrapped. 
+ 
+    Unfinished ranges from previous runs are scrapped first, then new ranges are created after the last known range until the end of the id space is found. The range containing the end of the id space stays unfinished, so that repositories created later are scrapped by the next run. 
+ */
+struct Ranges {
+    unfinished : Vec<Range>,
+    next_start : i64,
+    exhausted : bool,
+}
+
+impl Ranges {
+    fn next(& mut self) -> Option<Range> {
+        if let Some(range) = self.unfinished.pop() {
+            return Some(range);
+        }
+        if self.exhausted {
+            return None;
+        }
+        let start = self.next_start;
+        self.next_start += RANGE_SIZE;
+        return Some(Range{ start, end : start + RANGE_SIZE, since : start });
+    }
+}
+
+enum ScrapperInfo {
+    /** Rows of a scrapped page (the CSV row, repository name and whether it is a fork) and the id the range has been scrapped to. 
+     */
+    Page{range : Range, since : i64, rows : Vec<(String, String, bool)>},
+    Failed{range : Range, err : String},
+    Done,
+}
+
+type Tx = crossbeam_channel::Sender<ScrapperInfo>;
+
+fn scrapper(ranges : & Mutex<Ranges>, gh : & Github, tx : Tx) {
+    loop {
+        let range = match ranges.lock().unwrap().next() {
+            Some(range) => range,
+            None => break,
+        };
+        let mut since = range.since;
+        while since < range.end {
+            let repos = match get_page(gh, since) {
+                Ok(repos) => repos,
+                Err(err) => {
+                    tx.send(ScrapperInfo::Failed{range, err}).unwrap();
                     break;
-                } else {
-                    println!("unknown response format (query since {}): {} ", last_id, json);
                 }
-                retries -= 1;
-                std::thread::sleep(std::time::Duration::from_millis(1000 * 60 * 10));
-
+            };
+            // no repositories after the id means the end of the id space was reached, there are no more ranges to scrap and the range itself is left at the last id scrapped
+            if repos.is_empty() {
+                ranges.lock().unwrap().exhausted = true;
+                break;
             }
-            Err(e) => {
-                println!("error {} (query since {}) ", e, last_id);
-                retries -= 1;
-                std::thread::sleep(std::time::Duration::from_millis(1000 * 60 * 10));
+            let last = since;
+            let mut rows = Vec::new();
+            let mut missing = Vec::new();
+            for repo in repos {
+                // there are nulls scarcely distributed in the results, skip them 
+                if repo.is_null() {
+                    continue;
+                }
+                let id = repo["id"].as_i64().unwrap();
+                // an id past the range proves there are no more repositories in it
+                if id > range.end {
+                    since = range.end;
+                    break;
+                }
+                since = since.max(id);
+                if repo["created_at"].is_null() {
+                    missing.push(rows.len());
+                }
+                rows.push(repo);
             }
+            if since == last {
+                tx.send(ScrapperInfo::Failed{range, err : format!("no repositories in page (query since {})", since)}).unwrap();
+                break;
+            }
+            fill_missing_fields(gh, & mut rows, & missing);
+            let rows = rows.iter().map(|repo| {
+                let name = repo["full_name"].as_str().unwrap().to_owned();
+                let fork = repo["fork"].as_bool().unwrap_or(false);
+                let row = format!("{},\"{}\",{},{},{},{},{}",
+                    repo["id"],
+                    name,
+                    if fork { 1 } else { 0 },
+                    repo["created_at"].as_str().unwrap_or(""),
+                    repo["owner"]["type"].as_str().unwrap_or(""),
+                    repo["private"].as_bool().map(|x| if x { "1" } else { "0" }).unwrap_or(""),
+                    repo["archived"].as_bool().map(|x| if x { "1" } else { "0" }).unwrap_or(""),
+                );
+                return (row, name, fork);
+            }).collect();
+            tx.send(ScrapperInfo::Page{range, since, rows}).unwrap();
         }
     }
+    tx.send(ScrapperInfo::Done).unwrap();
 }
 
-/** Attempts to read the file as we have it and initialize the number of projects and latest id from it. 
+/** Returns the page of repositories created after given id. 
+ 
+    Failed requests are retried with exponentially increasing delays (starting at 10 seconds, up to 10 minutes) up to `MAX_ATTEMPTS` times. 
  */
-fn get_latest_results() -> (i64, i64) {
-    println!("Loading previous results...");
-    if let Ok(mut reader) = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .double_quote(false)
-        .escape(Some(b'\\'))
-        .from_path(& SETTINGS.datastore_root) {
-        let mut last_id : i64 = 0;
-        let mut records : i64 = 0;
-        for x in reader.records() {
-            records += 1;
-            if records % 1000000 == 0 { print!(".") }
-            let record = x.unwrap();
-            last_id = record[0].parse::<i64>().unwrap();
+fn get_page(gh : & Github, since : i64) -> Result<Vec<json::JsonValue>, String> {
+    let request = format!("https://api.github.com/repositories?since={}", since);
+    let mut delay = 10;
+    let mut attempts = 0;
+    loop {
+        let err = match gh.request(& request, None) {
+            Ok(json::JsonValue::Array(repos)) => return Ok(repos),
+            Ok(json) => format!("unknown response format (query since {}): {}", since, json),
+            Err(e) => format!("error {} (query since {})", e, since),
+        };
+        attempts += 1;
+        if attempts == MAX_ATTEMPTS {
+            return Err(err);
         }
-        println!("");
-        println!("{} records found, last id: {}", records, last_id);
-        return (last_id, records);
-    } else {
-        println!("No previous data found");
-        return (0,0);
+        println!("{}, retrying in {} seconds", err, delay);
+        std::thread::sleep(std::time::Duration::from_secs(delay));
+        delay = (delay * 2).min(600);
     }
 }
 
+/** The repository listing does not contain the time of creation and archived status of the repositories, so these are obtained by GraphQL queries for the given repositories. 
+ 
+    Failed queries are only reported, the fields are then left empty. Does nothing if GraphQL queries are disabled (see `--graphql-batch`). 
+ */
+fn fill_missing_fields(gh : & Github, repos : & mut Vec<json::JsonValue>, missing : & Vec<usize>) {
+    if SETTINGS.graphql_batch == 0 {
+        return;
+    }
+    for batch in missing.chunks(SETTINGS.graphql_batch) {
+        let names : Vec<String> = batch.iter().map(|i| repos[*i]["full_name"].as_str().unwrap().to_owned()).collect();
+        match gh.get_repos(& names, None) {
+            Ok(results) => {
+                for (i, result) in batch.iter().zip(results.into_iter()) {
+                    if let Some(result) = result {
+                        repos[*i]["created_at"] = result["created_at"].clone();
+                        repos[*i]["archived"] = result["archived"].clone();
+                    }
+                }
+            },
+            Err(e) => println!("GraphQL query failed: {}", e),
+        }
+    }
+}
+
+/** Loads the checkpoints and returns the ranges to be scrapped and the length of the CSV file at the last checkpoint. 
+ 
+    Only the latest checkpoint of each range is kept and the checkpoints file is rewritten with them. The last line may be incomplete if the scrapper crashed while writing it, such line is recognized by its missing terminator and dropped. Any other invalid line means that the file is not a checkpoints file of this version and an error is returned. An error is returned as well if the CSV file exists but there are no checkpoints, i.e. the file must have been created by an older version of the scrapper, which is not supported. 
+ */
+fn load_checkpoints(csv_filename : & str, checkpoints_filename : & str) -> Result<(Ranges, u64), String> {
+    println!("Loading checkpoints...");
+    let mut latest = HashMap::<i64, (Range, u64)>::new();
+    let mut csv_len = 0;
+    if let Ok(f) = std::fs::File::open(checkpoints_filename) {
+        let lines : Vec<String> = std::io::BufReader::new(f).lines().collect::<Result<_, _>>().map_err(|e| format!("Unable to read checkpoints {}: {}", checkpoints_filename, e))?;
+        for (i, line) in lines.iter().enumerate() {
+            let parts : Vec<i64> = line.strip_suffix(CHECKPOINT_END).unwrap_or("").split(",").filter_map(|x| x.parse::<i64>().ok()).collect();
+            if parts.len() != 4 {
+                if i + 1 == lines.len() {
+                    println!("Ignoring incomplete checkpoint {}", line);
+                    continue;
+                }
+                return Err(format!("Invalid checkpoint {} at line {} of {}, unknown checkpoints format", line, i + 1, checkpoints_filename));
+            }
+            latest.insert(parts[0], (Range{ start : parts[0], end : parts[1], since : parts[2] }, parts[3] as u64));
+            csv_len = csv_len.max(parts[3] as u64);
+        }
+    }
+    // the header is written before the first checkpoint
+    if latest.is_empty() && std::fs::metadata(csv_filename).map(|x| x.len() > CSV_HEADER.len() as u64 + 1).unwrap_or(false) {
+        return Err(format!("File {} exists, but has no checkpoints. Files created by older versions of the scrapper cannot be resumed, use a new file", csv_filename));
+    }
+    let mut ranges = Ranges{ unfinished : Vec::new(), next_start : 0, exhausted : false };
+    let mut f = std::fs::File::create(format!("{}.tmp", checkpoints_filename)).unwrap();
+    let mut sorted : Vec<& (Range, u64)> = latest.values().collect();
+    sorted.sort_by_key(|(range, _)| range.start);
+    for (range, len) in sorted {
+        writeln!(& mut f, "{},{},{},{}{}", range.start, range.end, range.since, len, CHECKPOINT_END).unwrap();
+        if range.since < range.end {
+            ranges.unfinished.push(*range);
+        }
+        ranges.next_start = ranges.next_start.max(range.end);
+    }
+    f.sync_all().unwrap();
+    std::fs::rename(format!("{}.tmp", checkpoints_filename), checkpoints_filename).unwrap();
+    // unfinished ranges are taken from the end, start with the lowest ids
+    ranges.unfinished.reverse();
+    println!("{} ranges, {} unfinished, next range starts at {}", latest.len(), ranges.unfinished.len(), ranges.next_start);
+    return Ok((ranges, csv_len));
+}
@@ -9,6 +9,20 @@ pub struct Settings {
     pub datastore_root : String, 
     pub github_tokens : String,
     pub num_threads : usize,
+    pub control_socket : Option<String>,
+    pub headless_log : Option<String>,
+    pub metrics : Option<String>,
+    pub policy : String,
+    pub update_timeout : Option<i64>,
+    pub max_commits : Option<usize>,
+    pub max_fetched_bytes : Option<usize>,
+    pub max_snapshot_bytes : Option<usize>,
+    pub oversized_limits : usize,
+    pub memory_budget : Option<usize>,
+    pub graphql_batch : usize,
+    pub github_items : bool,
+    pub forks : ForkPolicy,
+    pub output_datastore : Option<String>,
     pub command : Vec<String>,
 }
 
@@ -22,6 +36,20 @@ impl Settings {
             datastore_root : "/dejavuii/dcd3".to_owned(),
             github_tokens : "/mnt/data/github-tokens.csv".to_owned(),
             num_threads : 16,
+            control_socket : None,
+            headless_log : None,
+            metrics : None,
+            policy : "oldest".to_owned(),
+            update_timeout : None,
+            max_commits : None,
+            max_fetched_bytes : None,
+            max_snapshot_bytes : None,
+            oversized_limits : 10,
+            memory_budget : None,
+            graphql_batch : 50,
+            github_items : false,
+            forks : ForkPolicy::Update,
+            output_datastore : None,
             command : Vec::new(),
         };
     }
@@ -49,6 +77,48 @@ impl Settings {
             } else if arg == "-n" || arg == "--num-threads" {
                 settings.num_threads = args.get(arg_i + 1).expect("Number of threads missing").parse::<usize>().unwrap();
                 arg_i += 2;
+            } else if arg == "-cs" || arg == "--control-socket" {
+                settings.control_socket = Some(args.get(arg_i + 1).expect("Control socket path missing").to_owned());
+                arg_i += 2;
+            } else if arg == "-hl" || arg == "--headless" {
+                settings.headless_log = Some(args.get(arg_i + 1).expect("Headless log path missing").to_owned());
+                arg_i += 2;
+            } else if arg == "-m" || arg == "--metrics" {
+                settings.metrics = Some(args.get(arg_i + 1).expect("Metrics address or file missing").to_owned());
+                arg_i += 2;
+            } else if arg == "-p" || arg == "--policy" {
+                settings.policy = args.get(arg_i + 1).expect("Scheduling policy missing").to_owned();
+                arg_i += 2;
+            } else if arg == "-ut" || arg == "--update-timeout" {
+                settings.update_timeout = Some(args.get(arg_i + 1).expect("Update timeout missing").parse::<i64>().unwrap());
+                arg_i += 2;
+            } else if arg == "-mc" || arg == "--max-commits" {
+                settings.max_commits = Some(args.get(arg_i + 1).expect("Maximum number of commits missing").parse::<usize>().unwrap());
+                arg_i += 2;
+            } else if arg == "-mfb" || arg == "--max-fetched-bytes" {
+                settings.max_fetched_bytes = Some(args.get(arg_i + 1).expect("Maximum fetched bytes missing").parse::<usize>().unwrap());
+                arg_i += 2;
+            } else if arg == "-msb" || arg == "--max-snapshot-bytes" {
+                settings.max_snapshot_bytes = Some(args.get(arg_i + 1).expect("Maximum snapshot bytes missing").parse::<usize>().unwrap());
+                arg_i += 2;
+            } else if arg == "-ol" || arg == "--oversized-limits" {
+                settings.oversized_limits = args.get(arg_i + 1).expect("Oversized limits multiplier missing").parse::<usize>().unwrap();
+                arg_i += 2;
+            } else if arg == "-mb" || arg == "--memory-budget" {
+                settings.memory_budget = Some(args.get(arg_i + 1).expect("Memory budget missing").parse::<usize>().unwrap());
+                arg_i += 2;
+            } else if arg == "-gi" || arg == "--github-items" {
+                settings.github_items = true;
+                arg_i += 1;
+            } else if arg == "-fk" || arg == "--forks" {
+                settings.forks = ForkPolicy::from_string(args.get(arg_i + 1).expect("Fork policy missing")).expect("Unknown fork policy");
+                arg_i += 2;
+            } else if arg == "-od" || arg == "--output-datastore" {
+                settings.output_datastore = Some(args.get(arg_i + 1).expect("Output datastore path missing").to_owned());
+                arg_i += 2;
+            } else if arg == "-gqb" || arg == "--graphql-batch" {
+                settings.graphql_batch = args.get(arg_i + 1).expect("GraphQL batch size missing").parse::<usize>().unwrap();
+                arg_i += 2;
             } else {
                 break;
             }
@@ -59,6 +129,32 @@ impl Settings {
     }
 }
 
+/** Determines how the contents of GitHub forks are updated. 
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForkPolicy {
+    /** Forks are updated as any other project. 
+     */
+    Update,
+    /** Only the default branch of forks is fetched and analyzed. 
+     */
+    Shallow,
+    /** Contents of forks are not fetched at all, only their metadata are updated. 
+     */
+    Skip,
+}
+
+impl ForkPolicy {
+    pub fn from_string(name : & str) -> Option<ForkPolicy> {
+        match name {
+            "update" => return Some(ForkPolicy::Update),
+            "shallow" => return Some(ForkPolicy::Shallow),
+            "skip" => return Some(ForkPolicy::Skip),
+            _ => return None,
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! LOG {
     ($($tts:tt)*) => { {
@@ -0,0 +1,87 @@
+use crate::datastore::*;
+use crate::updater::*;
+use crate::records::*;
+use crate::helpers;
+use crate::task_migrate_project::*;
+
+/** Garbage collects the given substore.
+
+    Over time substores accumulate commits, hashes, contents, paths and users that are not reachable from any project's current heads (projects that were renamed, moved to other substores, failed updates, etc.). The garbage collection copies only the data reachable from the heads of projects that currently belong to the substore (and from heads of pending migrations from the substore) into a fresh substore, which then replaces the old one. As ids are assigned anew, the project heads are rewritten and the history of heads of all projects that ever belonged to the substore is dropped, as the older heads would refer to invalid ids. A new savepoint is created afterwards.
+
+    The new heads are journaled in the collected copy before it replaces the substore, so that a garbage collection interrupted at any point is either rolled back, or finished, when the datastore is opened again (see `Substore::recover_gc` and `Datastore::finish_gc`).
+
+    The garbage collection is refused if the substore is being updated and updates of the substore are postponed while it runs (see `Substore::guard_gc`). The substore is dropped from memory first so that it has to be loaded again afterwards. Note that the guard only works within the updater's process, the datastore must not be updated by another process while garbage collecting.
+
+    NOTE: Savepoints created before the garbage collection can no longer be used to revert the datastore.
+ */
+pub (crate) fn task_gc_substore(ds : & Datastore, store : StoreKind, task : TaskStatus) -> Result<(), std::io::Error> {
+    task.extra(format!("{:?}", store));
+    let substore = ds.substore(store);
+    let _guard = match substore.guard_gc() {
+        Some(guard) => guard,
+        None => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Substore {:?} is being updated, try again later", store))),
+    };
+    substore.clear(& task);
+    let size_before = substore.disk_size();
+    // determine the roots, i.e. heads of all projects in the substore and of pending migrations from the substore
+    task.info("collecting project heads...");
+    let mut projects = Vec::<(ProjectId, ProjectHeads)>::new();
+    let mut migrations = Vec::<(ProjectId, ProjectHeads)>::new();
+    for i in 0..ds.num_projects() {
+        let id = ProjectId::from(i as u64);
+        match ds.get_project_pending_migration(id) {
+            Some((source, heads)) => {
+                if source == store {
+                    migrations.push((id, heads));
+                }
+            },
+            None => {
+                if ds.get_project_substore(id) == store {
+                    if let Some(heads) = ds.get_project_heads(id) {
+                        projects.push((id, heads));
+                    }
+                }
+            }
+        }
+    }
+    // copy the reachable data into a fresh substore
+    let gc_root = format!("{}/{:?}.gc", ds.root_folder(), store);
+    if std::path::Path::new(& gc_root).exists() {
+        std::fs::remove_dir_all(& gc_root)?;
+    }
+    let gc_substore = Substore::new(std::path::Path::new(& gc_root), store, false);
+    gc_substore.load(& task);
+    let total = projects.len() + migrations.len();
+    let mut i = 0;
+    {
+        let mut migration = ProjectMigration::new(substore, & gc_substore, & task);
+        for (_, heads) in projects.iter_mut() {
+            *heads = migration.migrate(heads);
+            i += 1;
+            task.extra(format!("{:?} {}", store, helpers::pct(i, total)));
+        }
+        for (_, heads) in migrations.iter_mut() {
+            *heads = migration.migrate(heads);
+            i += 1;
+            task.extra(format!("{:?} {}", store, helpers::pct(i, total)));
+        }
+        task.info(migration.summary());
+    }
+    // journal the heads with the new ids first so that an interrupted garbage collection can be finished when the datastore is opened again
+    task.info("writing journal...");
+    Substore::write_gc_journal(& gc_root, & projects, & migrations)?;
+    task.info("replacing substore files...");
+    substore.replace_with(gc_substore)?;
+    task.info("updating project heads...");
+    ds.finish_gc(store)?;
+    let sp = ds.create_and_save_savepoint(format!("{}{:?}-{}", Datastore::GC_SAVEPOINT_PREFIX, store, helpers::now()));
+    let size_after = substore.disk_size();
+    task.info(format!("{} projects, {} -> {}, reclaimed {}, savepoint {}",
+        helpers::pretty_value(total),
+        helpers::pretty_size(size_before),
+        helpers::pretty_size(size_after),
+        helpers::pretty_size(size_before.saturating_sub(size_after)),
+        sp.name()
+    ));
+    return Ok(());
+}
@@ -0,0 +1,273 @@
+use std::collections::*;
+
+use crate::datastore::*;
+use crate::updater::*;
+use crate::records::*;
+
+/** Names shared by more users than this are considered too common to identify a single person.
+ */
+const MAX_USERS_PER_NAME : usize = 10;
+
+/** Domain of the GitHub noreply emails.
+ */
+const GITHUB_NOREPLY : &'static str = "@users.noreply.github.com";
+
+/** Merges the aliases of the users of given substore.
+
+    Users are keyed by their emails, so a single person using several emails appears as several users. The users are clustered so that users are in the same cluster if any of the following holds:
+
+    - their emails only differ in case
+    - they have used the same name (lowercased and with collapsed whitespace), provided that the name has at least two words and is not too common
+    - they are linked to the same GitHub login (see the `users` updater command), or one of them uses the GitHub noreply email of the login (`[id+]login@users.noreply.github.com`)
+    - their emails are mapped to each other, or to the same proper name by the `.mailmap` file of any project in the substore
+
+    All users in a cluster are then mapped to the user with the smallest id in the cluster, which becomes their canonical identity. Only the changed canonical identities are stored. The merge is not incremental, the whole substore is clustered every time.
+ */
+pub (crate) fn task_merge_aliases(ds : & Datastore, store : StoreKind, task : TaskStatus) -> Result<(), std::io::Error> {
+    task.extra(format!("{:?}", store));
+    if store == StoreKind::Unspecified {
+        return Err(std::io::Error::new(std::io::ErrorKind::Other, "Unspecified substore has no users"));
+    }
+    let substore = ds.substore(store);
+    let _guard = match substore.guard_update() {
+        Some(guard) => guard,
+        None => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Substore {:?} is being garbage collected", store))),
+    };
+    task.info("collecting users...");
+    let users : Vec<(UserId, String)> = substore.users.lock().unwrap().iter().collect();
+    let mut aliases = Aliases::new(substore.users.lock().unwrap().len());
+    let mut emails = HashMap::<String, UserId>::new();
+    for (id, email) in users.iter() {
+        // the empty email is the sentinel
+        if email.is_empty() {
+            continue;
+        }
+        let email = email.to_lowercase();
+        aliases.link(*id, format!("email:{}", email));
+        if let Some(login) = noreply_login(& email) {
+            aliases.link(*id, format!("login:{}", login));
+        }
+        emails.insert(email, *id);
+    }
+    task.check_cancelled()?;
+    // all names the users used and their latest logins
+    task.info("collecting users metadata...");
+    let mut names = HashMap::<UserId, HashSet<String>>::new();
+    let mut logins = HashMap::<UserId, String>::new();
+    for (id, mtd) in substore.users_metadata.lock().unwrap().iter_all() {
+        match mtd.key.as_str() {
+            Metadata::USER_NAME => { names.entry(id).or_insert_with(HashSet::new).insert(mtd.value); },
+            Metadata::GITHUB_LOGIN => { logins.insert(id, mtd.value); },
+            _ => {},
+        }
+    }
+    task.check_cancelled()?;
+    for (id, login) in logins.iter() {
+        if ! login.is_empty() {
+            aliases.link(*id, format!("login:{}", login.to_lowercase()));
+        }
+    }
+    let mut by_name = HashMap::<String, Vec<UserId>>::new();
+    for (id, user_names) in names.iter() {
+        // different names of the user may normalize to the same one
+        let normalized : HashSet<String> = user_names.iter().filter_map(|x| normalize_name(x)).collect();
+        for name in normalized {
+            by_name.entry(name).or_insert_with(Vec::new).push(*id);
+        }
+    }
+    for (name, ids) in by_name.iter() {
+        if ids.len() <= MAX_USERS_PER_NAME {
+            for id in ids.iter() {
+                aliases.link(*id, format!("name:{}", name));
+            }
+        }
+    }
+    task.check_cancelled()?;
+    // mailmaps of the substore's projects
+    task.info("collecting mailmaps...");
+    let mut mailmaps = 0;
+    for i in 0..ds.num_projects() {
+        let id = ProjectId::from(i as u64);
+        if ds.get_project_substore(id) != store {
+            continue;
+        }
+        if let Some(mailmap) = ds.get_project_metadata(id, Metadata::MAILMAP) {
+            for (j, (name, addresses)) in parse_mailmap(& mailmap).iter().enumerate() {
+                let users : Vec<UserId> = addresses.iter().filter_map(|x| emails.get(x).copied()).collect();
+                for user in users.iter() {
+                    // entries with proper name only are merged by the name within the mailmap
+                    if addresses.len() == 1 && ! name.is_empty() {
+                        aliases.link(*user, format!("mailmap:{}:{}", id, name));
+                    } else {
+                        aliases.link(*user, format!("mailmap:{}:#{}", id, j));
+                    }
+                }
+            }
+            mailmaps += 1;
+        }
+        if i % 1000 == 0 {
+            task.progress(i, ds.num_projects());
+            task.check_cancelled()?;
+        }
+    }
+    // store the canonical identities
+    task.info(format!("{} mailmaps, storing canonical identities...", mailmaps));
+    let mut merged = 0;
+    let mut updated = 0;
+    for (id, email) in users.iter() {
+        if email.is_empty() {
+            continue;
+        }
+        let canonical = aliases.canonical(*id);
+        if canonical != *id {
+            merged += 1;
+        }
+        if substore.update_user_canonical_if_differ(*id, canonical) {
+            updated += 1;
+        }
+    }
+    task.info(format!("{} users, {} merged with other users, {} canonical identities updated", users.len(), merged, updated));
+    return Ok(());
+}
+
+/** Clusters of users, implemented as union-find over the user ids with keys (emails, names, logins) linking the users that share them.
+ */
+struct Aliases {
+    parents : Vec<u64>,
+    keys : HashMap<String, UserId>,
+}
+
+impl Aliases {
+    fn new(users : usize) -> Aliases {
+        return Aliases{
+            parents : (0..users as u64).collect(),
+            keys : HashMap::new(),
+        };
+    }
+
+    /** Makes sure the user is known, users created after the aliases were created start in their own clusters. 
+     */
+    fn add(& mut self, id : UserId) {
+        let id = u64::from(id);
+        while self.parents.len() as u64 <= id {
+            let next = self.parents.len() as u64;
+            self.parents.push(next);
+        }
+    }
+
+    /** Adds the user to the cluster of users with given key.
+     */
+    fn link(& mut self, id : UserId, key : String) {
+        self.add(id);
+        match self.keys.get(& key) {
+            Some(other) => {
+                let other = *other;
+                self.union(id, other);
+            },
+            None => {
+                self.keys.insert(key, id);
+            }
+        }
+    }
+
+    /** Returns the canonical identity of the user, i.e. the smallest id in its cluster.
+     */
+    fn canonical(& mut self, id : UserId) -> UserId {
+        self.add(id);
+        return UserId::from(self.find(u64::from(id)));
+    }
+
+    fn find(& mut self, id : u64) -> u64 {
+        let mut root = id;
+        while self.parents[root as usize] != root {
+            root = self.parents[root as usize];
+        }
+        // compress the path
+        let mut x = id;
+        while self.parents[x as usize] != root {
+            let next = self.parents[x as usize];
+            self.parents[x as usize] = root;
+            x = next;
+        }
+        return root;
+    }
+
+    /** Merges the clusters of the two users, the smaller root becomes the root of the merged cluster.
+     */
+    fn union(& mut self, a : UserId, b : UserId) {
+        let a = self.find(u64::from(a));
+        let b = self.find(u64::from(b));
+        if a < b {
+            self.parents[b as usize] = a;
+        } else if b < a {
+            self.parents[a as usize] = b;
+        }
+    }
+}
+
+/** Normalizes the name for comparison, returns None if the name is too vague to identify a person.
+ */
+fn normalize_name(name : & str) -> Option<String> {
+    let words : Vec<String> = name.split_whitespace().map(|x| x.to_lowercase()).collect();
+    if words.len() < 2 {
+        return None;
+    }
+    let result = words.join(" ");
+    if result.len() < 5 {
+        return None;
+    }
+    return Some(result);
+}
+
+/** Returns the GitHub login of a noreply email, if the email is one.
+ */
+fn noreply_login(email : & str) -> Option<String> {
+    if ! email.ends_with(GITHUB_NOREPLY) {
+        return None;
+    }
+    let local = & email[.. email.len() - GITHUB_NOREPLY.len()];
+    let login = match local.find('+') {
+        Some(i) => & local[i + 1 ..],
+        None => local,
+    };
+    if login.is_empty() {
+        return None;
+    }
+    return Some(login.to_owned());
+}
+
+/** Parses the mailmap file into its entries.
+
+    Each entry is the proper name (normalized as in the user names, empty if not given) and the lowercased emails of the line, i.e. the proper email and the commit email, if given. See `git help mailmap` for the format.
+ */
+fn parse_mailmap(mailmap : & str) -> Vec<(String, Vec<String>)> {
+    let mut result = Vec::new();
+    for line in mailmap.lines() {
+        let line = match line.find('#') {
+            Some(i) => & line[..i],
+            None => line,
+        };
+        let mut emails = Vec::new();
+        let mut rest = line;
+        let name = match rest.find('<') {
+            Some(i) => rest[..i].trim().to_owned(),
+            None => continue,
+        };
+        while let Some(start) = rest.find('<') {
+            match rest[start..].find('>') {
+                Some(end) => {
+                    let email = rest[start + 1 .. start + end].trim().to_lowercase();
+                    if ! email.is_empty() {
+                        emails.push(email);
+                    }
+                    rest = & rest[start + end + 1 ..];
+                },
+                None => break,
+            }
+        }
+        if ! emails.is_empty() {
+            result.push((normalize_name(& name).unwrap_or(name.to_lowercase()), emails));
+        }
+    }
+    return result;
+}
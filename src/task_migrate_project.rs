@@ -0,0 +1,262 @@
+use std::collections::*;
+
+use crate::db::*;
+use crate::datastore::*;
+use crate::updater::*;
+use crate::records::*;
+use crate::helpers;
+use crate::settings::SETTINGS;
+
+use crate::LOG;
+
+/** Migrates given project into the specified substore.
+
+    The project's data reachable from its latest heads are copied from its current substore into the target substore, which is loaded if necessary, and the project is then moved to the target substore with translated heads. If there is a migration pending for the project (i.e. the substore change was detected by the updater while the target substore was not loaded), the pending migration is used instead.
+ */
+pub (crate) fn task_migrate_project(ds : & Datastore, id : ProjectId, store : StoreKind, task : TaskStatus) -> Result<(), std::io::Error> {
+    task.extra(format!("{} -> {:?}", id, store));
+    let current = ds.get_project_substore(id);
+    // the substores are guarded against garbage collection before the heads, which it rewrites, are read
+    let mut _guards = Vec::new();
+    for kind in [ds.get_project_pending_migration(id).map(|(source, _)| source).unwrap_or(current), store] {
+        if kind.is_specified() {
+            match ds.substore(kind).guard_update() {
+                Some(guard) => _guards.push(guard),
+                None => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Substore {:?} is being garbage collected", kind))),
+            }
+        }
+    }
+    // determine the source substore and the heads to migrate
+    let pending = ds.get_project_pending_migration(id);
+    let (source, heads) = match pending {
+        Some((source, ref heads)) => (source, heads.clone()),
+        None => (current, ds.get_project_heads(id).unwrap_or(ProjectHeads::new())),
+    };
+    if source == store {
+        // the data are already in the target substore, if there is a pending migration, it is enough to restore its heads
+        if pending.is_some() {
+            ds.migrate_project_substore(id, store, & heads);
+        }
+        task.info("already in substore");
+        return Ok(());
+    }
+    if ! source.is_specified() || heads.is_empty() {
+        ds.update_project_substore(id, store);
+        ds.clear_project_pending_migration(id);
+        task.info("nothing to migrate");
+        return Ok(());
+    }
+    ds.substore(store).load(& task);
+    let mut migration = ProjectMigration::new(ds.substore(source), ds.substore(store), & task);
+    let new_heads = migration.migrate(& heads);
+    ds.migrate_project_substore(id, store, & new_heads);
+    task.info(migration.summary());
+    return Ok(());
+}
+
+/** Migration of project data from one substore to another.
+
+    Walks all commits reachable from given heads in the source substore and copies them, together with their users, paths, hashes, contents and metadata, to the target substore. Source ids are translated to the target ids much like when merging datastores (see `MergeContext`), but only the data reachable from the project are considered.
+
+    The source substore does not have to be loaded as only random access by ids is used, but the target substore must be loaded so that new mappings can be created.
+ */
+pub (crate) struct ProjectMigration<'a> {
+    source : &'a Substore,
+    target : &'a Substore,
+    task : &'a TaskStatus<'a>,
+    commits : HashMap<CommitId, CommitId>,
+    users : HashMap<UserId, UserId>,
+    paths : HashMap<PathId, PathId>,
+    hashes : HashMap<HashId, HashId>,
+    /** Number of commits whose information had to be copied to the target.
+     */
+    new_commits : usize,
+    /** Number of file contents copied to the target.
+     */
+    new_contents : usize,
+}
+
+impl<'a> ProjectMigration<'a> {
+
+    pub (crate) fn new(source : &'a Substore, target : &'a Substore, task : &'a TaskStatus<'a>) -> ProjectMigration<'a> {
+        assert!(target.is_loaded(), "Target substore must be loaded for the migration");
+        return ProjectMigration{
+            source,
+            target,
+            task,
+            commits : HashMap::new(),
+            users : HashMap::new(),
+            paths : HashMap::new(),
+            hashes : HashMap::new(),
+            new_commits : 0,
+            new_contents : 0,
+        };
+    }
+
+    /** Migrates all commits reachable from the given heads and returns the heads translated to the target substore.
+
+        This is a two step process. First all reachable commits are given their target ids, descending only to parents of commits whose information is not yet present in the target, because if it is, all its parents must be present as well. Then the commit information of the new commits is translated and stored in the target.
+     */
+    pub (crate) fn migrate(& mut self, heads : & ProjectHeads) -> ProjectHeads {
+        self.task.info("translating commits...");
+        let mut q : Vec<CommitId> = heads.iter().map(|(_, (id, _))| *id).collect();
+        let mut new_commits = Vec::<(CommitId, CommitId)>::new();
+        while let Some(source_id) = q.pop() {
+            if self.commits.contains_key(& source_id) {
+                continue;
+            }
+            let hash = match self.source.commits.lock().unwrap().get(source_id) {
+                Some(hash) => hash,
+                None => {
+                    LOG!("Commit id {} not found in substore {:?}, migration will not be complete", source_id, self.source.prefix);
+                    continue;
+                }
+            };
+            let (target_id, _) = self.target.get_or_create_commit_id(& hash);
+            self.commits.insert(source_id, target_id);
+            if ! self.target.commits_info.lock().unwrap().has(target_id) {
+                if let Some(cinfo) = self.source.commits_info.lock().unwrap().get(source_id) {
+                    q.extend(cinfo.parents.iter());
+                    new_commits.push((source_id, target_id));
+                }
+            }
+            if self.commits.len() % 1000 == 0 {
+                self.task.info(format!("translating commits... {}", helpers::pretty_value(self.commits.len())));
+            }
+        }
+        // now translate & copy the commit information of the new commits
+        let mut i = 0;
+        self.task.progress(i, new_commits.len());
+        for (source_id, target_id) in new_commits.iter() {
+            let mut cinfo = self.source.commits_info.lock().unwrap().get(*source_id).unwrap();
+            cinfo.committer = self.translate_user(cinfo.committer);
+            cinfo.author = self.translate_user(cinfo.author);
+            cinfo.parents = cinfo.parents.iter().map(|x| self.translate_commit(*x)).collect();
+            cinfo.changes = cinfo.changes.iter().map(|(path, hash)| (self.translate_path(*path), self.translate_hash(*hash))).collect();
+            self.target.add_commit_info_if_missing(*target_id, & cinfo);
+            let metadata : Vec<Metadata> = self.source.commits_metadata.lock().unwrap().iter_id(*source_id).collect();
+            if ! metadata.is_empty() {
+                let mut target_metadata = self.target.commits_metadata.lock().unwrap();
+                for mtd in metadata.iter().rev() {
+                    target_metadata.set(*target_id, mtd);
+                }
+            }
+            self.new_commits += 1;
+            i += 1;
+            if i % 1000 == 0 {
+                self.task.progress(i, new_commits.len());
+                self.task.info(self.summary());
+            }
+        }
+        self.task.progress(i, new_commits.len());
+        return heads.iter().map(|(name, (id, hash))| (name.to_owned(), (self.translate_commit(*id), *hash))).collect();
+    }
+
+    /** Returns a short summary of the migration.
+     */
+    pub (crate) fn summary(& self) -> String {
+        return format!("commits: {}, new: {}, users: {}, paths: {}, hashes: {}, contents: {}",
+            helpers::pretty_value(self.commits.len()),
+            helpers::pretty_value(self.new_commits),
+            helpers::pretty_value(self.users.len()),
+            helpers::pretty_value(self.paths.len()),
+            helpers::pretty_value(self.hashes.len()),
+            helpers::pretty_value(self.new_contents)
+        );
+    }
+
+    fn translate_commit(& self, source_id : CommitId) -> CommitId {
+        if let Some(target_id) = self.commits.get(& source_id) {
+            return *target_id;
+        } else {
+            LOG!("Required commit id {} not migrated. Target will not be consistent", source_id);
+            return CommitId::NONE;
+        }
+    }
+
+    /** Translates the user, copying its metadata and canonical identity if the user is new to the target substore.
+     */
+    fn translate_user(& mut self, source_id : UserId) -> UserId {
+        if let Some(target_id) = self.users.get(& source_id) {
+            return *target_id;
+        }
+        let email = match self.source.users.lock().unwrap().get(source_id) {
+            Some(email) => email,
+            None => {
+                LOG!("Required user id {} not found. Target will not be consistent", source_id);
+                return UserId::NONE;
+            }
+        };
+        let (target_id, is_new) = self.target.get_or_create_user_id(& email);
+        if is_new {
+            let metadata : Vec<Metadata> = self.source.users_metadata.lock().unwrap().iter_id(source_id).collect();
+            let mut target_metadata = self.target.users_metadata.lock().unwrap();
+            for mtd in metadata.iter().rev() {
+                target_metadata.set(target_id, mtd);
+            }
+        }
+        self.users.insert(source_id, target_id);
+        if is_new {
+            // the canonical identity is its own canonical identity, so this recurses at most once
+            let canonical = self.source.get_user_canonical(source_id);
+            if canonical != source_id {
+                let target_canonical = self.translate_user(canonical);
+                if target_canonical != UserId::NONE {
+                    self.target.update_user_canonical_if_differ(target_id, target_canonical);
+                }
+            }
+        }
+        return target_id;
+    }
+
+    fn translate_path(& mut self, source_id : PathId) -> PathId {
+        if let Some(target_id) = self.paths.get(& source_id) {
+            return *target_id;
+        }
+        let path = match self.source.path_strings.lock().unwrap().get(source_id) {
+            Some(path) => path,
+            None => {
+                LOG!("Required path id {} not found. Target will not be consistent", source_id);
+                return PathId::NONE;
+            }
+        };
+        let (target_id, _) = self.target.get_or_create_path_id(& path);
+        self.paths.insert(source_id, target_id);
+        return target_id;
+    }
+
+    /** Translates the hash and copies its contents if they are not present in the target substore. 
+
+        The contents metadata are copied if the hash is new to the target, or if its contents had to be copied.
+     */
+    fn translate_hash(& mut self, source_id : HashId) -> HashId {
+        if let Some(target_id) = self.hashes.get(& source_id) {
+            return *target_id;
+        }
+        let hash = match self.source.hashes.lock().unwrap().get(source_id) {
+            Some(hash) => hash,
+            None => {
+                LOG!("Required hash id {} not found. Target will not be consistent", source_id);
+                return HashId::NONE;
+            }
+        };
+        let (target_id, is_new) = self.target.get_or_create_hash_id(& hash);
+        let mut copy_metadata = is_new;
+        if ! self.target.contents.lock().unwrap().has(target_id) {
+            if let Some((kind, contents)) = self.source.contents.lock().unwrap().get(source_id) {
+                self.target.add_file_contents(target_id, kind, & contents);
+                self.new_contents += 1;
+                copy_metadata = true;
+            }
+        }
+        if copy_metadata {
+            let metadata : Vec<Metadata> = self.source.contents_metadata.lock().unwrap().iter_id(source_id).collect();
+            let mut target_metadata = self.target.contents_metadata.lock().unwrap();
+            for mtd in metadata.iter().rev() {
+                target_metadata.set(target_id, mtd);
+            }
+        }
+        self.hashes.insert(source_id, target_id);
+        return target_id;
+    }
+}
@@ -0,0 +1,112 @@
+use crate::datastore::*;
+use crate::updater::*;
+use crate::records::*;
+use crate::github::*;
+use crate::task_update_repo::*;
+
+/** Number of items requested per page.
+ */
+const PAGE_SIZE : usize = 100;
+
+/** Updates issues, pull requests and releases of given GitHub project.
+
+    The items are updated incrementally, i.e. only items updated since the latest item stored for the project are requested. Issues are requested in the order of their updates using the `since` parameter and stored page by page so that an interrupted update continues where it stopped. Pull requests and releases cannot be filtered by time, so they are requested from the most recently updated (created for releases) until an already stored item is found and then stored in the order of their updates. Note that releases are only ordered by their creation so edits of already stored releases are not detected.
+
+    Pull requests are stored together with the hashes of their head and merge commits.
+ */
+pub (crate) fn task_update_github_items(ds : & Datastore, gh : & Github, id : ProjectId, task : TaskStatus) -> Result<(), std::io::Error> {
+    let user_and_repo = match ds.get_project(id) {
+        Some(ProjectUrl::GitHub{user_and_repo}) => user_and_repo,
+        _ => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Project {} is not a GitHub project", id))),
+    };
+    task.extra_url(& user_and_repo, & format!("https://github.com/{}", user_and_repo));
+    let mut added = 0;
+    for kind in GithubItemKind::ALL.iter() {
+        let since = ds.get_project_metadata(id, kind.since_key()).and_then(|x| x.parse::<i64>().ok());
+        let mut page = 1;
+        let mut items = Vec::<GithubItem>::new();
+        loop {
+            task.info(format!("{:?}, page {}...", kind, page));
+            task.check_cancelled()?;
+            let url = match kind {
+                GithubItemKind::Issues => format!("https://api.github.com/repos/{}/issues?state=all&sort=updated&direction=asc&per_page={}&page={}{}",
+                    user_and_repo, PAGE_SIZE, page, since.map(|x| format!("&since={}", to_iso_time(x))).unwrap_or(String::new())),
+                GithubItemKind::PullRequests => format!("https://api.github.com/repos/{}/pulls?state=all&sort=updated&direction=desc&per_page={}&page={}", user_and_repo, PAGE_SIZE, page),
+                GithubItemKind::Releases => format!("https://api.github.com/repos/{}/releases?per_page={}&page={}", user_and_repo, PAGE_SIZE, page),
+            };
+            let result = gh.request(& url, Some(& task))?;
+            let mut done = result.len() < PAGE_SIZE;
+            for mut json in result.members().cloned() {
+                // the issues also contain pull requests, which are stored separately
+                if *kind == GithubItemKind::Issues && json.has_key("pull_request") {
+                    continue;
+                }
+                let (number, time) = match kind {
+                    GithubItemKind::Releases => (json["id"].as_u64(), json["created_at"].as_str().and_then(from_iso_time)),
+                    _ => (json["number"].as_u64(), json["updated_at"].as_str().and_then(from_iso_time)),
+                };
+                let (number, time) = match (number, time) {
+                    (Some(number), Some(time)) => (number, time),
+                    _ => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Invalid {:?} item {}", kind, json))),
+                };
+                // items are requested from the latest, stop at the first one already stored
+                if *kind != GithubItemKind::Issues && since.map(|x| time < x).unwrap_or(false) {
+                    done = true;
+                    break;
+                }
+                // items updated at the time of the latest stored item may have been stored already
+                if since == Some(time) && ds.has_project_github_item(id, *kind, number, time) {
+                    if *kind != GithubItemKind::Issues {
+                        done = true;
+                        break;
+                    }
+                    continue;
+                }
+                let mut commits = Vec::new();
+                if *kind == GithubItemKind::PullRequests {
+                    for sha in [& json["head"]["sha"], & json["merge_commit_sha"]].iter() {
+                        if let Some(commit) = sha.as_str().and_then(|x| SHA::from_str(x).ok()) {
+                            commits.push(commit);
+                        }
+                    }
+                }
+                filter_github_metadata_keys(& mut json, true);
+                items.push(GithubItem{ number, time, commits, json : json.dump() });
+            }
+            // issues are stored as they come so that the update can be continued if interrupted
+            if *kind == GithubItemKind::Issues {
+                added += store_items(ds, id, *kind, & items);
+                items.clear();
+            }
+            if done {
+                break;
+            }
+            page += 1;
+        }
+        // pull requests and releases were obtained latest first
+        items.reverse();
+        added += store_items(ds, id, *kind, & items);
+    }
+    task.info(format!("{} items added", added));
+    return Ok(());
+}
+
+/** Stores the items, ordered by their time, and updates the time since which the project's items of the kind will be requested the next time.
+ */
+fn store_items(ds : & Datastore, id : ProjectId, kind : GithubItemKind, items : & Vec<GithubItem>) -> usize {
+    for item in items.iter() {
+        ds.add_project_github_item(id, kind, item);
+    }
+    if let Some(time) = items.iter().map(|x| x.time).max() {
+        ds.update_project_metadata_if_differ(id, kind.since_key().to_owned(), time.to_string());
+    }
+    return items.len();
+}
+
+fn from_iso_time(time : & str) -> Option<i64> {
+    return chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%SZ").ok().map(|x| x.timestamp());
+}
+
+fn to_iso_time(time : i64) -> String {
+    return chrono::NaiveDateTime::from_timestamp(time, 0).format("%Y-%m-%dT%H:%M:%SZ").to_string();
+}
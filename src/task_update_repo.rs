@@ -5,6 +5,10 @@ use crate::updater::*;
 use crate::records::*;
 use crate::helpers;
 use crate::github::*;
+use crate::task_migrate_project::*;
+use crate::metrics::*;
+use crate::scheduling::*;
+use crate::settings::{SETTINGS, ForkPolicy};
 
 
 /** Provides a full update of the given repository. 
@@ -21,12 +25,23 @@ pub (crate) fn task_update_repo(ds : & Datastore, gh : & Github, task : TaskStat
     let mut ru = RepoUpdater::new(ds, gh, task, force, load_substore);
     match ru.update() {
         Err(e) => {
-                // if there was an error, report the error and exit
+                // cancelled updates (such as while waiting for Github API tokens) are not errors
+                if ru.task.is_cancelled() {
+                    ru.task.info("cancelled");
+                    return Ok(());
+                }
+                // if there was an error, classify it, report the error and exit
+                let code = ru.error_code.take().unwrap_or_else(|| ErrorCode::from_io_error(& e));
                 ru.ds.update_project_update_status(ru.id, ProjectLog::Error{
                     time : helpers::now(),
                     version : Datastore::VERSION,
+                    code,
                     error : format!("{:?}", e),
                 });
+                // repositories not found repeatedly are permanently gone
+                if code == ErrorCode::NotFound && retry_time(ru.ds, ru.id).is_none() {
+                    ru.tombstone("not found");
+                }
                 return Err(e);
         },
         Ok(()) => {
@@ -54,7 +69,28 @@ struct RepoUpdater<'a> {
     users : HashMap<String, UserId>,
     paths : HashMap<String, PathId>,
     q : Vec<(SHA, CommitId)>,
+    /** Root commits found by the update and already known commits to be walked for roots (see `analyze_branch`). Known commits are only walked if no roots of the project were recorded yet, otherwise their roots were recorded by earlier updates. 
+     */
+    roots : Vec<SHA>,
+    known : Vec<SHA>,
+    walk_known : bool,
     snapshots : usize,
+    /** Class of the libgit2 error the update failed with, if any. Other errors are classified from the returned I/O error. 
+     */
+    error_code : Option<ErrorCode>,
+    /** Time the update started, bytes of the stored snapshots, the limit that aborted the update, if any, and the multiplier of the limits, which are raised for projects that exceeded them before (see `exceeded_limit`). 
+     */
+    start_time : i64,
+    snapshot_bytes : usize,
+    limit_exceeded : Option<String>,
+    limits_multiplier : usize,
+    /** Whether GitHub reports the project as a fork and the project's default branch, if known. Forks are updated according to the fork policy (see `ForkPolicy`). 
+     */
+    fork : bool,
+    default_branch : Option<String>,
+    /** Guards of the substores the update adds data to, or migrates data from, held until the update finishes (see `guard_substore`). 
+     */
+    guards : Vec<(StoreKind, std::sync::RwLockReadGuard<'a, ()>)>,
 }
 
 impl<'a> Drop for RepoUpdater<'a> {
@@ -70,7 +106,7 @@ impl<'a> RepoUpdater<'a> {
     /** Creates new repository updater. 
      */
     fn new(ds : &'a Datastore, gh : &'a Github, task : TaskStatus<'a>, force : bool, load_substore : bool) -> RepoUpdater<'a> {
-        if let Task::UpdateRepo{id, last_update_time : _ } = task.task {
+        if let Task::UpdateRepo{id, due_time : _ } = task.task {
             return RepoUpdater {
                 ds,
                 gh,
@@ -86,7 +122,18 @@ impl<'a> RepoUpdater<'a> {
                 users : HashMap::new(),
                 paths : HashMap::new(),
                 q : Vec::new(),
+                roots : Vec::new(),
+                known : Vec::new(),
+                walk_known : false,
                 snapshots : 0,
+                error_code : None,
+                start_time : helpers::now(),
+                snapshot_bytes : 0,
+                limit_exceeded : None,
+                limits_multiplier : 1,
+                fork : ds.get_project_metadata(id, Metadata::FORK).map(|x| x == "true").unwrap_or(false),
+                default_branch : None,
+                guards : Vec::new(),
             };
         } else {
             panic!("Invalid task kind");
@@ -100,11 +147,29 @@ impl<'a> RepoUpdater<'a> {
     fn update(& mut self) -> Result<(), std::io::Error> {
         self.task.extra_url(self.project.name(), self.project.clone_url());
         if self.can_be_updated() {
-            self.check_metadata()?;
+            // if the repository has been deleted, there is nothing to update
+            if ! self.check_metadata()? {
+                return Ok(());
+            }
             // update the project contents
             match self.update_repository() {
                 Err(e) => {
-                    return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("{}", e.message())));
+                    // exceeding the limits is not an error, but a distinct outcome so that such projects can be updated separately
+                    if let Some(limit) = self.limit_exceeded.take() {
+                        self.ds.update_project_update_status(self.id, ProjectLog::LimitExceeded{
+                            time : helpers::now(),
+                            version : Datastore::VERSION,
+                            limit : limit.clone(),
+                        });
+                        self.task.info(format!("limit exceeded: {}", limit));
+                        self.task.color("\x1b[93m");
+                        return Ok(());
+                    }
+                    // a cancelled update is not an error, it is simply not recorded in the project log
+                    if ! self.task.is_cancelled() {
+                        self.error_code = Some(git_error_code(& e));
+                        return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("{}", e.message())));
+                    }
                 },
                 Ok(processed) => {
                     // if there was no error and the task was not cancelled, report the change / no-change 
@@ -136,11 +201,25 @@ impl<'a> RepoUpdater<'a> {
 
     /** Checks whether the current project can be updated and whether the update should be forced. 
      
-        TODO we should ideally do something smatrter when there is an error during the update, i.e. dependning on the error, etc. 
+        Projects whose last update failed with an error that should not be retried anymore (see `ErrorCode::retry_interval`) are not updated unless the update is forced. 
      */
     fn can_be_updated(& mut self) -> bool {
-        // check if there was error during the update, in which case we do not attempt to update the project again
         if let Some(last_update) = self.ds.get_project_last_update(self.id) {
+            if last_update.is_tombstone() && ! self.force {
+                self.task.info("deleted");
+                return false;
+            }
+            // check if there was error during the update which should not be retried, in which case we do not attempt to update the project again
+            if let Some(code) = last_update.error_code() {
+                if ! self.force && retry_time(self.ds, self.id).is_none() {
+                    self.task.info(format!("not retried ({:?})", code));
+                    return false;
+                }
+            }
+            // projects that exceeded the limits before are updated with raised limits (see `updateoversized`)
+            if last_update.is_limit_exceeded() {
+                self.limits_multiplier = SETTINGS.oversized_limits;
+            }
             // if the version of the last update differs from current version of the datastore, we might need to do something special
             if Datastore::VERSION != last_update.version() {
                 self.new_version_update(last_update.version(), Datastore::VERSION);
@@ -153,11 +232,19 @@ impl<'a> RepoUpdater<'a> {
      
         The default action is to do force update, which is technically not always what we want to do and different version situations should actually be covered here. 
      */
-    fn new_version_update(& mut self, _old : u16, _new : u16) {
+    fn new_version_update(& mut self, old : u16, new : u16) {
+        // version 1 only added new kinds of project update log records, there are no new data to obtain
+        if old == 0 && new == 1 {
+            return;
+        }
         self.force = true;
     }
 
-    fn check_metadata(& mut self) -> Result<(), std::io::Error> {
+    /** Checks the project's metadata, records project renames and stores the metadata. 
+     
+        Returns false if the metadata show that the repository has been deleted (blocked due to a DMCA takedown, or disabled), in which case the project is tombstoned, true otherwise. Repositories that are not found are reported as errors first and tombstoned only if they are not found repeatedly (see `ErrorCode::NOT_FOUND_ATTEMPTS`). 
+     */
+    fn check_metadata(& mut self) -> Result<bool, std::io::Error> {
         match & self.project {
             /* There is nothing extra we can do for raw git projects as there are no metadata associated with them. 
              */
@@ -168,23 +255,93 @@ impl<'a> RepoUpdater<'a> {
              */
             ProjectUrl::GitHub{user_and_repo} => {
                 self.task.info("checking metadata...");
-                let mut metadata = self.gh.get_repo(user_and_repo, Some(& self.task))
-                ?;
-                // check project rename
-                let new_url = format!("{}.git",metadata["html_url"]).to_lowercase();
-                self.check_url_change(& new_url)?;
-                // clean the metadata and store, if applicable
-                filter_github_metadata_keys(& mut metadata, true);
-                self.changed = self.ds.update_project_metadata_if_differ(self.id, Metadata::GITHUB_METADATA.to_owned(), metadata.to_string());
-                // update the project store if the language is provided in the metadata, i.e. hold the substore as provided by the metadata tentatively in the substore field, when the project is updated, the tentative value and the real value obtained from the datastore will be reconciled
-                if metadata["language"].is_string() {
-                    if let Some(substore) = StoreKind::from_string(metadata["language"].as_str().unwrap()) {
-                        self.tentative_substore = substore;
+                let stored = self.ds.get_project_metadata(self.id, Metadata::GITHUB_METADATA).and_then(|x| json::parse(& x).ok());
+                // metadata obtained by a batched GraphQL query only contain a subset of the fields, so update the stored metadata with them
+                if let Some(prefetched) = self.gh.take_prefetched(user_and_repo) {
+                    let mut metadata = stored.unwrap_or_else(|| json::JsonValue::new_object());
+                    for (key, value) in prefetched.entries() {
+                        metadata[key] = value.clone();
                     }
+                    return self.update_metadata(metadata);
                 }
+                // otherwise only ask for the metadata if they changed since they were stored
+                let etag = if stored.is_some() { self.ds.get_project_metadata(self.id, Metadata::GITHUB_ETAG) } else { None };
+                let metadata = match self.gh.get_repo_if_modified(user_and_repo, etag.as_deref(), Some(& self.task)) {
+                    Ok(GithubResponse::Modified{value, etag}) => {
+                        if let Some(etag) = etag {
+                            self.ds.update_project_metadata_if_differ(self.id, Metadata::GITHUB_ETAG.to_owned(), etag);
+                        }
+                        value
+                    },
+                    Ok(GithubResponse::NotModified) => stored.unwrap(),
+                    Err(e) => {
+                        // repositories unavailable for legal reasons (DMCA takedowns) are gone for good
+                        if GithubError::status_of(& e) == Some(451) {
+                            self.tombstone("blocked");
+                            return Ok(false);
+                        }
+                        return Err(e);
+                    }
+                };
+                return self.update_metadata(metadata);
             }
         }
-        return Ok(());
+        return Ok(true);
+    }
+
+    /** Updates the project from the obtained github metadata. 
+     
+        Checks the project rename, stores the metadata if they changed, determines the tentative substore from the language and tombstones disabled projects. 
+     */
+    fn update_metadata(& mut self, mut metadata : json::JsonValue) -> Result<bool, std::io::Error> {
+        // check project rename
+        let new_url = format!("{}.git",metadata["html_url"]).to_lowercase();
+        self.check_url_change(& new_url)?;
+        // clean the metadata and store, if applicable
+        filter_github_metadata_keys(& mut metadata, true);
+        self.changed = self.ds.update_project_metadata_if_differ(self.id, Metadata::GITHUB_METADATA.to_owned(), metadata.to_string());
+        // update the project store if the language is provided in the metadata, i.e. hold the substore as provided by the metadata tentatively in the substore field, when the project is updated, the tentative value and the real value obtained from the datastore will be reconciled
+        if metadata["language"].is_string() {
+            if let Some(substore) = StoreKind::from_string(metadata["language"].as_str().unwrap()) {
+                self.tentative_substore = substore;
+            }
+        }
+        if metadata["disabled"].as_bool() == Some(true) {
+            self.tombstone("disabled");
+            return Ok(false);
+        }
+        // record the fork relations, the parent and source repositories may not be in the datastore, so they are kept as urls
+        if let Some(fork) = metadata["fork"].as_bool() {
+            self.fork = fork;
+            self.ds.update_project_metadata_if_differ(self.id, Metadata::FORK.to_owned(), fork.to_string());
+        }
+        self.default_branch = metadata["default_branch"].as_str().map(|x| x.to_owned());
+        let related_url = |x : & json::JsonValue| x["full_name"].as_str().map(|x| ProjectUrl::GitHub{ user_and_repo : x.to_lowercase() });
+        let mut relations = Vec::new();
+        if let Some(url) = related_url(& metadata["parent"]) {
+            relations.push(ProjectRelation::Parent{url});
+        }
+        if let Some(url) = related_url(& metadata["source"]) {
+            relations.push(ProjectRelation::Source{url});
+        }
+        for relation in relations {
+            if self.ds.add_project_relation(self.id, relation) {
+                self.changed = true;
+            }
+        }
+        return Ok(true);
+    }
+
+    /** Records that the project's repository has been deleted, or is permanently unavailable for given reason. 
+     */
+    fn tombstone(& mut self, reason : & str) {
+        self.ds.update_project_update_status(self.id, ProjectLog::Tombstone{
+            time : helpers::now(),
+            version : Datastore::VERSION,
+            reason : reason.to_owned(),
+        });
+        self.task.info(format!("deleted ({})", reason));
+        self.task.color("\x1b[95m");
     }
 
     /** Compares the newly obtained project url to the one stored and records project rename if applicable. 
@@ -209,8 +366,15 @@ impl<'a> RepoUpdater<'a> {
         First loads the previous heads, if any and compares these to the heads fetched from the repository. If there are differences, clones the full repository and performs an update of its contents. 
      */
     fn update_repository(& mut self) -> Result<bool, git2::Error> {
-        // determine the actual substore of the project from the datastore
+        if self.fork && SETTINGS.forks == ForkPolicy::Skip {
+            self.task.info("fork, contents skipped");
+            return Ok(true);
+        }
+        // determine the actual substore of the project from the datastore, its data must not be garbage collected while the heads are used
         let mut substore = self.ds.get_project_substore(self.id);
+        if ! self.guard_substore(substore) {
+            return Ok(false);
+        }
         // create local repository
         // TODO reuse repository if found on disk already?, for now make sure there is no leftover repo present
         let path = std::path::Path::new(& self.local_folder);
@@ -221,24 +385,40 @@ impl<'a> RepoUpdater<'a> {
         let repo = git2::Repository::init_bare(self.local_folder.clone())?;
         let mut remote = repo.remote("dcd", & self.project.clone_url())?;
         remote.connect(git2::Direction::Fetch)?;
+        // finish the migration of project's data to its substore, if there is one pending 
+        if ! self.migrate_pending(substore) {
+            return Ok(false);
+        }
         // get own and remote heads and compare them 
         let last_heads = self.get_latest_heads();
         let mut remote_heads = self.get_remote_heads(& mut remote)?;
+        // only the default branch of forks is updated with the shallow policy, unless the branch is not found
+        if self.fork && SETTINGS.forks == ForkPolicy::Shallow {
+            if let Some(branch) = self.default_branch.as_ref().map(|x| format!("refs/heads/{}", x)) {
+                if remote_heads.contains_key(& branch) {
+                    remote_heads.retain(|name, _| *name == branch);
+                }
+            }
+        }
         let heads_to_fetch = self.compare_project_heads(& last_heads, & mut remote_heads, substore);
         // fetch the repository from the remote and analyze its contents
         if ! heads_to_fetch.is_empty() {
             self.clone_repository(& mut remote, & heads_to_fetch)?;
-            // check the repository's substore and terminate if the substore is not loaded should not be loaded
-            substore = self.update_repository_substore(& repo, substore)?;
-            if ! self.ds.substore(substore).is_loaded() {
-                if self.load_substore {
-                    self.ds.substore(substore).load(& self.task);
-                } else {
-                    return Ok(false);
-                }
+            // check the repository's substore, migrate the project if it changed and terminate if the substore is not loaded should not be loaded
+            let new_substore = self.update_repository_substore(& repo, substore)?;
+            if self.load_substore && ! self.ds.substore(new_substore).is_loaded() {
+                self.ds.substore(new_substore).load(& self.task);
+            }
+            if new_substore != substore {
+                self.change_substore(substore, new_substore, & last_heads, & mut remote_heads);
+                substore = new_substore;
+            }
+            if ! self.ds.substore(substore).is_loaded() || ! self.guard_substore(substore) {
+                return Ok(false);
             }
             // analyze the fetched heads
             let ds_s = self.ds.substore(substore);
+            self.walk_known = self.ds.get_project_roots(self.id).is_empty();
             let mut i = 0;
             self.task.progress(i, heads_to_fetch.len());
             for head in heads_to_fetch.iter() {
@@ -249,6 +429,8 @@ impl<'a> RepoUpdater<'a> {
                 i += 1;
                 self.task.progress(i, heads_to_fetch.len());
             }
+            self.update_mailmap(& repo, & remote_heads, & heads_to_fetch);
+            self.update_roots();
         }
         // if either the heads to fetch were not empty (i.e. there was a content to download), or there was no content, but the number of heads is different (some heads were deleted), store the updated heads
         if ! heads_to_fetch.is_empty() || remote_heads.len() != last_heads.len() {
@@ -258,6 +440,44 @@ impl<'a> RepoUpdater<'a> {
         return Ok(true);
     }
 
+    /** Records the root commits found while analyzing the fetched heads and relates the project to other projects sharing them. 
+     */
+    fn update_roots(& mut self) {
+        if self.roots.is_empty() {
+            return;
+        }
+        let roots = std::mem::take(& mut self.roots);
+        for other in self.ds.add_project_roots(self.id, & roots) {
+            if self.ds.add_project_relation(self.id, ProjectRelation::SharedRoot{ id : other }) {
+                self.task.info(format!("shares history with project {}", other));
+                self.changed = true;
+            }
+        }
+    }
+
+    /** Stores the repository's `.mailmap` file, if any, in the project metadata. 
+     
+        The file is taken from the master (or main) branch if it was fetched, from any other fetched branch otherwise. The mailmap is later used to merge the aliases of the substore's users (see `task_merge_aliases`). 
+     */
+    fn update_mailmap(& mut self, repo : & git2::Repository, heads : & ProjectHeads, fetched : & Vec<String>) {
+        let mut branches : Vec<& String> = fetched.iter().collect();
+        branches.sort_by_key(|x| ! (x.as_str() == "refs/heads/master" || x.as_str() == "refs/heads/main"));
+        for branch in branches {
+            let (_, hash) = heads[branch];
+            let blob = repo.find_commit(hash)
+                .and_then(|commit| commit.tree())
+                .ok()
+                .and_then(|tree| tree.get_name(".mailmap").map(|entry| entry.id()))
+                .and_then(|id| repo.find_blob(id).ok());
+            if let Some(blob) = blob {
+                if self.ds.update_project_metadata_if_differ(self.id, Metadata::MAILMAP.to_owned(), helpers::to_string(blob.content())) {
+                    self.changed = true;
+                }
+                return;
+            }
+        }
+    }
+
     /** Check the repository to determine the substore that should be used for the update. 
      
         Returns the store kind for the project, taking the current  store kind as a hint. 
@@ -287,13 +507,87 @@ impl<'a> RepoUpdater<'a> {
                 substore = StoreKind::Generic;
             }
         }
-        // check if the substore changed and if so, update the substore information. 
-        if substore != current_substore {
-            self.ds.update_project_substore(self.id, substore);
-        }
         return Ok(substore);
     }
 
+    /** Moves the project from its current substore to a new one. 
+     
+        If the project has any data in its current substore, these are migrated to the new substore so that the project does not have to be analyzed again in its entirety. The migration requires the new substore to be loaded, if it is not, the project's substore is changed with empty heads and the migration is recorded as pending, to be finished when the project is updated with the new substore loaded.
+
+        Unchanged remote heads have their ids taken from the old substore, so they are translated to the new substore's ids as well.   
+     */
+    fn change_substore(& mut self, current_substore : StoreKind, new_substore : StoreKind, last_heads : & ProjectHeads, remote_heads : & mut ProjectHeads) {
+        if ! current_substore.is_specified() || last_heads.is_empty() {
+            self.ds.update_project_substore(self.id, new_substore);
+        } else if self.ds.substore(new_substore).is_loaded() && self.guard_substore(new_substore) {
+            self.task.info(format!("migrating project from {:?} to {:?}...", current_substore, new_substore));
+            let mut migration = ProjectMigration::new(self.ds.substore(current_substore), self.ds.substore(new_substore), & self.task);
+            let new_heads = migration.migrate(last_heads);
+            self.ds.migrate_project_substore(self.id, new_substore, & new_heads);
+            for (name, (id, hash)) in remote_heads.iter_mut() {
+                if let Some((new_id, new_hash)) = new_heads.get(name) {
+                    if hash == new_hash {
+                        *id = *new_id;
+                    }
+                }
+            }
+        } else {
+            self.ds.update_project_substore(self.id, new_substore);
+            self.ds.set_project_pending_migration(self.id, current_substore, last_heads);
+        }
+    }
+
+    /** Finishes pending migration of the project's data to its current substore, if any.
+     
+        Returns false if there is a pending migration, but it cannot be performed because the substore is not loaded and should not be loaded, true otherwise. 
+     */
+    fn migrate_pending(& mut self, substore : StoreKind) -> bool {
+        // the pending heads are only read once the source substore is guarded as the garbage collection rewrites them
+        if let Some((source, _)) = self.ds.get_project_pending_migration(self.id) {
+            if ! self.guard_substore(source) {
+                return false;
+            }
+        }
+        if let Some((source, heads)) = self.ds.get_project_pending_migration(self.id) {
+            if source == substore || heads.is_empty() {
+                self.ds.migrate_project_substore(self.id, substore, & heads);
+                return true;
+            }
+            if ! self.ds.substore(substore).is_loaded() {
+                if self.load_substore {
+                    self.ds.substore(substore).load(& self.task);
+                } else {
+                    return false;
+                }
+            }
+            self.task.info(format!("migrating project from {:?} to {:?}...", source, substore));
+            let mut migration = ProjectMigration::new(self.ds.substore(source), self.ds.substore(substore), & self.task);
+            let new_heads = migration.migrate(& heads);
+            self.ds.migrate_project_substore(self.id, substore, & new_heads);
+        }
+        return true;
+    }
+
+    /** Guards given substore against garbage collection for the rest of the update. 
+     
+        Returns false if the substore is being garbage collected, in which case the update must not continue. 
+     */
+    fn guard_substore(& mut self, store : StoreKind) -> bool {
+        if ! store.is_specified() || self.guards.iter().any(|(kind, _)| *kind == store) {
+            return true;
+        }
+        match self.ds.substore(store).guard_update() {
+            Some(guard) => {
+                self.guards.push((store, guard));
+                return true;
+            },
+            None => {
+                self.task.info(format!("substore {:?} is being garbage collected", store));
+                return false;
+            }
+        }
+    }
+
     /** Counts commits in the repository up to given limit. 
      
         Determines the number of commits in the repository. If the number of commits is at least the given limit, stops looking further. 
@@ -326,7 +620,7 @@ impl<'a> RepoUpdater<'a> {
 
     /** Returns the remote heads as of last analysis. 
      
-        If no previous update is found, returns empty heads. Note that if project changes store, it receives an extra tombstone empty heads so that the update in the new substore will be full. 
+        If no previous update is found, returns empty heads. Note that if project changes store and its data could not be migrated, it receives an extra tombstone empty heads so that the update in the new substore will be full. 
      */
     fn get_latest_heads(& mut self) -> ProjectHeads {
         // if we are performing a forced update, return empty result so that all heads are fetched again 
@@ -388,29 +682,82 @@ impl<'a> RepoUpdater<'a> {
      */
     fn clone_repository(& mut self, remote : & mut git2::Remote, heads : & Vec<String>) -> Result<(), git2::Error> {
         self.task.info("downloading repository contents...");
-        let mut callbacks = git2::RemoteCallbacks::new();
-        callbacks.transfer_progress(|progress : git2::Progress| -> bool {
-            self.task.progress(
-                progress.received_objects() + progress.indexed_deltas() + progress.indexed_objects(),
-                progress.total_deltas() + progress.total_objects() * 2
-            );
-            return true;
-        });
-        let mut opts = git2::FetchOptions::new();
-        opts.remote_callbacks(callbacks); 
-        return remote.fetch(& heads, Some(&mut opts), None);        
+        let mut limit_exceeded = None;
+        let result;
+        {
+            let cancelled = self.task.cancelled.clone();
+            let mut callbacks = git2::RemoteCallbacks::new();
+            let mut received_bytes = 0;
+            let start_time = self.start_time;
+            let multiplier = self.limits_multiplier;
+            callbacks.transfer_progress(|progress : git2::Progress| -> bool {
+                Metrics::add(& METRICS.bytes_fetched, (progress.received_bytes() - received_bytes) as u64);
+                received_bytes = progress.received_bytes();
+                self.task.progress(
+                    progress.received_objects() + progress.indexed_deltas() + progress.indexed_objects(),
+                    progress.total_deltas() + progress.total_objects() * 2
+                );
+                // returning false aborts the fetch
+                if cancelled.load(std::sync::atomic::Ordering::SeqCst) {
+                    return false;
+                }
+                limit_exceeded = exceeded_limit(multiplier, start_time, 0, received_bytes, 0);
+                return limit_exceeded.is_none();
+            });
+            let mut opts = git2::FetchOptions::new();
+            opts.remote_callbacks(callbacks); 
+            result = remote.fetch(& heads, Some(&mut opts), None);
+        }
+        self.limit_exceeded = limit_exceeded;
+        return result;
+    }
+
+    /** Checks the limits of the update against its current state and aborts the update with an error if any of them is exceeded, or if the task has been cancelled. 
+     */
+    fn check_limits(& mut self) -> Result<(), git2::Error> {
+        if self.task.is_cancelled() {
+            return Err(git2::Error::from_str(TaskStatus::CANCELLED));
+        }
+        if let Some(limit) = exceeded_limit(self.limits_multiplier, self.start_time, self.visited_commits.len(), 0, self.snapshot_bytes) {
+            let message = format!("limit exceeded: {}", limit);
+            self.limit_exceeded = Some(limit);
+            return Err(git2::Error::from_str(& message));
+        }
+        return Ok(());
     }
 
     /** Analyzes given branch, starting at a head commit and returns the id of the head commit. 
      
+        Root commits of the analyzed history are collected for `update_roots`. Commits already known to the substore are not analyzed, but if the project has no roots recorded yet, they are walked to find the roots of the history the project shares with other projects. 
      */
     fn analyze_branch(& mut self, repo : & git2::Repository, head : SHA, substore : & Substore) -> Result<CommitId, git2::Error> {
         // add head to the queue
         let head_id = self.add_commit(& head, substore);
-        // process the queue
-        while let Some((hash, id)) = self.q.pop() {
+        // process the queue, new commits first
+        loop {
+            let (hash, id) = match self.q.pop() {
+                Some(x) => x,
+                None => match self.known.pop() {
+                    Some(hash) => {
+                        self.check_limits()?;
+                        let commit = repo.find_commit(hash)?;
+                        if commit.parent_count() == 0 {
+                            self.roots.push(hash);
+                        }
+                        for parent in commit.parent_ids() {
+                            self.add_commit(& parent, substore);
+                        }
+                        continue;
+                    },
+                    None => break,
+                },
+            };
+            self.check_limits()?;
             // get the commit and process it
             let commit = repo.find_commit(hash)?;
+            if commit.parent_count() == 0 {
+                self.roots.push(hash);
+            }
             let mut commit_info = CommitInfo::new();
             // get committer & author information
             commit_info.committer = self.get_or_create_user(& commit.committer(), substore);
@@ -426,6 +773,7 @@ impl<'a> RepoUpdater<'a> {
             commit_info.changes = self.get_commit_changes(repo, & commit, substore)?;
             // store the commit info
             substore.add_commit_info_if_missing(id, & commit_info);
+            Metrics::add(& METRICS.commits_added, 1);
             // update the information
             self.update_task();
         }
@@ -446,6 +794,8 @@ impl<'a> RepoUpdater<'a> {
         self.visited_commits.insert(*hash, id);
         if is_new || self.force {
             self.q.push((*hash, id)); 
+        } else if self.walk_known {
+            self.known.push(*hash);
         }
         return id;
     }
@@ -458,7 +808,11 @@ impl<'a> RepoUpdater<'a> {
             let (id, _) = substore.get_or_create_user_id(& email);
             // add to cache
             self.users.insert(email, id);
-            // TODO check the username against usernames in the metadata of the user and so on? 
+            // keep all names the user uses, these help to identify the user (see github logins and aliases)
+            let name = helpers::to_string(user.name_bytes());
+            if ! name.is_empty() {
+                substore.add_user_metadata_if_new(id, Metadata::USER_NAME.to_owned(), name);
+            }
             return id;
         }
     }
@@ -484,6 +838,8 @@ impl<'a> RepoUpdater<'a> {
                         if let Some(kind) = ContentsKind::from_contents(contents, path_kind) {
                             substore.add_file_contents(*hash_id, kind, & Vec::from(contents));
                             self.snapshots += 1;
+                            self.snapshot_bytes += contents.len();
+                            Metrics::add(& METRICS.snapshots_added, 1);
                         }
                     } 
                 }
@@ -537,11 +893,49 @@ impl<'a> RepoUpdater<'a> {
 }
 
 
+/** Returns the description of the first limit of a single project update exceeded by an update in given state, or None if all limits are satisfied. 
+ 
+    The limits are specified on the command line (`--update-timeout`, `--max-commits`, `--max-fetched-bytes` and `--max-snapshot-bytes`) and multiplied by given multiplier, unspecified limits are never exceeded. 
+ */
+fn exceeded_limit(multiplier : usize, start_time : i64, commits : usize, fetched_bytes : usize, snapshot_bytes : usize) -> Option<String> {
+    let elapsed = helpers::now() - start_time;
+    if let Some(limit) = SETTINGS.update_timeout.map(|x| x * multiplier as i64).filter(|x| elapsed > *x) {
+        return Some(format!("timeout ({} s)", limit));
+    } else if let Some(limit) = SETTINGS.max_commits.map(|x| x * multiplier).filter(|x| commits > *x) {
+        return Some(format!("commits ({})", limit));
+    } else if let Some(limit) = SETTINGS.max_fetched_bytes.map(|x| x * multiplier).filter(|x| fetched_bytes > *x) {
+        return Some(format!("fetched bytes ({})", limit));
+    } else if let Some(limit) = SETTINGS.max_snapshot_bytes.map(|x| x * multiplier).filter(|x| snapshot_bytes > *x) {
+        return Some(format!("snapshot bytes ({})", limit));
+    }
+    return None;
+}
+
+/** Classifies the libgit2 error. 
+ 
+    Errors of the network related classes are classified by the http status, if present in the message, all other libgit2 errors are internal. 
+ */
+fn git_error_code(e : & git2::Error) -> ErrorCode {
+    if e.code() == git2::ErrorCode::Auth {
+        return ErrorCode::AuthRequired;
+    }
+    match e.class() {
+        git2::ErrorClass::Net | git2::ErrorClass::Http | git2::ErrorClass::Ssl | git2::ErrorClass::Ssh => {
+            match ErrorCode::from_message(e.message()) {
+                ErrorCode::Unknown => return ErrorCode::Network,
+                code => return code,
+            }
+        },
+        git2::ErrorClass::NoMemory => return ErrorCode::TooLarge,
+        _ => return ErrorCode::Internal,
+    }
+}
+
 /** Removes all redundant url records from github metadata JSON object. 
  
     Removes all `_url` suffixed fields from the metadata record with the exception of `html_url` 
  */
-fn filter_github_metadata_keys(json : & mut json::JsonValue, is_root : bool) {
+pub (crate) fn filter_github_metadata_keys(json : & mut json::JsonValue, is_root : bool) {
     let mut x = Vec::new();
     for (key, value) in json.entries_mut() {
         if is_root && key == "html_url" {
@@ -1,42 +1,74 @@
 use crate::updater::*;
 use crate::records::*;
-use crate::db::*;
+use crate::helpers;
+use crate::scheduling::*;
 
 /** Task that does an update of a given substore. 
  
-    First the substore is loaded, then its own and unspecified projects are scheduled and then the task waits for completion of the scheduled queue and monitor the health of the datastore. 
+    First the substore is loaded, then its own and unspecified projects are scheduled and then the task waits for completion of the scheduled queue and monitor the health of the datastore. If the memory budget allows, other substores not yet updated in the current round are loaded and updated together with the substore (see `Updater::substore_update_group`). 
+
+    When cancelled, the projects scheduled so far stay in the queue, but no more projects are scheduled and the update does not continue with other substores. 
  */
 pub (crate) fn task_update_substore(updater : & Updater, store : StoreKind, mode : UpdateMode, task : TaskStatus) -> Result<(), std::io::Error> {
-    // load the substore
-    updater.ds.substore(store).load(& task);
-    let mut num_projects = 0;
+    // if the update was resumed from the persisted queue, continue scheduling where it stopped
+    let (mut i, mut num_projects, group) = updater.substore_update_progress(store, mode);
+    // load the substores updated together
+    updater.load_substores(& group, & task);
     // schedule all projects
     {
         let total_projects = updater.ds.num_projects();
         task.info("scheduling projects...");
-        task.progress(0, total_projects);
-        let mut i = 0;
+        task.progress(i, total_projects);
+        let now = helpers::now();
         while i < total_projects {
             let id = ProjectId::from(i as u64);
             let pstore = updater.ds.get_project_substore(id);
+            let mut project_task = None;
             // errors take *all* stores at once, and updates if the store is loaded
-            if pstore == store || pstore == StoreKind::Unspecified || mode == UpdateMode::Errors {
-                // its a possibly valid project, so determine the last time it was updated
-                if let Some(last_update) = updater.ds.get_project_last_update(id) {
-                    if ! last_update.is_error() || mode == UpdateMode::Errors {
-                        updater.schedule(Task::UpdateRepo{id, last_update_time : last_update.time()});
-                        num_projects += 1;
+            if group.contains(& pstore) || pstore == StoreKind::Unspecified || mode == UpdateMode::Errors {
+                // its a possibly valid project, so determine the last time it was updated and when it is due to be updated
+                let last_update = updater.ds.get_project_last_update(id);
+                let due_time = updater.policy.due_time(& updater.ds, id, last_update.as_ref());
+                if let Some(last_update) = last_update {
+                    // deleted projects are never scheduled, projects that exceeded the update limits only in the oversized mode
+                    if last_update.is_tombstone() || last_update.is_limit_exceeded() != (mode == UpdateMode::Oversized) {
+                        project_task = None;
+                    } else if ! last_update.is_error() {
+                        project_task = Some(Task::UpdateRepo{id, due_time});
+                    // failed projects are retried only when their retry time, which depends on the class of the error, has passed
+                    } else if mode == UpdateMode::Errors {
+                        if let Some(retry_time) = retry_time(& updater.ds, id) {
+                            if retry_time <= now {
+                                project_task = Some(Task::UpdateRepo{id, due_time : retry_time});
+                            }
+                        }
                     }
                 } else {
-                    if mode != UpdateMode::Errors {
-                        updater.schedule(Task::UpdateRepo{id, last_update_time : 0});
-                        num_projects += 1;
+                    if mode != UpdateMode::Errors && mode != UpdateMode::Oversized {
+                        project_task = Some(Task::UpdateRepo{id, due_time});
                     }
                 }
+                // continuous updates skip projects that are not due yet, they will be checked in the next round
+                if mode == UpdateMode::Continuous && due_time > now {
+                    project_task = None;
+                }
+            }
+            if project_task.is_some() {
+                num_projects += 1;
             }
             i += 1;
+            updater.schedule_substore_project(store, mode, project_task, i, num_projects);
             if i % 1000 == 0 {
                 task.progress(i, total_projects);
+                // the progress is kept so that the update can be resumed
+                if updater.is_stopping() {
+                    return Ok(());
+                }
+                // a cancelled update keeps the projects scheduled so far, but does not continue with other substores
+                if task.is_cancelled() {
+                    updater.finish_substore_update(store, mode);
+                    return Ok(());
+                }
             }
         }
     }
@@ -52,22 +84,27 @@ pub (crate) fn task_update_substore(updater : & Updater, store : StoreKind, mode
                 if pool.running_workers == 1 && pool.queue.is_empty() {
                     break;
                 }
-                progress = num_projects - pool.queue.len();
+                // the progress is kept so that the update can be resumed
+                if pool.state == State::Stopped {
+                    return Ok(());
+                }
+                if task.is_cancelled() {
+                    break;
+                }
+                progress = num_projects.saturating_sub(pool.queue.len());
             }
             task.progress(progress, num_projects);
             // and sleep for a second
             std::thread::sleep(std::time::Duration::from_millis(1000));
         }
     }
-    // now that we have finished we can start update of other datastore. Technically we can do this earlier too, as long as the queue is empty and there are some idle threads, but that would require the necessity to have two substore mappings loaded in memory which we want to avoid. So this is less efficient but more robust solution
-    if mode != UpdateMode::Single {
-        let mut next_substore = StoreKind::from_number(store.to_number() + 1);
-        if next_substore == StoreKind::Unspecified && mode == UpdateMode::Continuous {
-            next_substore = StoreKind::from_number(0);
-        }
-        if next_substore != StoreKind::Unspecified && mode != UpdateMode::Errors {
-            updater.schedule(Task::UpdateSubstore{store : next_substore, mode});
-        }
+    let done = updater.finish_substore_update(store, mode);
+    if task.is_cancelled() {
+        return Ok(());
+    }
+    // now that we have finished we can start update of other datastore. Technically we can do this earlier too, as long as the queue is empty and there are some idle threads, but that would require the necessity to have two substore mappings loaded in memory which we want to avoid. So this is less efficient but more robust solution. Substores that fit in the memory budget are updated together instead
+    if mode != UpdateMode::Single && mode != UpdateMode::Errors {
+        updater.schedule_next_substore_update(mode, done);
     }
     return Ok(());
 }
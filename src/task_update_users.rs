@@ -0,0 +1,109 @@
+use std::collections::*;
+
+use crate::datastore::*;
+use crate::updater::*;
+use crate::records::*;
+use crate::github::*;
+
+/** Resolves the authors of given GitHub project's commits to their GitHub accounts and stores their profiles.
+
+    For each author of the project's commits whose GitHub login is not known yet, one of their commits is requested from GitHub, which reports the account the author's email is linked to, if any. The login (empty if the email is not linked to any account) is stored in the user's metadata, so that each author is only resolved once. Profiles of the accounts of all authors (login, name, location, company and time of the account creation) are then requested and stored in the user's metadata as well, every time they change.
+
+    Commits that cannot be found on GitHub (such as commits no longer reachable from the repository's branches) are skipped, their authors are resolved later via other commits.
+ */
+pub (crate) fn task_update_users(ds : & Datastore, gh : & Github, id : ProjectId, task : TaskStatus) -> Result<(), std::io::Error> {
+    let user_and_repo = match ds.get_project(id) {
+        Some(ProjectUrl::GitHub{user_and_repo}) => user_and_repo,
+        _ => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Project {} is not a GitHub project", id))),
+    };
+    task.extra_url(& user_and_repo, & format!("https://github.com/{}", user_and_repo));
+    let substore = match ds.get_project_substore(id) {
+        StoreKind::Unspecified => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Project {} has not been analyzed yet", id))),
+        store => ds.substore(store),
+    };
+    let _guard = match substore.guard_update() {
+        Some(guard) => guard,
+        None => return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("Substore {:?} is being garbage collected", substore.prefix))),
+    };
+    // find a commit for every author whose login is not known
+    task.info("collecting authors...");
+    let mut authors = HashMap::<UserId, CommitId>::new();
+    let mut linked = HashMap::<UserId, String>::new();
+    let mut known = HashSet::<UserId>::new();
+    let mut visited = HashSet::<CommitId>::new();
+    let mut q : Vec<CommitId> = ds.get_project_heads(id).unwrap_or(ProjectHeads::new()).iter().map(|(_, (id, _))| *id).collect();
+    while let Some(commit) = q.pop() {
+        if ! visited.insert(commit) {
+            continue;
+        }
+        if visited.len() % 1000 == 0 {
+            task.info(format!("collecting authors, {} commits...", visited.len()));
+            task.check_cancelled()?;
+        }
+        if let Some(cinfo) = substore.get_commit_info(commit) {
+            if ! known.contains(& cinfo.author) && ! authors.contains_key(& cinfo.author) {
+                if let Some(login) = substore.get_user_metadata(cinfo.author, Metadata::GITHUB_LOGIN) {
+                    if ! login.is_empty() {
+                        linked.insert(cinfo.author, login);
+                    }
+                    known.insert(cinfo.author);
+                } else {
+                    authors.insert(cinfo.author, commit);
+                }
+            }
+            q.extend(cinfo.parents.iter());
+        }
+    }
+    // resolve the authors
+    let mut resolved = 0;
+    let mut i = 0;
+    for (user, commit) in authors.iter() {
+        task.progress(i, authors.len());
+        i += 1;
+        task.check_cancelled()?;
+        let hash = match substore.get_commit_hash(*commit) {
+            Some(hash) => hash,
+            None => continue,
+        };
+        let login = match gh.get_commit(& user_and_repo, & hash.to_string(), Some(& task)) {
+            Ok(json) => json["author"]["login"].as_str().unwrap_or("").to_owned(),
+            Err(e) => {
+                task.check_cancelled()?;
+                task.info(format!("commit {} not available: {}", hash, e));
+                continue;
+            }
+        };
+        substore.update_user_metadata_if_differ(*user, Metadata::GITHUB_LOGIN.to_owned(), login.clone());
+        if ! login.is_empty() {
+            linked.insert(*user, login);
+            resolved += 1;
+        }
+    }
+    // update the profiles of the accounts, multiple emails may be linked to the same account
+    task.info(format!("{} authors resolved, updating {} profiles...", resolved, linked.len()));
+    let mut profiles = HashMap::<String, String>::new();
+    for (user, login) in linked.iter() {
+        task.check_cancelled()?;
+        if ! profiles.contains_key(login) {
+            // accounts may have been renamed, or deleted since the login was resolved
+            let json = match gh.get_user(login, Some(& task)) {
+                Ok(json) => json,
+                Err(e) => {
+                    task.check_cancelled()?;
+                    task.info(format!("user {} not available: {}", login, e));
+                    continue;
+                }
+            };
+            let mut profile = json::JsonValue::new_object();
+            profile["login"] = json["login"].clone();
+            profile["name"] = json["name"].clone();
+            profile["location"] = json["location"].clone();
+            profile["company"] = json["company"].clone();
+            profile["created_at"] = json["created_at"].clone();
+            profiles.insert(login.clone(), profile.dump());
+        }
+        substore.update_user_metadata_if_differ(*user, Metadata::GITHUB_PROFILE.to_owned(), profiles[login].clone());
+    }
+    task.info(format!("{} new authors, {} linked to GitHub accounts, {} profiles", authors.len(), resolved, profiles.len()));
+    return Ok(());
+}
@@ -16,7 +16,10 @@ pub (crate) fn task_verify_substore(updater : & Updater, store : StoreKind, mode
         },
         Err(e) => {
             substore.clear(& task);
-            verify_next(updater, store, mode);
+            // cancelled verification does not continue with other substores
+            if ! task.is_cancelled() {
+                verify_next(updater, store, mode);
+            }
             return Err(e);
         }
     }
@@ -14,6 +14,14 @@ use crate::datastore_maintenance_tasks::*;
 use crate::task_update_repo::*;
 use crate::task_update_substore::*;
 use crate::task_verify_substore::*;
+use crate::task_migrate_project::*;
+use crate::task_gc_substore::*;
+use crate::task_update_github_items::*;
+use crate::task_update_users::*;
+use crate::task_merge_aliases::*;
+use crate::remote_control::*;
+use crate::metrics::*;
+use crate::scheduling::*;
 use crate::reporter::*;
 
 use crate::settings::SETTINGS;
@@ -25,17 +33,46 @@ pub struct TaskStatus<'a> {
     pub tx : &'a Tx,
     pub name : String,
     pub task : Task,
+    /** Cancellation token of the task, set when the task should stop at its next checkpoint. 
+     */
+    pub cancelled : Arc<atomic::AtomicBool>,
 }
 
 impl<'a> TaskStatus<'a> {
+    /** Error message of tasks stopped by their cancellation. 
+     */
+    pub const CANCELLED : &'static str = "cancelled";
+
     pub fn new(tx : &'a Tx, task : Task) -> TaskStatus {
+        return TaskStatus::with_token(tx, task, Arc::new(atomic::AtomicBool::new(false)));
+    }
+
+    /** Creates the task status with an existing cancellation token so that the task can be cancelled from the outside. 
+     */
+    pub fn with_token(tx : &'a Tx, task : Task, cancelled : Arc<atomic::AtomicBool>) -> TaskStatus<'a> {
         return TaskStatus {
             tx : tx, 
             name : task.name(),
-            task : task
+            task : task,
+            cancelled,
         };
     }
 
+    /** Returns true if the task has been cancelled. 
+     */
+    pub fn is_cancelled(& self) -> bool {
+        return self.cancelled.load(atomic::Ordering::SeqCst);
+    }
+
+    /** Returns an error if the task has been cancelled, so that cancellation can be propagated with the `?` operator from the task's checkpoints. 
+     */
+    pub fn check_cancelled(& self) -> Result<(), std::io::Error> {
+        if self.is_cancelled() {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, TaskStatus::CANCELLED));
+        }
+        return Ok(());
+    }
+
     pub fn info<S: Into<String>>(& self, info : S) {
         self.tx.send(TaskMessage::Info{name : self.name.to_owned(), info : info.into() }).unwrap();
     }
@@ -72,6 +109,10 @@ pub (crate) struct Updater {
 
     pub (crate) github : Github,
 
+    /** Policy that determines when projects are due to be updated. 
+     */
+    pub (crate) policy : Box<dyn SchedulingPolicy>,
+
     /** Incremental updater
      */
     num_workers : usize, 
@@ -82,6 +123,13 @@ pub (crate) struct Updater {
      */
     pub (crate) project_urls : Mutex<HashSet<ProjectUrl>>,
 
+    /** Substores used by substore updates, least recently used first. 
+     */
+    substores_used : Mutex<Vec<StoreKind>>,
+
+    /** Channels to which all task messages received by the reporter are forwarded (used by the remote control to stream the events). 
+     */
+    listeners : Mutex<Vec<Tx>>,
 
     /** Mutex to guard console output.
      */
@@ -92,17 +140,37 @@ impl Updater {
 
     pub const NEVER : i64 = 0;
 
+    /** Number of seconds between the summaries written by the headless reporter. 
+     */
+    pub const SUMMARY_INTERVAL : i64 = 60;
+
+    /** Number of seconds between the saves of the task queue while the updater is running. 
+     */
+    pub const QUEUE_SAVE_INTERVAL : i64 = 300;
+
+    /** Name of the file in the datastore root where the task queue is persisted. 
+     */
+    pub const QUEUE_FILE : &'static str = "updater-queue.txt";
+
+    /** Number of next tasks displayed by the `queue` command. 
+     */
+    const QUEUE_SUMMARY_TASKS : usize = 10;
+
     /** Updater is initialized with an existing datastore. 
      */
     pub fn new(ds : Datastore) -> Updater {
         return Updater {
             ds, 
             github : Github::new(& SETTINGS.github_tokens),
+            policy : scheduling_policy(& SETTINGS.policy).expect(& format!("Unknown scheduling policy {}", SETTINGS.policy)),
             num_workers : SETTINGS.num_threads,
             pool : Mutex::new(Pool::new()),
             cv_workers : Condvar::new(),
 
             project_urls : Mutex::new(HashSet::new()),
+            substores_used : Mutex::new(Vec::new()),
+
+            listeners : Mutex::new(Vec::new()),
 
             cout_lock : Mutex::new(()),
         }
@@ -113,19 +181,49 @@ impl Updater {
         Because of the condvars not being able to pass the catch_unwind function barrier we use to execute the tasks, 
      */
     pub fn run(& self, command : String) {
+        match self.load_queue() {
+            Ok(0) => {},
+            Ok(tasks) => println!("Resumed {} tasks from the persisted queue", tasks),
+            Err(e) => println!("Unable to resume the persisted queue: {}", e),
+        }
         println!("Running updater...");
-        // prepare status & control screen
-        print!("\x1b[?1049h"); // switch to alternate mode
-        print!("\x1b[7r"); // enable scroll region
-        print!("\x1b[2J"); // clear screen
-        stdout().flush().unwrap();
+        if SETTINGS.headless_log.is_none() {
+            // prepare status & control screen
+            print!("\x1b[?1049h"); // switch to alternate mode
+            print!("\x1b[7r"); // enable scroll region
+            print!("\x1b[2J"); // clear screen
+            stdout().flush().unwrap();
+        }
         let (tx, rx) = crossbeam_channel::unbounded::<TaskMessage>();
         crossbeam::thread::scope(|s| {
+            match & SETTINGS.headless_log {
+                Some(path) => {
+                    // the headless reporter also executes the initial command as there is no interactive controller
+                    s.spawn(move |_| {
+                        self.headless_reporter(rx, path, command);
+                    });
+                },
+                None => {
+                    s.spawn(|_| {
+                        self.reporter(rx);
+                    });
+                    s.spawn(|_| {
+                        self.controller(command);
+                    });
+                }
+            }
+            if let Some(path) = & SETTINGS.control_socket {
+                s.spawn(move |s| {
+                    remote_controller(self, path, s);
+                });
+            }
+            if let Some(target) = & SETTINGS.metrics {
+                s.spawn(move |_| {
+                    metrics_exporter(self, target);
+                });
+            }
             s.spawn(|_| {
-                self.reporter(rx);
-            });
-            s.spawn(|_| {
-                self.controller(command);
+                self.queue_saver();
             });
             // start the worker threads
             for _ in 0.. self.num_workers {
@@ -134,8 +232,12 @@ impl Updater {
                 });
             }
         }).unwrap();
-        print!("\x1b[?1049l"); // return to normal mode
-        print!("\x1b[r"); // reset scroll region
+        if SETTINGS.headless_log.is_none() {
+            print!("\x1b[?1049l"); // return to normal mode
+            print!("\x1b[r"); // reset scroll region
+        }
+        // all workers have finished, save the pending tasks so that they can be resumed
+        self.save_queue().unwrap();
         println!("Updater terminated.");
     }
 
@@ -148,42 +250,94 @@ impl Updater {
         while let Some(task) = self.get_next_task() {
             let task_name = task.name();
             tx.send(TaskMessage::Start{name : task_name.to_owned()}).unwrap();
+            let cancelled = self.register_task(& task_name);
+            // substore updates are resumed from their progress, other tasks must be scheduled again if interrupted by stopping the updater
+            let interrupted = match task {
+                Task::UpdateSubstore{store : _, mode : _} => None,
+                _ => Some(task.clone()),
+            };
+            // if enabled, updated github projects have their issues, pull requests and releases updated as well
+            let github_items = match task {
+                Task::UpdateRepo{id, due_time : _} if SETTINGS.github_items => Some(id),
+                _ => None,
+            };
             let result = std::panic::catch_unwind(|| {
                 match task {
-                    Task::UpdateRepo{last_update_time : _, id : _ } => {
-                        return task_update_repo(& self.ds, & self.github, TaskStatus::new(& tx, task), /* force */ false, /* load_substore */ false);
+                    Task::UpdateRepo{due_time : _, id } => {
+                        let task = TaskStatus::with_token(& tx, task, cancelled.clone());
+                        self.prefetch_metadata(id, & task);
+                        return task_update_repo(& self.ds, & self.github, task, /* force */ false, /* load_substore */ false);
                     }
                     Task::AddProjects{ref source} => {
-                        return task_add_projects(& self.ds, source.to_owned(), TaskStatus::new(& tx, task));
+                        return task_add_projects(& self.ds, source.to_owned(), TaskStatus::with_token(& tx, task, cancelled.clone()));
                     },
                     Task::UpdateSubstore{store, mode} => {
-                        return task_update_substore(self, store, mode, TaskStatus::new(& tx, task));
+                        return task_update_substore(self, store, mode, TaskStatus::with_token(& tx, task, cancelled.clone()));
                     }, 
                     Task::LoadSubstore{store} => {
-                        return task_load_substore(& self.ds, store, TaskStatus::new(& tx, task));
+                        return task_load_substore(& self.ds, store, TaskStatus::with_token(& tx, task, cancelled.clone()));
                     },
                     Task::DropSubstore{store} => {
-                        return task_drop_substore(& self.ds, store, TaskStatus::new(& tx, task));
+                        return task_drop_substore(& self.ds, store, TaskStatus::with_token(& tx, task, cancelled.clone()));
                     }
                     Task::VerifySubstore{store, mode} => {
-                        return task_verify_substore(self, store, mode, TaskStatus::new(& tx, task));
+                        return task_verify_substore(self, store, mode, TaskStatus::with_token(& tx, task, cancelled.clone()));
                     }
                     Task::VerifyDatastore{} => {
-                        return task_verify_datastore(self, TaskStatus::new(& tx, task));
+                        return task_verify_datastore(self, TaskStatus::with_token(& tx, task, cancelled.clone()));
                     }
                     Task::CreateSavepoint{name : _} => {
-                        return task_create_savepoint(& self.ds, TaskStatus::new(& tx, task));
+                        return task_create_savepoint(& self.ds, TaskStatus::with_token(& tx, task, cancelled.clone()));
+                    }
+                    Task::MigrateProject{id, store} => {
+                        return task_migrate_project(& self.ds, id, store, TaskStatus::with_token(& tx, task, cancelled.clone()));
+                    }
+                    Task::GarbageCollect{store} => {
+                        return task_gc_substore(& self.ds, store, TaskStatus::with_token(& tx, task, cancelled.clone()));
+                    }
+                    Task::CompactTable{table : _, savepoint : _} => {
+                        return task_compact_table(& self.ds, TaskStatus::with_token(& tx, task, cancelled.clone()));
+                    }
+                    Task::UpdateGithubItems{id} => {
+                        return task_update_github_items(& self.ds, & self.github, id, TaskStatus::with_token(& tx, task, cancelled.clone()));
+                    }
+                    Task::UpdateUsers{id} => {
+                        return task_update_users(& self.ds, & self.github, id, TaskStatus::with_token(& tx, task, cancelled.clone()));
+                    }
+                    Task::MergeAliases{store} => {
+                        return task_merge_aliases(& self.ds, store, TaskStatus::with_token(& tx, task, cancelled.clone()));
                     }
                 }
             });
+            self.pool.lock().unwrap().running_tasks.remove(& task_name);
+            // write the headers of the mapping indices the task added to
+            self.ds.flush();
+            let cancelled = cancelled.load(atomic::Ordering::SeqCst);
+            if cancelled && self.is_stopping() {
+                if let Some(task) = interrupted {
+                    self.schedule(task);
+                }
+            }
             match result {
                 Ok(Ok(())) => {
+                    Metrics::add(& METRICS.tasks_done, 1);
+                    tx.send(TaskMessage::Done{ name : task_name }).unwrap();
+                    if let Some(id) = github_items {
+                        if ! cancelled && self.is_github_project(id) {
+                            self.schedule(Task::UpdateGithubItems{id});
+                        }
+                    }
+                },
+                Ok(Err(_)) if cancelled => {
+                    tx.send(TaskMessage::Info{ name : task_name.to_owned(), info : TaskStatus::CANCELLED.to_owned() }).unwrap();
                     tx.send(TaskMessage::Done{ name : task_name }).unwrap();
                 },
                 Ok(Err(cause)) => {
+                    Metrics::add(& METRICS.tasks_error, 1);
                     tx.send(TaskMessage::Error{ name : task_name, cause : format!("{}", cause).trim().to_owned() }).unwrap();
                 },
                 Err(cause) => {
+                    Metrics::add(& METRICS.tasks_error, 1);
                     tx.send(TaskMessage::Error{ name : task_name, cause : format!("PANIC: {:?}", cause) }).unwrap();
                 }
             }
@@ -191,6 +345,87 @@ impl Updater {
         self.pool.lock().unwrap().running_workers -= 1;
     }
 
+    /** Returns true if given project is a GitHub project that has not been deleted. 
+     */
+    fn is_github_project(& self, id : ProjectId) -> bool {
+        if let Some(ProjectUrl::GitHub{user_and_repo : _}) = self.ds.get_project(id) {
+            return ! self.ds.get_project_last_update(id).map(|x| x.is_tombstone()).unwrap_or(false);
+        }
+        return false;
+    }
+
+    /** Schedules the task created for each of the given projects (or all projects if the only argument is `all`) for which the filter holds. 
+     
+        Returns the number of scheduled tasks, or an error if any of the explicitly given projects is invalid, or does not pass the filter, in which case nothing is scheduled. 
+     */
+    fn schedule_project_tasks(& self, args : & [& str], filter : impl Fn(ProjectId) -> bool, task : impl Fn(ProjectId) -> Task) -> Result<usize, String> {
+        let ids : Vec<ProjectId> = if args == ["all"] {
+            (0..self.ds.num_projects()).map(|i| ProjectId::from(i as u64)).filter(|id| filter(*id)).collect()
+        } else {
+            let mut ids = Vec::new();
+            for x in args.iter() {
+                match x.parse::<u64>() {
+                    Ok(id) if (id as usize) < self.ds.num_projects() && filter(ProjectId::from(id)) => ids.push(ProjectId::from(id)),
+                    Ok(id) if (id as usize) < self.ds.num_projects() => return Err(format!("Task cannot be executed for project {}", id)),
+                    _ => return Err(format!("Invalid project id {}", x)),
+                }
+            }
+            ids
+        };
+        for id in ids.iter() {
+            self.schedule(task(*id));
+        }
+        return Ok(ids.len());
+    }
+
+    /** Obtains the metadata of given GitHub project together with the metadata of other GitHub projects waiting in the queue in a single GraphQL query. 
+     
+        The other projects are those to be updated next, i.e. the queued project updates with the highest priority. Does nothing if the project's metadata have already been prefetched, or if batching is disabled. Failures are only reported, the metadata of the projects are then requested individually by their updates. 
+     */
+    fn prefetch_metadata(& self, id : ProjectId, task : & TaskStatus) {
+        if SETTINGS.graphql_batch == 0 {
+            return;
+        }
+        match self.ds.get_project(id) {
+            Some(ProjectUrl::GitHub{user_and_repo}) if ! self.github.is_prefetched(& user_and_repo) => {
+                // the heap is iterated in arbitrary order, so the tasks to be executed next are popped and pushed back
+                let queued : Vec<ProjectId> = {
+                    let mut pool = self.pool.lock().unwrap();
+                    let mut next = Vec::new();
+                    while next.len() < SETTINGS.graphql_batch * 2 {
+                        match pool.queue.pop() {
+                            Some(task) => next.push(task),
+                            None => break,
+                        }
+                    }
+                    let result = next.iter()
+                        .filter_map(|x| if let Task::UpdateRepo{id, due_time : _} = x { Some(*id) } else { None })
+                        .collect();
+                    pool.queue.extend(next);
+                    result
+                };
+                let mut batch = vec!(user_and_repo);
+                for id in queued {
+                    if let Some(ProjectUrl::GitHub{user_and_repo}) = self.ds.get_project(id) {
+                        if ! self.github.is_prefetched(& user_and_repo) {
+                            batch.push(user_and_repo);
+                            if batch.len() == SETTINGS.graphql_batch {
+                                break;
+                            }
+                        }
+                    }
+                }
+                if batch.len() > 1 {
+                    task.info(format!("prefetching metadata of {} projects...", batch.len()));
+                    if let Err(e) = self.github.prefetch_repos(& batch, Some(task)) {
+                        task.info(format!("prefetching metadata failed: {}", e));
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+
     /** Returns the next project to be updated. 
      
         Returns None if the updater should stop and blocks if there are no avilable projects, or the updater should pause. 
@@ -219,12 +454,350 @@ impl Updater {
         return state.queue.pop();
     }
 
+    /** Registers the cancellation token of a task that is about to be executed and returns it. 
+     */
+    fn register_task(& self, name : & str) -> Arc<atomic::AtomicBool> {
+        let cancelled = Arc::new(atomic::AtomicBool::new(false));
+        self.pool.lock().unwrap().running_tasks.insert(name.to_owned(), cancelled.clone());
+        return cancelled;
+    }
+
+    /** Cancels the running task of given name and returns true, or returns false if there is no such task. 
+     
+        The task stops at its next checkpoint. A project id can be given instead of the name to cancel the update of the project. 
+     */
+    pub (crate) fn cancel_task(& self, name : & str) -> bool {
+        let pool = self.pool.lock().unwrap();
+        let token = match pool.running_tasks.get(name) {
+            Some(token) => Some(token),
+            None => name.parse::<u64>().ok().and_then(|id| pool.running_tasks.get(& Task::UpdateRepo{id : ProjectId::from(id), due_time : 0}.name())),
+        };
+        if let Some(token) = token {
+            token.store(true, atomic::Ordering::SeqCst);
+            return true;
+        }
+        return false;
+    }
+
     pub fn schedule(& self, task : Task) {
         let mut pool = self.pool.lock().unwrap();
         pool.queue.push(task);
         self.cv_workers.notify_one();
     }
 
+    /** Returns the progress of scheduling the projects of given substore update, i.e. the index of next project to check and number of projects scheduled so far. 
+     
+        If the update has not been resumed from the persisted queue, its progress is created and starts from the first project. 
+     */
+    pub (crate) fn substore_update_progress(& self, store : StoreKind, mode : UpdateMode) -> (usize, usize, Vec<StoreKind>) {
+        let existing = self.pool.lock().unwrap().substore_updates.iter().find(|x| x.store == store && x.mode == mode).cloned();
+        if let Some(progress) = existing.as_ref().filter(|x| ! x.group.is_empty()) {
+            return (progress.next, progress.scheduled, progress.group.clone());
+        }
+        // the update has not started yet, it may have been scheduled by the previous update of the round
+        let done = existing.map(|x| x.done).unwrap_or(Vec::new());
+        let group = self.substore_update_group(store, mode, & done);
+        let mut pool = self.pool.lock().unwrap();
+        pool.substore_updates.retain(|x| x.store != store || x.mode != mode);
+        pool.substore_updates.push(SubstoreUpdateProgress{store, mode, group : group.clone(), done, next : 0, scheduled : 0});
+        return (0, 0, group);
+    }
+
+    /** Returns the substores updated together with the given substore, starting with the given substore. 
+     
+        If there is a memory budget (see `--memory-budget`), any substores not yet updated in the current round, nor by other running updates, are added to the update as long as the estimated memory of their mappings fits in the budget, so that their projects are updated concurrently. Substores that do not fit are skipped so that smaller ones can still be added, they are updated later in the round. Without the budget, or when updating a single substore, or errors, only the given substore is updated. 
+     */
+    fn substore_update_group(& self, store : StoreKind, mode : UpdateMode, done : & Vec<StoreKind>) -> Vec<StoreKind> {
+        let mut group = vec!(store);
+        if let Some(budget) = SETTINGS.memory_budget {
+            if mode != UpdateMode::Single && mode != UpdateMode::Errors {
+                let mut memory = self.ds.substore(store).memory_estimate();
+                for substore in self.ds.substores_iter() {
+                    let kind = substore.prefix;
+                    if group.contains(& kind) || done.contains(& kind) || self.is_substore_updated(kind) {
+                        continue;
+                    }
+                    let required = substore.memory_estimate();
+                    if memory + required <= budget {
+                        memory += required;
+                        group.push(kind);
+                    }
+                }
+            }
+        }
+        return group;
+    }
+
+    /** Schedules the update of the first substore not yet updated in the current round of substore updates, if any. 
+     
+        The substores updated in the round so far are kept in the progress of the scheduled update so that they are not updated again in the same round, even if the update is resumed from the persisted queue. Continuous updates start a new round once all substores have been updated. 
+     */
+    pub (crate) fn schedule_next_substore_update(& self, mode : UpdateMode, mut done : Vec<StoreKind>) {
+        let mut next = self.ds.substores_iter().map(|x| x.prefix).find(|x| ! done.contains(x));
+        if next.is_none() && mode == UpdateMode::Continuous {
+            done.clear();
+            next = Some(StoreKind::from_number(0));
+        }
+        if let Some(store) = next {
+            let mut pool = self.pool.lock().unwrap();
+            pool.substore_updates.retain(|x| x.store != store || x.mode != mode);
+            pool.substore_updates.push(SubstoreUpdateProgress{store, mode, group : Vec::new(), done, next : 0, scheduled : 0});
+            pool.queue.push(Task::UpdateSubstore{store, mode});
+            self.cv_workers.notify_one();
+        }
+    }
+
+    /** Loads the given substores for an update. 
+     
+        If there is a memory budget, least recently used substores are dropped from memory first until the loaded substores fit in the budget. The substores to load are accounted for with the estimated memory of all their mapping values, other loaded substores with the measured memory of their cached values. Substores used by running substore updates are never dropped. 
+     */
+    pub (crate) fn load_substores(& self, group : & Vec<StoreKind>, task : & TaskStatus) {
+        {
+            let mut used = self.substores_used.lock().unwrap();
+            used.retain(|x| ! group.contains(x));
+            used.extend(group.iter().copied());
+        }
+        if let Some(budget) = SETTINGS.memory_budget {
+            let required : usize = group.iter().map(|x| self.ds.substore(*x).memory_estimate()).sum();
+            let mut loaded : usize = self.ds.substores_iter()
+                .filter(|x| x.is_loaded() && ! group.contains(& x.prefix))
+                .map(|x| x.memory_size())
+                .sum();
+            // substores loaded otherwise than by substore updates (such as by the load command) are considered least recently used
+            let used = self.substores_used.lock().unwrap().clone();
+            let mut lru : Vec<StoreKind> = self.ds.substores_iter().map(|x| x.prefix).filter(|x| ! used.contains(x)).collect();
+            lru.extend(used);
+            for store in lru {
+                if required + loaded <= budget {
+                    break;
+                }
+                let substore = self.ds.substore(store);
+                if ! substore.is_loaded() || group.contains(& store) || self.is_substore_updated(store) {
+                    continue;
+                }
+                task.info(format!("dropping substore {:?} to fit the memory budget", store));
+                loaded = loaded.saturating_sub(substore.memory_size());
+                substore.clear(task);
+            }
+        }
+        for store in group {
+            self.ds.substore(*store).load(task);
+        }
+    }
+
+    /** Returns true if given substore is used by any running substore update. 
+     */
+    fn is_substore_updated(& self, store : StoreKind) -> bool {
+        let pool = self.pool.lock().unwrap();
+        return pool.substore_updates.iter().any(|x| x.group.contains(& store));
+    }
+
+    /** Schedules the task, if any, for given substore update and updates the update's progress. 
+     
+        Both happen under the same lock so that the persisted queue and progress are always consistent. 
+     */
+    pub (crate) fn schedule_substore_project(& self, store : StoreKind, mode : UpdateMode, task : Option<Task>, next : usize, scheduled : usize) {
+        let mut pool = self.pool.lock().unwrap();
+        if let Some(progress) = pool.substore_updates.iter_mut().find(|x| x.store == store && x.mode == mode) {
+            progress.next = next;
+            progress.scheduled = scheduled;
+        }
+        if let Some(task) = task {
+            pool.queue.push(task);
+            self.cv_workers.notify_one();
+        }
+    }
+
+    /** Removes the progress of given substore update once the update finishes and returns the substores updated in the current round so far, including those of the finished update. 
+     */
+    pub (crate) fn finish_substore_update(& self, store : StoreKind, mode : UpdateMode) -> Vec<StoreKind> {
+        let mut pool = self.pool.lock().unwrap();
+        let mut done = Vec::new();
+        if let Some(progress) = pool.substore_updates.iter().find(|x| x.store == store && x.mode == mode) {
+            done.extend(progress.done.iter().copied());
+            done.extend(progress.group.iter().copied());
+        }
+        pool.substore_updates.retain(|x| x.store != store || x.mode != mode);
+        return done;
+    }
+
+    fn queue_path(& self) -> String {
+        return format!("{}/{}", self.ds.root_folder(), Updater::QUEUE_FILE);
+    }
+
+    /** Saves the pending tasks and the progress of substore updates in the datastore root so that they can be resumed when the updater starts next time. 
+     
+        Tasks that are being executed are not saved, with the exception of substore updates, which are resumed from their saved progress. Tasks interrupted by stopping the updater are scheduled again before the queue is saved. If there is nothing to save, the file is removed. 
+     */
+    pub (crate) fn save_queue(& self) -> Result<(), std::io::Error> {
+        let mut contents = String::new();
+        {
+            let pool = self.pool.lock().unwrap();
+            for progress in pool.substore_updates.iter() {
+                contents.push_str(& format!("{}\n", progress.to_line()));
+            }
+            for task in pool.queue.iter() {
+                contents.push_str(& format!("{}\n", task.to_line()));
+            }
+        }
+        let path = self.queue_path();
+        if contents.is_empty() {
+            if std::path::Path::new(& path).exists() {
+                std::fs::remove_file(& path)?;
+            }
+        } else {
+            // write to a temporary file first so that a crash while saving keeps the previous queue
+            let tmp = format!("{}.tmp", path);
+            std::fs::write(& tmp, contents)?;
+            std::fs::rename(& tmp, & path)?;
+        }
+        return Ok(());
+    }
+
+    /** Loads the queue persisted by a previous run of the updater and returns the number of resumed tasks. 
+     
+        Invalid lines, such as tasks saved by a newer version, are skipped and reported so that the rest of the queue is still resumed. 
+     */
+    fn load_queue(& self) -> Result<usize, std::io::Error> {
+        let path = self.queue_path();
+        if ! std::path::Path::new(& path).exists() {
+            return Ok(0);
+        }
+        let mut pool = self.pool.lock().unwrap();
+        for line in std::fs::read_to_string(& path)?.lines() {
+            if let Some(progress) = SubstoreUpdateProgress::from_line(line) {
+                // the substore update was running, schedule it again so that it continues from the saved progress
+                pool.queue.push(Task::UpdateSubstore{store : progress.store, mode : progress.mode});
+                pool.substore_updates.push(progress);
+            } else if let Some(task) = Task::from_line(line) {
+                pool.queue.push(task);
+            } else if ! line.is_empty() {
+                println!("Skipping invalid task {} in {}", line, path);
+            }
+        }
+        return Ok(pool.queue.len());
+    }
+
+    /** Periodically saves the queue while the updater is running so that it can be resumed after a crash. 
+     */
+    fn queue_saver(& self) {
+        let mut last_save = helpers::now();
+        while ! self.should_stop() {
+            std::thread::sleep(std::time::Duration::from_millis(1000));
+            if helpers::now() - last_save >= Updater::QUEUE_SAVE_INTERVAL {
+                self.save_queue().unwrap();
+                last_save = helpers::now();
+            }
+        }
+    }
+
+    /** Returns a summary of the pending tasks, the first few tasks to be executed and the progress of substore updates. 
+     */
+    fn queue_summary(& self) -> String {
+        let pool = self.pool.lock().unwrap();
+        let mut kinds = BTreeMap::<&'static str, usize>::new();
+        for task in pool.queue.iter() {
+            *kinds.entry(task.kind()).or_insert(0) += 1;
+        }
+        let mut result = format!("{} pending tasks", pool.queue.len());
+        for (kind, count) in kinds.iter() {
+            result.push_str(& format!(", {} {}", count, kind));
+        }
+        for progress in pool.substore_updates.iter() {
+            let stores = if progress.group.len() <= 1 { format!("{:?}", progress.store) } else { SubstoreUpdateProgress::stores_to_string(& progress.group) };
+            result.push_str(& format!("\nupdate {} {:?}: {} projects checked, {} scheduled", stores, progress.mode, progress.next, progress.scheduled));
+        }
+        let mut next = pool.queue.clone().into_sorted_vec();
+        next.reverse();
+        for task in next.iter().take(Updater::QUEUE_SUMMARY_TASKS) {
+            result.push_str(& format!("\n    {} ({})", task.name(), task.priority()));
+        }
+        return result;
+    }
+
+    /** Removes all pending tasks whose name starts with given prefix and returns their number. 
+     */
+    fn clear_queue(& self, prefix : & str) -> usize {
+        let mut pool = self.pool.lock().unwrap();
+        let before = pool.queue.len();
+        let tasks = std::mem::take(& mut pool.queue).into_vec();
+        pool.queue = tasks.into_iter().filter(|x| ! x.name().starts_with(prefix)).collect();
+        return before - pool.queue.len();
+    }
+
+    /** Changes the priority, i.e. the due time, of the pending update of given project. 
+     
+        Returns false if there is no pending update of the project. 
+     */
+    fn prioritize(& self, id : ProjectId, priority : i64) -> bool {
+        let mut pool = self.pool.lock().unwrap();
+        let mut found = false;
+        let tasks = std::mem::take(& mut pool.queue).into_vec();
+        pool.queue = tasks.into_iter().map(|x| {
+            match x {
+                Task::UpdateRepo{id : task_id, due_time : _} if task_id == id => {
+                    found = true;
+                    return Task::UpdateRepo{id, due_time : priority};
+                },
+                _ => return x,
+            }
+        }).collect();
+        return found;
+    }
+
+    /** Registers a new listener that will receive copies of all task messages. 
+     
+        The listener is removed once its receiving end is dropped. 
+     */
+    pub (crate) fn add_listener(& self) -> Rx {
+        let (tx, rx) = crossbeam_channel::unbounded::<TaskMessage>();
+        self.listeners.lock().unwrap().push(tx);
+        return rx;
+    }
+
+    /** Forwards the task message to all listeners. 
+     */
+    fn broadcast(& self, msg : & TaskMessage) {
+        let mut listeners = self.listeners.lock().unwrap();
+        if ! listeners.is_empty() {
+            listeners.retain(|tx| tx.send(msg.clone()).is_ok());
+        }
+    }
+
+    /** Returns the status of the updater as JSON. 
+     */
+    pub (crate) fn status_json(& self) -> json::JsonValue {
+        let mut result = json::JsonValue::new_object();
+        result["version"] = Datastore::VERSION.into();
+        result["policy"] = self.policy.name().into();
+        {
+            let threads = self.pool.lock().unwrap();
+            result["status"] = threads.status().into();
+            result["running"] = threads.running_workers.into();
+            result["idle"] = threads.idle_workers.into();
+            result["paused"] = threads.paused_workers.into();
+            result["queue"] = threads.queue.len().into();
+        }
+        result["projects"] = self.ds.num_projects().into();
+        let mut loaded = json::JsonValue::new_array();
+        let mut reports = vec![self.ds.project_urls_memory_report()];
+        reports.extend(self.ds.substores_iter().map(|substore| substore.memory_report()));
+        for x in reports.into_iter().filter(|x| ! x.is_empty()) {
+            loaded.push(x).unwrap();
+        }
+        result["loaded"] = loaded;
+        result["tokens"] = self.github.tokens_status();
+        return result;
+    }
+
+    /** Returns true if the updater has been asked to stop. 
+     
+        Long running tasks should check this periodically and terminate early so that the updater can stop. 
+     */
+    pub fn is_stopping(& self) -> bool {
+        return self.pool.lock().unwrap().state == State::Stopped;
+    }
+
     /** Returns true if the non-worker thread should stop immediately, false otherwise. 
      
         Non worker threads are required to stop immediately after al worker threads are done. 
@@ -242,54 +815,9 @@ impl Updater {
             // see how many messages are there and process them, otherwise we can just keep processing messages without ever printing anything 
             let mut msgs = rx.len();
             while msgs > 0 {
-                match rx.recv() {
-                    Ok(TaskMessage::Start{name}) => {
-                        assert!(rinfo.tasks.contains_key(& name) == false, "Task already exists");
-                        rinfo.tasks.insert(name, TaskInfo::new());
-                    },
-                    Ok(TaskMessage::Done{name}) => {
-                        assert!(rinfo.tasks.contains_key(& name) == true, "Task does not exist");
-                        let mut task = rinfo.tasks.remove(& name).unwrap();
-                        task.end_time = helpers::now();
-                        rinfo.done.push((name, task));
-                        rinfo.tick_tasks_done += 1;
-                    },
-                    Ok(TaskMessage::Error{name, cause}) => {
-                        assert!(rinfo.tasks.contains_key(& name) == true, "Task does not exist");
-                        let mut task = rinfo.tasks.remove(& name).unwrap();
-                        task.end_time = helpers::now();
-                        rinfo.errors.push((name, task, cause));
-                        rinfo.tick_tasks_error += 1;
-                    },
-                    Ok(TaskMessage::Progress{name, progress, max}) => {
-                        assert!(rinfo.tasks.contains_key(& name) == true, "Task does not exist");
-                        let task = rinfo.tasks.get_mut(& name).unwrap();    
-                        task.ping = 0;
-                        task.progress = progress;
-                        task.progress_max = max;
-                    },
-                    Ok(TaskMessage::Info{name, info}) => {
-                        assert!(rinfo.tasks.contains_key(& name) == true, "Task does not exist");
-                        let task = rinfo.tasks.get_mut(& name).unwrap();    
-                        task.ping = 0;
-                        task.info = info;
-                    },
-                    Ok(TaskMessage::Extra{name, extra}) => {
-                        assert!(rinfo.tasks.contains_key(& name) == true, "Task does not exist");
-                        let task = rinfo.tasks.get_mut(& name).unwrap();    
-                        task.ping = 0;
-                        task.extra = extra;
-                    },
-                    Ok(TaskMessage::Color{name, color}) => {
-                        assert!(rinfo.tasks.contains_key(& name) == true, "Task does not exist");
-                        let task = rinfo.tasks.get_mut(& name).unwrap();    
-                        task.ping = 0;
-                        task.color = color;
-                    },
-                    Err(_) => {
-                        panic!("Oh noez, can't receive stuff");
-                    }
-                }
+                let msg = rx.recv().expect("Oh noez, can't receive stuff");
+                self.broadcast(& msg);
+                rinfo.update(msg);
                 msgs -= 1;
             }
             // now that the messages have been processed, redraw the status information
@@ -302,6 +830,68 @@ impl Updater {
         }
     }
 
+    /** Reports the task messages as JSON lines to given log file instead of the terminal. 
+     
+        All task messages except progress and color updates are logged together with periodic summaries of the updater's status. The initial command, if any, is executed first and its result is logged too. 
+     */
+    fn headless_reporter(& self, rx : crossbeam_channel::Receiver<TaskMessage>, path : & str, initial_command : String) {
+        let mut log = LogFile::open(path).expect(& format!("Unable to open log file {}", path));
+        let mut rinfo = ReporterInfo::new();
+        if ! initial_command.is_empty() {
+            let mut entry = json::JsonValue::new_object();
+            entry["event"] = "command".into();
+            entry["command"] = initial_command.as_str().into();
+            match self.process_command(initial_command) {
+                Ok(output) => entry["output"] = output.into(),
+                Err(error) => entry["error"] = error.into(),
+            }
+            entry["time"] = helpers::now().into();
+            log.write(& entry).unwrap();
+        }
+        let mut last_summary = helpers::now();
+        while ! self.should_stop() {
+            let mut msgs = rx.len();
+            while msgs > 0 {
+                let msg = rx.recv().expect("Oh noez, can't receive stuff");
+                self.broadcast(& msg);
+                match msg {
+                    TaskMessage::Progress{..} | TaskMessage::Color{..} => {},
+                    _ => log.write(& msg.to_json()).unwrap(),
+                }
+                rinfo.update(msg);
+                msgs -= 1;
+            }
+            let now = helpers::now();
+            if now - last_summary >= Updater::SUMMARY_INTERVAL {
+                log.write(& self.summary_json(& rinfo)).unwrap();
+                last_summary = now;
+            }
+            rinfo.tick();
+            std::thread::sleep(std::time::Duration::from_millis(1000));
+        }
+        log.write(& self.summary_json(& rinfo)).unwrap();
+    }
+
+    /** Returns the summary of the updater's status and finished tasks as JSON. 
+     */
+    fn summary_json(& self, info : & ReporterInfo) -> json::JsonValue {
+        let mut result = json::JsonValue::new_object();
+        result["event"] = "summary".into();
+        result["time"] = helpers::now().into();
+        for (key, value) in self.status_json().entries() {
+            result[key] = value.clone();
+        }
+        result["uptime"] = (helpers::now() - info.start_time).into();
+        result["active"] = info.tasks.len().into();
+        result["done"] = (info.total_tasks_done + info.tick_tasks_done).into();
+        result["errors"] = (info.total_tasks_error + info.tick_tasks_error).into();
+        let (pid, mem, cpu) = helpers::process_resources();
+        result["pid"] = pid.into();
+        result["cpu"] = cpu.into();
+        result["mem"] = mem.into();
+        return result;
+    }
+
     fn num_projects(& self) -> usize {
         return self.ds.projects.lock().unwrap().len();
     }
@@ -344,6 +934,7 @@ impl Updater {
             cpu,
             mem,
         );
+        println!("  Github: [ {} ]\x1b[K", self.github.tokens_summary());
 
         // tasks summary
         print!("\x1b[6H\x1b[104m");
@@ -398,7 +989,7 @@ impl Updater {
      */
     fn controller(& self, initial_command : String) {
         if ! initial_command.is_empty() {
-            self.process_command(initial_command);
+            self.execute_command(initial_command);
         } else {
             self.display_prompt("ready...");
         }
@@ -412,8 +1003,12 @@ impl Updater {
             }
             let mut command = String::new();
             match std::io::stdin().read_line(& mut command) {
+                // stdin has been closed (such as when running as a service), the updater can still be controlled remotely
+                Ok(0) => {
+                    break;
+                },
                 Ok(_) => {
-                    self.process_command(command);
+                    self.execute_command(command);
                 },
                 Err(e) => {
                     self.display_prompt(& format!("Unknown error: {:?}", e));
@@ -423,6 +1018,15 @@ impl Updater {
         self.display_prompt("Controller thread terminated. Command interface not available");
     }
 
+    /** Executes given command and displays its output. 
+     */
+    fn execute_command(& self, command : String) {
+        match self.process_command(command) {
+            Ok(output) => self.display_prompt(output),
+            Err(error) => self.display_error(error),
+        }
+    }
+
     fn display_prompt<T: Into<String>>(& self, command_output : T) {
         let _g = self.cout_lock.lock().unwrap();
         print!("\x1b[4;H\x1b[0m > \x1b[K\n");  
@@ -435,7 +1039,11 @@ impl Updater {
         self.display_prompt(& format!("ERROR: {}", error.into()));
     }
 
-    fn process_command(& self, command : String) {
+    /** Executes given command and returns its output, or an error. 
+     
+        The commands are shared by the interactive controller and the remote control (see `remote_control.rs`). 
+     */
+    pub (crate) fn process_command(& self, command : String) -> Result<String, String> {
         let cmd : Vec<&str> = command.trim().split(" ").collect();
         match cmd[0] {
             "pause" => {
@@ -444,15 +1052,32 @@ impl Updater {
                     threads.state = State::Paused;
                     self.cv_workers.notify_all();
                 }
-                self.display_prompt("Pausing threads...");
+                return Ok("Pausing threads...".to_owned());
             },
             "stop" => {
                 {
                     let mut threads = self.pool.lock().unwrap();
                     threads.state = State::Stopped;
+                    // cancel the running tasks so that the workers stop at their next checkpoints
+                    for token in threads.running_tasks.values() {
+                        token.store(true, atomic::Ordering::SeqCst);
+                    }
                     self.cv_workers.notify_all();
                 }
-                self.display_prompt("Stopping threads...");
+                return Ok("Stopping threads...".to_owned());
+            },
+            /* Cancels a running task given its name, or the update of a project given its id. 
+             */
+            "cancel" => {
+                if cmd.len() < 2 {
+                    return Err("No task to cancel specified".to_owned());
+                }
+                let name = cmd[1..].join(" ");
+                if self.cancel_task(& name) {
+                    return Ok(format!("Cancelling task {}...", name));
+                } else {
+                    return Err(format!("No running task {}", name));
+                }
             },
             "run" => {
                 {
@@ -460,121 +1085,238 @@ impl Updater {
                     threads.state = State::Running;
                     self.cv_workers.notify_all();
                 }
-                self.display_prompt("Resuming worker threads...");
+                return Ok("Resuming worker threads...".to_owned());
             }, 
             /* Updates project belonging to the given substore . 
              */
             "update" => {
                 if cmd.len() != 2 {
-                    self.display_error("No store to update specified");
+                    return Err("No store to update specified".to_owned());
                 } else if let Some(kind) = StoreKind::from_string(cmd[1]) {
                     self.schedule(Task::UpdateSubstore{store : kind, mode : UpdateMode::Single});
-                    self.display_prompt(format!("Updating substore {:?}, see task progress...", kind));
+                    return Ok(format!("Updating substore {:?}, see task progress...", kind));
                 } else {
-                    self.display_error(format!("Unknown store kind {}", cmd[1]));
+                    return Err(format!("Unknown store kind {}", cmd[1]));
                 }
             },
             /* Updates all projects once substore by substore. 
              */
             "updateall" => {
                 if cmd.len() != 1 {
-                    self.display_error("Invalid arguments");
+                    return Err("Invalid arguments".to_owned());
                 } else {
                     self.schedule(Task::UpdateSubstore{store : StoreKind::from_number(0), mode : UpdateMode::All});
-                    self.display_prompt("Updating all substores , see task progress...");
+                    return Ok("Updating all substores , see task progress...".to_owned());
                 }
             },
             /* Retries projects that were marked as errors. 
              */
             "updateerrors" => {
                 if cmd.len() != 1 {
-                    self.display_error("Invalid arguments");
+                    return Err("Invalid arguments".to_owned());
                 } else {
                     self.schedule(Task::UpdateSubstore{store : StoreKind::from_number(0), mode : UpdateMode::Errors});
-                    self.display_prompt("Checking all errors , see task progress...");
+                    return Ok("Checking all errors , see task progress...".to_owned());
                 }
 
             },
+            /* Updates projects that exceeded the limits of a single project update, store by store, with the limits raised (see `--oversized-limits`). 
+             */
+            "updateoversized" => {
+                if cmd.len() != 1 {
+                    return Err("Invalid arguments".to_owned());
+                } else {
+                    self.schedule(Task::UpdateSubstore{store : StoreKind::from_number(0), mode : UpdateMode::Oversized});
+                    return Ok("Updating oversized projects in all substores, see task progress...".to_owned());
+                }
+            },
             /* Continuously updates all projects store by store
              */
             "updatecontinuous" => {
                 if cmd.len() != 1 {
-                    self.display_error("Invalid arguments");
+                    return Err("Invalid arguments".to_owned());
                 } else {
                     self.schedule(Task::UpdateSubstore{store : StoreKind::from_number(0), mode : UpdateMode::Continuous});
-                    self.display_prompt("Updating all substores , see task progress...");
+                    return Ok("Updating all substores , see task progress...".to_owned());
                 }
             },
             /* Adds given project url, or projects from given csv file. 
              */
             "add" => {
                 if cmd.len() != 2 {
-                    self.display_error("Specify single project url or csv file to load the projects from");
+                    return Err("Specify single project url or csv file to load the projects from".to_owned());
                 } else {
-                    self.display_prompt("Adding projects to datastore, see task progress...");
                     self.schedule(Task::AddProjects{ source : cmd[1].to_owned() });
+                    return Ok("Adding projects to datastore, see task progress...".to_owned());
                 }
             },
             /* Loads given substore in memory. 
              */
             "load" => {
                 if cmd.len() != 2 {
-                    self.display_error("No store to load specified");
+                    return Err("No store to load specified".to_owned());
                 } else if let Some(kind) = StoreKind::from_string(cmd[1]) {
                     self.schedule(Task::LoadSubstore{store : kind});
-                    self.display_prompt(format!("Loading substore {:?}, see task progress...", kind));
+                    return Ok(format!("Loading substore {:?}, see task progress...", kind));
                 } else {
-                    self.display_error(format!("Unknown store kind {}", cmd[1]));
+                    return Err(format!("Unknown store kind {}", cmd[1]));
                 }
             },
             "drop" => {
                 if cmd.len() != 2 {
-                    self.display_error("No store to drop specified");
+                    return Err("No store to drop specified".to_owned());
                 } else if let Some(kind) = StoreKind::from_string(cmd[1]) {
                     self.schedule(Task::DropSubstore{store : kind});
-                    self.display_prompt(format!("Dropping substore {:?}, see task progress...", kind));
+                    return Ok(format!("Dropping substore {:?}, see task progress...", kind));
                 } else {
-                    self.display_error(format!("Unknown store kind {}", cmd[1]));
+                    return Err(format!("Unknown store kind {}", cmd[1]));
                 }
             },
             "loadall" => {
                 for kind in SplitKindIter::<StoreKind>::new() {
-                    self.display_prompt("Loading all substores, see task progress...");
                     self.schedule(Task::LoadSubstore{store : kind});
                 }
+                return Ok("Loading all substores, see task progress...".to_owned());
             }
             "verify" => {
                 if cmd.len() != 2 {
-                    self.display_error("No store to verify specified");
+                    return Err("No store to verify specified".to_owned());
                 } else if let Some(kind) = StoreKind::from_string(cmd[1]) {
                     self.schedule(Task::VerifySubstore{store : kind, mode : UpdateMode::Single});
-                    self.display_prompt(format!("Verifying substore {:?}, see task progress...", kind));
+                    return Ok(format!("Verifying substore {:?}, see task progress...", kind));
                 } else {
-                    self.display_error(format!("Unknown store kind {}", cmd[1]));
+                    return Err(format!("Unknown store kind {}", cmd[1]));
                 }
             },
             "verifyall" => {
                 if cmd.len() != 1 {
-                    self.display_error("Invalid arguments");
+                    return Err("Invalid arguments".to_owned());
                 } else {
                     self.schedule(Task::VerifySubstore{store : StoreKind::from_number(0), mode : UpdateMode::All});
-                    self.display_prompt("Verifying all substores, see task progress...");
+                    return Ok("Verifying all substores, see task progress...".to_owned());
                 }
             },
             "verifyds" => {
                 if cmd.len() != 1 {
-                    self.display_error("Invalid arguments");
+                    return Err("Invalid arguments".to_owned());
                 } else {
                     self.schedule(Task::VerifyDatastore{});
-                    self.display_prompt("Verifying main datastore, see task progress...");
+                    return Ok("Verifying main datastore, see task progress...".to_owned());
                 }
             },
             "create-savepoint" => {
                 if cmd.len() != 2 {
-                    self.display_error("Invalid arguments");
+                    return Err("Invalid arguments".to_owned());
                 } else {
                     self.schedule(Task::CreateSavepoint{name : cmd[1].to_owned()});
-                    self.display_prompt("Creating savepoint, see task progress...");
+                    return Ok("Creating savepoint, see task progress...".to_owned());
+                }
+            },
+            /* Migrates given project's data to the specified substore. 
+             */
+            "migrate" => {
+                if cmd.len() != 3 {
+                    return Err("Specify project id and the substore to migrate to".to_owned());
+                } else if let Ok(id) = cmd[1].parse::<u64>() {
+                    if id as usize >= self.ds.num_projects() {
+                        return Err(format!("Unknown project id {}", id));
+                    } else if let Some(kind) = StoreKind::from_string(cmd[2]) {
+                        self.schedule(Task::MigrateProject{id : ProjectId::from(id), store : kind});
+                        return Ok(format!("Migrating project {} to substore {:?}, see task progress...", id, kind));
+                    } else {
+                        return Err(format!("Unknown store kind {}", cmd[2]));
+                    }
+                } else {
+                    return Err(format!("Invalid project id {}", cmd[1]));
+                }
+            },
+            /* Updates issues, pull requests and releases of given GitHub projects, or of all GitHub projects. 
+             */
+            "github" => {
+                if cmd.len() < 2 {
+                    return Err("Specify project ids, or all".to_owned());
+                }
+                let scheduled = self.schedule_project_tasks(& cmd[1..], |id| self.is_github_project(id), |id| Task::UpdateGithubItems{id})?;
+                return Ok(format!("Updating GitHub items of {} projects, see task progress...", scheduled));
+            },
+            /* Resolves authors of given GitHub projects, or of all analyzed GitHub projects, to GitHub accounts and updates their profiles. 
+             */
+            "users" => {
+                if cmd.len() < 2 {
+                    return Err("Specify project ids, or all".to_owned());
+                }
+                let scheduled = self.schedule_project_tasks(& cmd[1..], |id| self.is_github_project(id) && self.ds.get_project_substore(id) != StoreKind::Unspecified, |id| Task::UpdateUsers{id})?;
+                return Ok(format!("Updating GitHub users of {} projects, see task progress...", scheduled));
+            },
+            /* Merges the aliases of the users of given substore into canonical identities. 
+             */
+            "aliases" => {
+                if cmd.len() != 2 {
+                    return Err("No store to merge aliases in specified".to_owned());
+                } else if let Some(kind) = StoreKind::from_string(cmd[1]) {
+                    self.schedule(Task::MergeAliases{store : kind});
+                    return Ok(format!("Merging user aliases in substore {:?}, see task progress...", kind));
+                } else {
+                    return Err(format!("Unknown store kind {}", cmd[1]));
+                }
+            },
+            /* Garbage collects given substore, removing all data not reachable from its projects. 
+             */
+            "gc" => {
+                if cmd.len() != 2 {
+                    return Err("No store to garbage collect specified".to_owned());
+                } else if let Some(kind) = StoreKind::from_string(cmd[1]) {
+                    self.schedule(Task::GarbageCollect{store : kind});
+                    return Ok(format!("Garbage collecting substore {:?}, see task progress...", kind));
+                } else {
+                    return Err(format!("Unknown store kind {}", cmd[1]));
+                }
+            },
+            /* Compacts given table, keeping either only the latest records, or history since given savepoint. 
+             */
+            "compact" => {
+                if cmd.len() != 2 && cmd.len() != 3 {
+                    return Err("Specify table to compact and optionally a savepoint since which to keep history".to_owned());
+                } else {
+                    self.schedule(Task::CompactTable{table : cmd[1].to_owned(), savepoint : cmd.get(2).map(|x| x.to_string())});
+                    return Ok(format!("Compacting table {}, see task progress...", cmd[1]));
+                }
+            },
+            /* Inspects, clears, or reprioritizes the pending tasks. 
+             */
+            "queue" => {
+                match cmd.get(1) {
+                    None => {
+                        return Ok(self.queue_summary());
+                    },
+                    Some(&"save") => {
+                        return self.save_queue().map(|_| "Queue saved".to_owned()).map_err(|e| format!("{}", e));
+                    },
+                    Some(&"clear") => {
+                        let removed = self.clear_queue(& cmd[2..].join(" "));
+                        return Ok(format!("{} tasks removed", removed));
+                    },
+                    Some(&"prioritize") => {
+                        if cmd.len() != 3 && cmd.len() != 4 {
+                            return Err("Specify project id and optionally its priority".to_owned());
+                        } 
+                        let priority = match cmd.get(3) {
+                            Some(x) => x.parse::<i64>().map_err(|_| format!("Invalid priority {}", x))?,
+                            None => Updater::NEVER,
+                        };
+                        if let Ok(id) = cmd[2].parse::<u64>() {
+                            if self.prioritize(ProjectId::from(id), priority) {
+                                return Ok(format!("Project {} priority set to {}", id, priority));
+                            } else {
+                                return Err(format!("No pending update of project {}", id));
+                            }
+                        } else {
+                            return Err(format!("Invalid project id {}", cmd[2]));
+                        }
+                    },
+                    Some(x) => {
+                        return Err(format!("Unknown queue command {}", x));
+                    }
                 }
             },
             // debug stuffz
@@ -591,7 +1333,7 @@ impl Updater {
             }
 
             _ => {
-                self.display_error(& format!("Unknown command: {}", command));
+                return Err(format!("Unknown command: {}", command));
             }
         }
     }
@@ -634,11 +1376,102 @@ pub enum UpdateMode {
     All,
     Continuous,
     Errors,
+    /** Updates only projects whose last update exceeded the limits of a single project update. 
+     */
+    Oversized,
+}
+
+impl UpdateMode {
+    pub fn from_string(name : & str) -> Option<UpdateMode> {
+        match name {
+            "Single" => Some(UpdateMode::Single),
+            "All" => Some(UpdateMode::All),
+            "Continuous" => Some(UpdateMode::Continuous),
+            "Errors" => Some(UpdateMode::Errors),
+            "Oversized" => Some(UpdateMode::Oversized),
+            _ => None,
+        }
+    }
+}
+
+/** Progress of scheduling the projects of a running substore update. 
+ 
+    Persisted together with the queue so that the update can continue where it stopped without scheduling the same projects again. 
+ */
+#[derive(Debug, Clone)]
+pub (crate) struct SubstoreUpdateProgress {
+    pub (crate) store : StoreKind,
+    pub (crate) mode : UpdateMode,
+    /** The substores updated together, starting with the store, empty if the update has not started yet (see `Updater::substore_update_group`). 
+     */
+    pub (crate) group : Vec<StoreKind>,
+    /** The substores already updated in the current round of substore updates (see `Updater::schedule_next_substore_update`). 
+     */
+    pub (crate) done : Vec<StoreKind>,
+    /** Index of the next project to check. 
+     */
+    pub (crate) next : usize,
+    /** Number of projects scheduled so far. 
+     */
+    pub (crate) scheduled : usize,
 }
 
-#[derive(Eq, PartialEq, Debug)] 
+impl SubstoreUpdateProgress {
+    /** Serializes the progress into a single line of the persisted queue. 
+     
+        The substores updated together and those already updated are comma separated lists, `-` if empty. 
+     */
+    fn to_line(& self) -> String {
+        return format!("progress {:?} {:?} {} {} {} {}", self.store, self.mode, self.next, self.scheduled, Self::stores_to_string(& self.group), Self::stores_to_string(& self.done));
+    }
+
+    fn from_line(line : & str) -> Option<SubstoreUpdateProgress> {
+        let parts : Vec<&str> = line.split(" ").collect();
+        if parts.len() < 5 || parts.len() > 7 || parts[0] != "progress" {
+            return None;
+        }
+        let store = StoreKind::from_string(parts[1])?;
+        let group = match parts.len() {
+            7 => Self::stores_from_string(parts[5])?,
+            // queues saved by older versions update a single substore, or a range of substores up to the given last one
+            6 => (store.to_number() ..= StoreKind::from_string(parts[5])?.to_number()).map(StoreKind::from_number).collect(),
+            _ => vec!(store),
+        };
+        return Some(SubstoreUpdateProgress{
+            store,
+            mode : UpdateMode::from_string(parts[2])?,
+            group,
+            done : match parts.get(6) {
+                Some(done) => Self::stores_from_string(done)?,
+                None => Vec::new(),
+            },
+            next : parts[3].parse().ok()?,
+            scheduled : parts[4].parse().ok()?,
+        });
+    }
+
+    fn stores_to_string(stores : & Vec<StoreKind>) -> String {
+        if stores.is_empty() {
+            return "-".to_owned();
+        }
+        return stores.iter().map(|x| format!("{:?}", x)).collect::<Vec<String>>().join(",");
+    }
+
+    fn stores_from_string(stores : & str) -> Option<Vec<StoreKind>> {
+        if stores == "-" {
+            return Some(Vec::new());
+        }
+        return stores.split(",").map(StoreKind::from_string).collect();
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)] 
 pub enum Task {
-    UpdateRepo{id : ProjectId, last_update_time : i64},
+    /** Updates given project. 
+     
+        The due time, as determined by the scheduling policy, is the task's priority. 
+     */
+    UpdateRepo{id : ProjectId, due_time : i64},
     AddProjects{source : String},
     /** Updates projects that belong to the specific substore. 
      
@@ -654,19 +1487,37 @@ pub enum Task {
     VerifySubstore{store : StoreKind, mode : UpdateMode},
     VerifyDatastore{},
     CreateSavepoint{name : String},
+    /** Migrates the project's data from its current substore to the given one. 
+     */
+    MigrateProject{id : ProjectId, store : StoreKind},
+    /** Removes data unreachable from any project from the given substore. 
+     */
+    GarbageCollect{store : StoreKind},
+    /** Compacts given table, keeping history since the savepoint, if specified. 
+     */
+    CompactTable{table : String, savepoint : Option<String>},
+    /** Updates issues, pull requests and releases of given GitHub project. 
+     */
+    UpdateGithubItems{id : ProjectId},
+    /** Resolves the authors of given GitHub project's commits to GitHub accounts and updates their profiles. 
+     */
+    UpdateUsers{id : ProjectId},
+    /** Merges the aliases of the users of given substore into canonical identities. 
+     */
+    MergeAliases{store : StoreKind},
 }
 
 impl Task {
     pub fn priority(& self) -> i64 {
         match self {
-            Task::UpdateRepo{last_update_time, id : _} => *last_update_time, 
+            Task::UpdateRepo{due_time, id : _} => *due_time, 
             _ => -1,
         }
     }
 
     pub fn name(& self) -> String {
         match self {
-            Task::UpdateRepo{id, last_update_time : _} => format!("{:?}", id),
+            Task::UpdateRepo{id, due_time : _} => format!("{:?}", id),
             Task::AddProjects{source : _ } => "add".to_owned(), 
             Task::UpdateSubstore{store, mode} => format!("update {:?} {:?}", store, mode),
             Task::LoadSubstore{store} => format!("load {:?}", store),
@@ -674,8 +1525,110 @@ impl Task {
             Task::VerifySubstore{store, mode} => format!("verify {:?} {:?}", store, mode),
             Task::VerifyDatastore{} => format!("verify datastore"),
             Task::CreateSavepoint{name} => format!("create savepoint {}", name),
+            Task::MigrateProject{id, store} => format!("migrate {:?} {:?}", id, store),
+            Task::GarbageCollect{store} => format!("gc {:?}", store),
+            Task::CompactTable{table, savepoint : _} => format!("compact {}", table),
+            Task::UpdateGithubItems{id} => format!("github {:?}", id),
+            Task::UpdateUsers{id} => format!("users {:?}", id),
+            Task::MergeAliases{store} => format!("aliases {:?}", store),
         }
     }
+
+    /** Returns the kind of the task used in the queue summary. 
+     */
+    pub fn kind(& self) -> &'static str {
+        match self {
+            Task::UpdateRepo{id : _, due_time : _} => "repo",
+            Task::AddProjects{source : _} => "add",
+            Task::UpdateSubstore{store : _, mode : _} => "update",
+            Task::LoadSubstore{store : _} => "load",
+            Task::DropSubstore{store : _} => "drop",
+            Task::VerifySubstore{store : _, mode : _} => "verify",
+            Task::VerifyDatastore{} => "verifyds",
+            Task::CreateSavepoint{name : _} => "savepoint",
+            Task::MigrateProject{id : _, store : _} => "migrate",
+            Task::GarbageCollect{store : _} => "gc",
+            Task::CompactTable{table : _, savepoint : _} => "compact",
+            Task::UpdateGithubItems{id : _} => "github",
+            Task::UpdateUsers{id : _} => "users",
+            Task::MergeAliases{store : _} => "aliases",
+        }
+    }
+
+    /** Serializes the task into a single line of the persisted queue. 
+     
+        The line starts with the task kind followed by the task's arguments separated by spaces. Each task kind has a fixed number of arguments and the last one is the rest of the line, so that it may contain spaces (such as source paths of added projects, or savepoint names). 
+     */
+    pub fn to_line(& self) -> String {
+        let args = match self {
+            Task::UpdateRepo{id, due_time} => format!("{} {}", id, due_time),
+            Task::AddProjects{source} => source.to_owned(),
+            Task::UpdateSubstore{store, mode} => format!("{:?} {:?}", store, mode),
+            Task::LoadSubstore{store} => format!("{:?}", store),
+            Task::DropSubstore{store} => format!("{:?}", store),
+            Task::VerifySubstore{store, mode} => format!("{:?} {:?}", store, mode),
+            Task::VerifyDatastore{} => String::new(),
+            Task::CreateSavepoint{name} => name.to_owned(),
+            Task::MigrateProject{id, store} => format!("{} {:?}", id, store),
+            Task::GarbageCollect{store} => format!("{:?}", store),
+            Task::CompactTable{table, savepoint} => match savepoint {
+                Some(savepoint) => format!("{} {}", table, savepoint),
+                None => table.to_owned(),
+            },
+            Task::UpdateGithubItems{id} => format!("{}", id),
+            Task::UpdateUsers{id} => format!("{}", id),
+            Task::MergeAliases{store} => format!("{:?}", store),
+        };
+        if let Task::VerifyDatastore{} = self {
+            return self.kind().to_owned();
+        }
+        return format!("{} {}", self.kind(), args);
+    }
+
+    /** Deserializes the task from a line of the persisted queue, returns None if the line is not a valid task. 
+     */
+    pub fn from_line(line : & str) -> Option<Task> {
+        let (kind, args) = match line.split_once(" ") {
+            Some((kind, args)) => (kind, Some(args)),
+            None => (line, None),
+        };
+        // tasks with two arguments, the second of which is the rest of the line
+        let pair = || args.and_then(|x| x.split_once(" "));
+        let task = match kind {
+            "repo" => {
+                let (id, due_time) = pair()?;
+                Task::UpdateRepo{id : ProjectId::from(id.parse::<u64>().ok()?), due_time : due_time.parse().ok()?}
+            },
+            "add" => Task::AddProjects{source : args?.to_owned()},
+            "update" => {
+                let (store, mode) = pair()?;
+                Task::UpdateSubstore{store : StoreKind::from_string(store)?, mode : UpdateMode::from_string(mode)?}
+            },
+            "load" => Task::LoadSubstore{store : StoreKind::from_string(args?)?},
+            "drop" => Task::DropSubstore{store : StoreKind::from_string(args?)?},
+            "verify" => {
+                let (store, mode) = pair()?;
+                Task::VerifySubstore{store : StoreKind::from_string(store)?, mode : UpdateMode::from_string(mode)?}
+            },
+            "verifyds" if args.is_none() => Task::VerifyDatastore{},
+            "savepoint" => Task::CreateSavepoint{name : args?.to_owned()},
+            "migrate" => {
+                let (id, store) = pair()?;
+                Task::MigrateProject{id : ProjectId::from(id.parse::<u64>().ok()?), store : StoreKind::from_string(store)?}
+            },
+            "gc" => Task::GarbageCollect{store : StoreKind::from_string(args?)?},
+            // table names have no spaces, so the savepoint, if any, is the rest of the line
+            "compact" => match pair() {
+                Some((table, savepoint)) => Task::CompactTable{table : table.to_owned(), savepoint : Some(savepoint.to_owned())},
+                None => Task::CompactTable{table : args?.to_owned(), savepoint : None},
+            },
+            "github" => Task::UpdateGithubItems{id : ProjectId::from(args?.parse::<u64>().ok()?)},
+            "users" => Task::UpdateUsers{id : ProjectId::from(args?.parse::<u64>().ok()?)},
+            "aliases" => Task::MergeAliases{store : StoreKind::from_string(args?)?},
+            _ => return None,
+        };
+        return Some(task);
+    }
 }
 
 impl Ord for Task {
@@ -704,6 +1657,12 @@ pub (crate) struct Pool {
     pub (crate) idle_workers : u64,
     pub (crate) paused_workers : u64,
     pub (crate) queue : BinaryHeap<Task>,
+    /** Progress of the running substore updates. 
+     */
+    pub (crate) substore_updates : Vec<SubstoreUpdateProgress>,
+    /** Cancellation tokens of the running tasks by their names. 
+     */
+    pub (crate) running_tasks : HashMap<String, Arc<atomic::AtomicBool>>,
 }
 
 #[derive(Eq, PartialEq)]
@@ -721,7 +1680,9 @@ impl Pool {
             running_workers : 0,
             idle_workers : 0,
             paused_workers : 0,
-            queue : BinaryHeap::new()
+            queue : BinaryHeap::new(),
+            substore_updates : Vec::new(),
+            running_tasks : HashMap::new(),
         };
     }
     fn is_paused(& self) -> bool {
@@ -758,6 +1719,7 @@ impl Pool {
 
 /** Messages that communicate to the updater changes about tasks. 
  */
+#[derive(Clone)]
 pub enum TaskMessage {
     Start{name : String},
     Done{name : String},
@@ -768,6 +1730,52 @@ pub enum TaskMessage {
     Color{name : String, color : String },
 }
 
+impl TaskMessage {
+    /** Returns the message as JSON object with the message kind stored in the `event` field. 
+     */
+    pub fn to_json(& self) -> json::JsonValue {
+        let mut result = json::JsonValue::new_object();
+        match self {
+            TaskMessage::Start{name} => {
+                result["event"] = "start".into();
+                result["task"] = name.as_str().into();
+            },
+            TaskMessage::Done{name} => {
+                result["event"] = "done".into();
+                result["task"] = name.as_str().into();
+            },
+            TaskMessage::Error{name, cause} => {
+                result["event"] = "error".into();
+                result["task"] = name.as_str().into();
+                result["cause"] = cause.as_str().into();
+            },
+            TaskMessage::Progress{name, progress, max} => {
+                result["event"] = "progress".into();
+                result["task"] = name.as_str().into();
+                result["progress"] = (*progress).into();
+                result["max"] = (*max).into();
+            },
+            TaskMessage::Info{name, info} => {
+                result["event"] = "info".into();
+                result["task"] = name.as_str().into();
+                result["info"] = info.as_str().into();
+            },
+            TaskMessage::Extra{name, extra} => {
+                result["event"] = "extra".into();
+                result["task"] = name.as_str().into();
+                result["extra"] = extra.as_str().into();
+            },
+            TaskMessage::Color{name, color} => {
+                result["event"] = "color".into();
+                result["task"] = name.as_str().into();
+                result["color"] = color.as_str().into();
+            },
+        }
+        result["time"] = helpers::now().into();
+        return result;
+    }
+}
+
 /** Task info as stored on the updater's end. 
  */
 struct TaskInfo {
@@ -841,6 +1849,56 @@ impl ReporterInfo {
         };
     }
 
+    /** Updates the information with given task message. 
+     */
+    fn update(& mut self, msg : TaskMessage) {
+        match msg {
+            TaskMessage::Start{name} => {
+                assert!(self.tasks.contains_key(& name) == false, "Task already exists");
+                self.tasks.insert(name, TaskInfo::new());
+            },
+            TaskMessage::Done{name} => {
+                assert!(self.tasks.contains_key(& name) == true, "Task does not exist");
+                let mut task = self.tasks.remove(& name).unwrap();
+                task.end_time = helpers::now();
+                self.done.push((name, task));
+                self.tick_tasks_done += 1;
+            },
+            TaskMessage::Error{name, cause} => {
+                assert!(self.tasks.contains_key(& name) == true, "Task does not exist");
+                let mut task = self.tasks.remove(& name).unwrap();
+                task.end_time = helpers::now();
+                self.errors.push((name, task, cause));
+                self.tick_tasks_error += 1;
+            },
+            TaskMessage::Progress{name, progress, max} => {
+                assert!(self.tasks.contains_key(& name) == true, "Task does not exist");
+                let task = self.tasks.get_mut(& name).unwrap();    
+                task.ping = 0;
+                task.progress = progress;
+                task.progress_max = max;
+            },
+            TaskMessage::Info{name, info} => {
+                assert!(self.tasks.contains_key(& name) == true, "Task does not exist");
+                let task = self.tasks.get_mut(& name).unwrap();    
+                task.ping = 0;
+                task.info = info;
+            },
+            TaskMessage::Extra{name, extra} => {
+                assert!(self.tasks.contains_key(& name) == true, "Task does not exist");
+                let task = self.tasks.get_mut(& name).unwrap();    
+                task.ping = 0;
+                task.extra = extra;
+            },
+            TaskMessage::Color{name, color} => {
+                assert!(self.tasks.contains_key(& name) == true, "Task does not exist");
+                let task = self.tasks.get_mut(& name).unwrap();    
+                task.ping = 0;
+                task.color = color;
+            },
+        }
+    }
+
     fn get_tick_symbol(& self) -> &'static str {
         match self.tick_num {
             0 => "-",